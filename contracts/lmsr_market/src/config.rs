@@ -0,0 +1,53 @@
+use soroban_sdk::{contracttype, Address, String, Vec};
+
+/// Optional market settings supplied at initialization.
+///
+/// Grouped in one struct so new settings don't keep growing the constructor
+/// signature. Manual deployments leave fields at their neutral values.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketConfig {
+    /// Factory that deployed the market (may pause it). None for manual deployments.
+    pub factory: Option<Address>,
+    /// Prefix for get_metadata_uri (e.g. "ipfs://"). None returns the bare hash.
+    pub metadata_base_uri: Option<String>,
+    /// Protocol fee taken on every buy and sell, in basis points. 0 disables it.
+    pub protocol_fee_bps: u32,
+    /// Where the protocol fee is sent. Required when protocol_fee_bps > 0.
+    pub protocol_fee_recipient: Option<Address>,
+    /// Fee on claimed winnings in basis points, in [0, 10000]. 200 = 2%.
+    pub claim_fee_bps: i128,
+    /// Ledgers after resolution during which winnings can be claimed.
+    /// None keeps winnings claimable forever.
+    pub claim_deadline_ledgers: Option<u32>,
+    /// Address allowed to resolve the market, when resolution is delegated
+    /// away from the funding oracle. None lets the oracle resolve.
+    pub resolver: Option<Address>,
+    /// Ledgers a proposed resolution can be disputed before `finalize` locks
+    /// it in. None makes `resolve` final immediately.
+    pub dispute_window_ledgers: Option<u32>,
+    /// Extra funding buffer in basis points of the b * ln(2) minimum:
+    /// 15000 requires 1.5x. 0 requires only the minimum.
+    pub safety_multiplier_bps: u32,
+    /// Human-readable outcome names, one per outcome in outcome order
+    /// (e.g. ["Team A wins", "Team B wins"]). None keeps "YES" and "NO".
+    pub outcome_labels: Option<Vec<String>>,
+    /// Account that pays initial_funding instead of the oracle (e.g. a
+    /// treasury). None has the oracle fund the market.
+    pub funder: Option<Address>,
+    /// Smallest token amount a single buy or sell may move (scaled).
+    /// 0 allows any positive amount.
+    pub min_trade_amount: i128,
+    /// Reject `resolve` to an outcome no one holds tokens of, as a guard
+    /// against oracle mistakes.
+    pub require_nonzero_winner: bool,
+    /// Receives a share of every claim fee, e.g. to reward early traders or
+    /// liquidity providers. Required when rewards_share_bps > 0.
+    pub rewards_address: Option<Address>,
+    /// Share of each claim fee sent to rewards_address, in basis points of
+    /// the fee. The rest stays with the oracle. 0 disables rewards.
+    pub rewards_share_bps: u32,
+    /// Ledgers of trading the settlement price averages over before
+    /// resolution. None keeps TWAP_WINDOW_LEDGERS (about an hour).
+    pub twap_window_ledgers: Option<u32>,
+}
@@ -34,4 +34,40 @@ pub enum MarketError {
     StorageCorrupted = 14,
     /// Pool has insufficient funds (should not happen in normal operation)
     InsufficientPool = 15,
+    /// Invalid market configuration (e.g., self-referential addresses)
+    InvalidConfig = 16,
+    /// Trading is paused
+    Paused = 17,
+    /// Token is not in the market's accepted collateral set
+    TokenNotAccepted = 18,
+    /// Trade would push quantities beyond the range the pricing math supports
+    TradeTooLarge = 19,
+    /// Market was cancelled by the oracle
+    Cancelled = 20,
+    /// Refunds are only available on a cancelled market
+    NotCancelled = 21,
+    /// Fee outside [0, BPS_DENOMINATOR]
+    InvalidFee = 22,
+    /// No oracle transfer has been proposed
+    NoPendingOracle = 23,
+    /// Claim window after resolution has passed
+    ClaimExpired = 24,
+    /// A proposed resolution is still inside its dispute window
+    DisputeWindowOpen = 25,
+    /// The dispute window for the proposed resolution has passed
+    DisputeWindowClosed = 26,
+    /// Trade amount is below the market's minimum trade size
+    BelowMinimum = 27,
+    /// Market already has the maximum number of resolution callbacks
+    TooManyCallbacks = 28,
+    /// Winning outcome has no tokens sold and the market requires a holder
+    EmptyWinningSide = 29,
+    /// Collateral token rejected a payout transfer (e.g. deauthorized recipient)
+    TransferFailed = 30,
+    /// Position transfer where sender and recipient are the same address
+    SelfTransfer = 31,
+    /// Challenger already disputed, or the market reached MAX_DISPUTES
+    DisputeLimitReached = 32,
+    /// Basket float can't cover the base cost of a basket buy
+    InsufficientFloat = 33,
 }
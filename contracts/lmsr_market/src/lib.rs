@@ -1,14 +1,24 @@
 #![no_std]
 
+mod config;
 mod error;
 mod lmsr;
 mod storage;
+mod summary;
 
+use config::MarketConfig;
 use error::MarketError;
-use soroban_sdk::{contract, contractimpl, symbol_short, token, Address, Env, String};
-#[cfg(test)]
-use storage::SCALE_FACTOR;
-use storage::{is_valid_outcome, DataKey, BPS_DENOMINATOR, CLAIM_FEE_BPS, OUTCOME_YES};
+use soroban_sdk::{
+    contract, contractimpl, symbol_short, token, vec, Address, BytesN, Env, IntoVal, String,
+    Symbol, Vec,
+};
+use storage::{
+    is_valid_outcome, DataKey, ExtDataKey, BPS_DENOMINATOR, CERTAINTY_PRICE, CLAIM_FEE_BPS,
+    COLLATERAL_DECIMALS, MAX_COLLATERAL_DECIMALS, MAX_DISPUTES, MAX_METADATA_URI_LEN,
+    MAX_RESOLUTION_CALLBACKS, OUTCOME_COUNT, OUTCOME_NO, OUTCOME_YES, PRICE_SUM_TOLERANCE,
+    SCALE_FACTOR, SHARE_SCALE, TWAP_WINDOW_LEDGERS,
+};
+use summary::MarketSummary;
 
 /// LMSR Prediction Market Contract
 ///
@@ -35,6 +45,7 @@ impl LmsrMarket {
         liquidity_param: i128,
         metadata_hash: String,
         initial_funding: i128,
+        config: MarketConfig,
     ) {
         Self::initialize(
             env,
@@ -43,6 +54,7 @@ impl LmsrMarket {
             liquidity_param,
             metadata_hash,
             initial_funding,
+            config,
         )
         .expect("initialization failed");
     }
@@ -52,11 +64,13 @@ impl LmsrMarket {
     /// Can be called directly for manual deployment, or via constructor for factory deployment.
     ///
     /// # Arguments
-    /// * `oracle` - Address that can resolve the market
+    /// * `oracle` - Administers the market and funds it unless `config.funder`
+    ///   is set; also resolves it unless `config.resolver` is set
     /// * `collateral_token` - Token contract for collateral (e.g., EURMTL SAC)
     /// * `liquidity_param` - LMSR b parameter (scaled by 10^7)
     /// * `metadata_hash` - IPFS hash for market metadata
     /// * `initial_funding` - Initial collateral to fund the market (scaled by 10^7)
+    /// * `config` - Optional settings (see `MarketConfig`)
     pub fn initialize(
         env: Env,
         oracle: Address,
@@ -64,44 +78,47 @@ impl LmsrMarket {
         liquidity_param: i128,
         metadata_hash: String,
         initial_funding: i128,
+        config: MarketConfig,
     ) -> Result<(), MarketError> {
         // Check not already initialized
         if env.storage().instance().has(&DataKey::Oracle) {
             return Err(MarketError::AlreadyInitialized);
         }
 
-        if liquidity_param <= 0 {
-            return Err(MarketError::InvalidLiquidity);
-        }
+        Self::validate_config(
+            &env,
+            &oracle,
+            &collateral_token,
+            liquidity_param,
+            &metadata_hash,
+            initial_funding,
+            &config,
+        )?;
 
-        // Verify initial funding matches required liquidity
-        let required = lmsr::initial_liquidity(liquidity_param)?;
-        if initial_funding < required {
-            return Err(MarketError::InvalidAmount);
+        // Oracle must authorize the initialization, and so must whoever
+        // provides the initial funding
+        oracle.require_auth();
+        let funder = config.funder.clone().unwrap_or(oracle.clone());
+        if funder != oracle {
+            funder.require_auth();
         }
 
-        // Verify initial funding doesn't risk overflow in pool operations
-        // Using i128::MAX / 2 as a safe upper bound for funding
-        const MAX_FUNDING: i128 = i128::MAX / 2;
-        if initial_funding > MAX_FUNDING {
-            return Err(MarketError::Overflow);
+        // Transfers convert to the token's own scale, so read it once here
+        // rather than trusting a configured value
+        let collateral_decimals = token::Client::new(&env, &collateral_token).decimals();
+        if collateral_decimals > MAX_COLLATERAL_DECIMALS {
+            return Err(MarketError::InvalidConfig);
         }
 
-        // Oracle must authorize the initialization (they provide initial funding)
-        oracle.require_auth();
-
-        // Transfer initial funding from oracle to contract
-        // Note: token_client.transfer() may panic on failure (e.g., insufficient balance,
-        // authorization issues). These panics are appropriate as they indicate the oracle
-        // does not have sufficient funds or proper authorization.
-        let token_client = token::Client::new(&env, &collateral_token);
-        token_client.transfer(&oracle, &env.current_contract_address(), &initial_funding);
-
-        // Store contract state
+        // Store state before pulling funding: the Oracle key is the
+        // initialization guard, so it must be in place before any external call
         env.storage().instance().set(&DataKey::Oracle, &oracle);
         env.storage()
             .instance()
             .set(&DataKey::CollateralToken, &collateral_token);
+        env.storage()
+            .instance()
+            .set(&ExtDataKey::CollateralDecimals, &collateral_decimals);
         env.storage()
             .instance()
             .set(&DataKey::LiquidityParam, &liquidity_param);
@@ -110,10 +127,100 @@ impl LmsrMarket {
             .set(&DataKey::MetadataHash, &metadata_hash);
         env.storage().instance().set(&DataKey::YesSold, &0i128);
         env.storage().instance().set(&DataKey::NoSold, &0i128);
+        Self::set_current_cost(&env, lmsr::cost(0, 0, liquidity_param)?);
         env.storage()
             .instance()
             .set(&DataKey::CollateralPool, &initial_funding);
+        env.storage()
+            .instance()
+            .set(&ExtDataKey::InitialFunding, &initial_funding);
         env.storage().instance().set(&DataKey::Resolved, &false);
+        env.storage().instance().set(&DataKey::Paused, &false);
+        env.storage()
+            .instance()
+            .set(&DataKey::ClaimFeeBps, &config.claim_fee_bps);
+        if let Some(factory) = config.factory {
+            env.storage().instance().set(&DataKey::Factory, &factory);
+        }
+        if let Some(window) = config.claim_deadline_ledgers {
+            env.storage().instance().set(&DataKey::ClaimWindow, &window);
+        }
+        if let Some(resolver) = config.resolver {
+            env.storage().instance().set(&DataKey::Resolver, &resolver);
+        }
+        if let Some(window) = config.dispute_window_ledgers {
+            env.storage()
+                .instance()
+                .set(&DataKey::DisputeWindow, &window);
+        }
+        if let Some(window) = config.twap_window_ledgers {
+            env.storage()
+                .instance()
+                .set(&ExtDataKey::TwapWindow, &window);
+        }
+        if let Some(recipient) = config.protocol_fee_recipient {
+            env.storage()
+                .instance()
+                .set(&DataKey::ProtocolFeeBps, &config.protocol_fee_bps);
+            env.storage()
+                .instance()
+                .set(&DataKey::ProtocolFeeRecipient, &recipient);
+        }
+        if let Some(base_uri) = config.metadata_base_uri {
+            env.storage()
+                .instance()
+                .set(&DataKey::MetadataBaseUri, &base_uri);
+        }
+        if config.min_trade_amount > 0 {
+            env.storage()
+                .instance()
+                .set(&DataKey::MinTradeAmount, &config.min_trade_amount);
+        }
+        if config.require_nonzero_winner {
+            env.storage()
+                .instance()
+                .set(&DataKey::RequireNonZeroWinner, &true);
+        }
+        if let Some(rewards_address) = config.rewards_address {
+            env.storage()
+                .instance()
+                .set(&ExtDataKey::RewardsAddress, &rewards_address);
+            env.storage()
+                .instance()
+                .set(&ExtDataKey::RewardsShareBps, &config.rewards_share_bps);
+        }
+        if let Some(labels) = config.outcome_labels {
+            env.storage()
+                .instance()
+                .set(&DataKey::OutcomeLabels, &labels);
+        }
+
+        // Start the TWAP accumulator at the creation ledger
+        let ledger = env.ledger().sequence();
+        env.storage()
+            .instance()
+            .set(&DataKey::CreatedLedger, &ledger);
+        env.storage()
+            .instance()
+            .set(&DataKey::PriceCumulative, &0i128);
+        env.storage()
+            .instance()
+            .set(&DataKey::PriceLastLedger, &ledger);
+        env.storage()
+            .instance()
+            .set(&DataKey::TwapCheckpoint, &(ledger, 0i128));
+
+        // Transfer initial funding from the funder to contract
+        // Note: token_client.transfer() may panic on failure (e.g., insufficient balance,
+        // authorization issues). The panic aborts the invocation and the host rolls back
+        // the state written above, so a failed transfer never leaves the market half-set.
+        let token_client = token::Client::new(&env, &collateral_token);
+        Self::transfer_in(&env, &token_client, &funder, initial_funding)?;
+
+        env.events().publish(
+            (symbol_short!("init"), oracle),
+            (initial_funding, collateral_token),
+        );
 
         Ok(())
     }
@@ -139,6 +246,8 @@ impl LmsrMarket {
     ) -> Result<i128, MarketError> {
         Self::require_initialized(&env)?;
         Self::require_not_resolved(&env)?;
+        Self::require_not_paused(&env)?;
+        Self::require_no_proposal(&env)?;
 
         if !is_valid_outcome(outcome) {
             return Err(MarketError::InvalidOutcome);
@@ -146,6 +255,7 @@ impl LmsrMarket {
         if amount <= 0 {
             return Err(MarketError::InvalidAmount);
         }
+        Self::require_min_trade(&env, amount)?;
 
         // User must authorize the buy
         user.require_auth();
@@ -167,95 +277,91 @@ impl LmsrMarket {
             .get(&DataKey::NoSold)
             .ok_or(MarketError::StorageCorrupted)?;
 
-        // Calculate cost
-        let cost = lmsr::calculate_buy_cost(q_yes, q_no, amount, outcome, b)?;
+        // Calculate cost, plus the protocol fee paid on top of it
+        let cost_before = Self::current_cost(&env, q_yes, q_no, b)?;
+        let cost = lmsr::calculate_buy_cost_from(cost_before, q_yes, q_no, amount, outcome, b)?;
+        let (fee, fee_recipient) = Self::protocol_fee(&env, cost)?;
+        let total_cost = cost.checked_add(fee).ok_or(MarketError::Overflow)?;
 
-        if cost > max_cost {
+        if total_cost > max_cost {
             return Err(MarketError::SlippageExceeded);
         }
 
+        // Weight the elapsed period by the price in effect before this trade
+        Self::update_price_accumulator(&env, q_yes, q_no, b)?;
+
+        // Update state before calling the token (checks-effects-interactions)
+        Self::record_buy(&env, &user, outcome, amount, cost, q_yes, q_no)?;
+        Self::set_current_cost(
+            &env,
+            cost_before.checked_add(cost).ok_or(MarketError::Overflow)?,
+        );
+        Self::add_cost_basis(&env, &user, outcome, fee)?;
+        Self::add_fee_revenue(&env, DataKey::TradeFeeRevenue, fee)?;
+
         // Transfer collateral from user to contract
         // Note: token_client.transfer() may panic on failure (e.g., insufficient balance,
         // authorization issues). These panics are appropriate as they indicate the user
-        // does not have sufficient funds or proper authorization.
+        // does not have sufficient funds or proper authorization; the host
+        // rolls back the state written above.
         let collateral_token: Address = env
             .storage()
             .instance()
             .get(&DataKey::CollateralToken)
             .ok_or(MarketError::StorageCorrupted)?;
         let token_client = token::Client::new(&env, &collateral_token);
-        token_client.transfer(&user, &env.current_contract_address(), &cost);
-
-        // Update state
-        if outcome == OUTCOME_YES {
-            env.storage()
-                .instance()
-                .set(&DataKey::YesSold, &(q_yes + amount));
-        } else {
-            env.storage()
-                .instance()
-                .set(&DataKey::NoSold, &(q_no + amount));
+        Self::transfer_in(&env, &token_client, &user, cost)?;
+        if let Some(recipient) = fee_recipient {
+            Self::transfer_fee(&env, &token_client, &user, &recipient, fee)?;
         }
 
-        let pool: i128 = env
-            .storage()
-            .instance()
-            .get(&DataKey::CollateralPool)
-            .ok_or(MarketError::StorageCorrupted)?;
-        env.storage()
-            .instance()
-            .set(&DataKey::CollateralPool, &(pool + cost));
-
-        // Update user balance
-        let balance_key = DataKey::UserBalance(user.clone(), outcome);
-        let current_balance: i128 = env.storage().instance().get(&balance_key).unwrap_or(0);
-        env.storage()
-            .instance()
-            .set(&balance_key, &(current_balance + amount));
-
-        env.events()
-            .publish((symbol_short!("buy"), user, outcome), (amount, cost));
+        env.events().publish(
+            (symbol_short!("buy"), user, outcome),
+            (amount, total_cost, collateral_token),
+        );
 
-        Ok(cost)
+        Ok(total_cost)
     }
 
-    /// Sell outcome tokens.
+    /// Buy several outcome legs in one call, e.g. to seed both sides.
+    ///
+    /// Each leg is priced on the quantities left by the previous one, so the
+    /// total matches the same buys made one after another. The slippage cap
+    /// applies to the total, collateral moves in a single transfer, and an
+    /// invalid leg reverts the whole batch.
     ///
     /// # Arguments
-    /// * `user` - User selling tokens (must authorize)
-    /// * `outcome` - 0 for YES, 1 for NO
-    /// * `amount` - Amount of tokens to sell (scaled by 10^7)
-    /// * `min_return` - Minimum collateral to receive (slippage protection)
+    /// * `amounts` - (outcome, amount) legs, applied in order
+    /// * `max_total_cost` - Maximum collateral for all legs including fees
     ///
     /// # Returns
-    /// Actual collateral received
-    pub fn sell(
+    /// Total cost paid in collateral
+    pub fn buy_batch(
         env: Env,
         user: Address,
-        outcome: u32,
-        amount: i128,
-        min_return: i128,
+        amounts: Vec<(u32, i128)>,
+        max_total_cost: i128,
     ) -> Result<i128, MarketError> {
         Self::require_initialized(&env)?;
         Self::require_not_resolved(&env)?;
+        Self::require_not_paused(&env)?;
+        Self::require_no_proposal(&env)?;
 
-        if !is_valid_outcome(outcome) {
-            return Err(MarketError::InvalidOutcome);
-        }
-        if amount <= 0 {
+        if amounts.is_empty() {
             return Err(MarketError::InvalidAmount);
         }
+        for (outcome, amount) in amounts.iter() {
+            if !is_valid_outcome(outcome) {
+                return Err(MarketError::InvalidOutcome);
+            }
+            if amount <= 0 {
+                return Err(MarketError::InvalidAmount);
+            }
+            Self::require_min_trade(&env, amount)?;
+        }
 
         user.require_auth();
 
-        // Check user has sufficient balance
-        let balance_key = DataKey::UserBalance(user.clone(), outcome);
-        let current_balance: i128 = env.storage().instance().get(&balance_key).unwrap_or(0);
-        if current_balance < amount {
-            return Err(MarketError::InsufficientBalance);
-        }
-
-        // Get current state
         let b: i128 = env
             .storage()
             .instance()
@@ -272,223 +378,338 @@ impl LmsrMarket {
             .get(&DataKey::NoSold)
             .ok_or(MarketError::StorageCorrupted)?;
 
-        // Calculate return
-        let return_amount = lmsr::calculate_sell_return(q_yes, q_no, amount, outcome, b)?;
-
-        if return_amount < min_return {
-            return Err(MarketError::ReturnTooLow);
+        // Price every leg before touching state: (cost, fee) per leg
+        let mut legs: Vec<(i128, i128)> = Vec::new(&env);
+        let (mut leg_q_yes, mut leg_q_no) = (q_yes, q_no);
+        let mut running_cost = Self::current_cost(&env, q_yes, q_no, b)?;
+        let mut total_cost: i128 = 0;
+        let mut total_fee: i128 = 0;
+        let mut fee_recipient = None;
+        for (outcome, amount) in amounts.iter() {
+            let cost = lmsr::calculate_buy_cost_from(
+                running_cost,
+                leg_q_yes,
+                leg_q_no,
+                amount,
+                outcome,
+                b,
+            )?;
+            let (fee, recipient) = Self::protocol_fee(&env, cost)?;
+            if recipient.is_some() {
+                fee_recipient = recipient;
+            }
+            legs.push_back((cost, fee));
+
+            running_cost = running_cost
+                .checked_add(cost)
+                .ok_or(MarketError::Overflow)?;
+            total_cost = total_cost.checked_add(cost).ok_or(MarketError::Overflow)?;
+            total_fee = total_fee.checked_add(fee).ok_or(MarketError::Overflow)?;
+            if outcome == OUTCOME_YES {
+                leg_q_yes = leg_q_yes.checked_add(amount).ok_or(MarketError::Overflow)?;
+            } else {
+                leg_q_no = leg_q_no.checked_add(amount).ok_or(MarketError::Overflow)?;
+            }
         }
 
-        // Update state
-        if outcome == OUTCOME_YES {
-            env.storage()
-                .instance()
-                .set(&DataKey::YesSold, &(q_yes - amount));
-        } else {
-            env.storage()
-                .instance()
-                .set(&DataKey::NoSold, &(q_no - amount));
+        let grand_total = total_cost
+            .checked_add(total_fee)
+            .ok_or(MarketError::Overflow)?;
+        if grand_total > max_total_cost {
+            return Err(MarketError::SlippageExceeded);
         }
 
-        let pool: i128 = env
+        // All legs execute at this ledger, so only the pre-batch price accrues
+        Self::update_price_accumulator(&env, q_yes, q_no, b)?;
+
+        let collateral_token: Address = env
             .storage()
             .instance()
-            .get(&DataKey::CollateralPool)
+            .get(&DataKey::CollateralToken)
             .ok_or(MarketError::StorageCorrupted)?;
 
-        // Guard against pool underflow (should not happen with correct LMSR math)
-        if pool < return_amount {
-            return Err(MarketError::InsufficientPool);
+        // Update state before calling the token (checks-effects-interactions)
+        let (mut leg_q_yes, mut leg_q_no) = (q_yes, q_no);
+        for ((outcome, amount), (cost, fee)) in amounts.iter().zip(legs.iter()) {
+            Self::record_buy(&env, &user, outcome, amount, cost, leg_q_yes, leg_q_no)?;
+            Self::add_cost_basis(&env, &user, outcome, fee)?;
+            Self::add_fee_revenue(&env, DataKey::TradeFeeRevenue, fee)?;
+            if outcome == OUTCOME_YES {
+                leg_q_yes += amount;
+            } else {
+                leg_q_no += amount;
+            }
+
+            env.events().publish(
+                (symbol_short!("buy"), user.clone(), outcome),
+                (amount, cost + fee, collateral_token.clone()),
+            );
         }
+        Self::set_current_cost(&env, running_cost);
 
-        env.storage()
-            .instance()
-            .set(&DataKey::CollateralPool, &(pool - return_amount));
-
-        // Update user balance
-        env.storage()
-            .instance()
-            .set(&balance_key, &(current_balance - amount));
-
-        // Transfer collateral to user
+        // Transfer collateral from user to contract
         // Note: token_client.transfer() may panic on failure (e.g., insufficient balance,
-        // authorization issues). These panics are appropriate as they indicate contract
-        // state inconsistency or external token contract issues.
-        let collateral_token: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::CollateralToken)
-            .ok_or(MarketError::StorageCorrupted)?;
+        // authorization issues). These panics are appropriate as they indicate the user
+        // does not have sufficient funds or proper authorization; the host
+        // rolls back the state written above.
         let token_client = token::Client::new(&env, &collateral_token);
-        token_client.transfer(&env.current_contract_address(), &user, &return_amount);
-
-        env.events().publish(
-            (symbol_short!("sell"), user, outcome),
-            (amount, return_amount),
-        );
+        Self::transfer_in(&env, &token_client, &user, total_cost)?;
+        if let Some(recipient) = fee_recipient {
+            Self::transfer_fee(&env, &token_client, &user, &recipient, total_fee)?;
+        }
 
-        Ok(return_amount)
+        Ok(grand_total)
     }
 
-    /// Resolve the market (oracle only).
+    /// Buy as many outcome tokens as a fixed collateral budget allows.
+    ///
+    /// Binary-searches the largest amount whose cost plus protocol fee fits in
+    /// `budget`, then buys it as `buy` would.
     ///
     /// # Arguments
-    /// * `oracle` - Must match the oracle set at initialization
-    /// * `winning_outcome` - 0 for YES, 1 for NO
-    pub fn resolve(env: Env, oracle: Address, winning_outcome: u32) -> Result<(), MarketError> {
+    /// * `user` - User buying tokens (must authorize)
+    /// * `outcome` - 0 for YES, 1 for NO
+    /// * `budget` - Maximum collateral to spend, including the protocol fee
+    /// * `min_tokens` - Minimum tokens to receive (slippage protection)
+    ///
+    /// # Returns
+    /// Amount of tokens bought
+    pub fn buy_with_budget(
+        env: Env,
+        user: Address,
+        outcome: u32,
+        budget: i128,
+        min_tokens: i128,
+    ) -> Result<i128, MarketError> {
         Self::require_initialized(&env)?;
         Self::require_not_resolved(&env)?;
+        Self::require_not_paused(&env)?;
+        Self::require_no_proposal(&env)?;
 
-        if !is_valid_outcome(winning_outcome) {
+        if !is_valid_outcome(outcome) {
             return Err(MarketError::InvalidOutcome);
         }
-
-        // Verify caller is oracle
-        let stored_oracle: Address = env
-            .storage()
-            .instance()
-            .get(&DataKey::Oracle)
-            .ok_or(MarketError::StorageCorrupted)?;
-        if oracle != stored_oracle {
-            return Err(MarketError::Unauthorized);
+        let amount = Self::amount_for_budget(&env, outcome, budget)?;
+        if amount < min_tokens {
+            return Err(MarketError::ReturnTooLow);
         }
-        oracle.require_auth();
-
-        // Mark as resolved
-        env.storage().instance().set(&DataKey::Resolved, &true);
-        env.storage()
-            .instance()
-            .set(&DataKey::WinningOutcome, &winning_outcome);
-
-        // Track total unclaimed winning tokens for withdraw_remaining protection
-        let winning_tokens: i128 = if winning_outcome == OUTCOME_YES {
-            env.storage()
-                .instance()
-                .get(&DataKey::YesSold)
-                .ok_or(MarketError::StorageCorrupted)?
-        } else {
-            env.storage()
-                .instance()
-                .get(&DataKey::NoSold)
-                .ok_or(MarketError::StorageCorrupted)?
-        };
-        env.storage()
-            .instance()
-            .set(&DataKey::UnclaimedWinningTokens, &winning_tokens);
-
-        env.events()
-            .publish((symbol_short!("resolve"), oracle), winning_outcome);
 
-        Ok(())
+        Self::buy(env, user, outcome, amount, budget)?;
+        Ok(amount)
     }
 
-    /// Claim winnings after market resolution.
-    /// Each winning token is redeemable for 1 unit of collateral (1:1 redemption),
-    /// minus a 2% fee that stays in the pool (recoverable by oracle via withdraw_remaining).
-    /// Note: Losing tokens have zero value and are not claimed.
+    /// Buy outcome tokens with slippage protection given as a limit price.
+    ///
+    /// Same as `buy`, but reverts if the average price per token, protocol
+    /// fee included, would exceed `max_avg_price`. Easier to set than an
+    /// absolute `max_cost` when `amount` is computed on the fly.
     ///
     /// # Arguments
-    /// * `user` - User claiming (must authorize)
+    /// * `max_avg_price` - Highest acceptable cost per token, scaled by 10^7
+    ///   (6_000_000 = 0.6)
     ///
     /// # Returns
-    /// Amount of collateral claimed (after fee deduction)
-    pub fn claim(env: Env, user: Address) -> Result<i128, MarketError> {
+    /// Actual cost paid in collateral
+    pub fn buy_limit_price(
+        env: Env,
+        user: Address,
+        outcome: u32,
+        amount: i128,
+        max_avg_price: i128,
+    ) -> Result<i128, MarketError> {
         Self::require_initialized(&env)?;
-        Self::require_resolved(&env)?;
+        Self::require_not_resolved(&env)?;
+        Self::require_not_paused(&env)?;
+        Self::require_no_proposal(&env)?;
 
-        user.require_auth();
+        let (total_cost, _) = Self::get_quote(env.clone(), outcome, amount)?;
+        let avg_price = total_cost
+            .checked_mul(SCALE_FACTOR)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(amount)
+            .ok_or(MarketError::Overflow)?;
+        if avg_price > max_avg_price {
+            return Err(MarketError::SlippageExceeded);
+        }
 
-        let winning_outcome: u32 = env
-            .storage()
-            .instance()
-            .get(&DataKey::WinningOutcome)
-            .ok_or(MarketError::StorageCorrupted)?;
+        Self::buy(env, user, outcome, amount, total_cost)
+    }
 
-        // Get user's winning token balance
-        let balance_key = DataKey::UserBalance(user.clone(), winning_outcome);
-        let winning_balance: i128 = env.storage().instance().get(&balance_key).unwrap_or(0);
+    /// Get the average price per token when spending `budget` via `buy_with_budget`.
+    ///
+    /// Compare with `get_price` to see the slippage a budget buy would incur.
+    ///
+    /// # Returns
+    /// Average fill price scaled by 10^7 (budget * 10^7 / tokens)
+    pub fn get_avg_price_for_budget(
+        env: Env,
+        outcome: u32,
+        budget: i128,
+    ) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
 
-        if winning_balance <= 0 {
-            return Err(MarketError::NothingToClaim);
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
         }
 
-        // Each winning token is worth 1 unit of collateral
-        let gross_payout = winning_balance;
-
-        // Calculate fee (2% = 200 basis points)
-        // Fee stays in pool; oracle recovers via withdraw_remaining()
-        // Note: Integer division truncates, so amounts < 50 units have zero fee (dust-level)
-        let fee = gross_payout
-            .checked_mul(CLAIM_FEE_BPS)
+        let shares = Self::amount_for_budget(&env, outcome, budget)?;
+        budget
+            .checked_mul(SCALE_FACTOR)
             .ok_or(MarketError::Overflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(MarketError::Overflow)?;
-        let user_payout = gross_payout.checked_sub(fee).ok_or(MarketError::Overflow)?;
+            .checked_div(shares)
+            .ok_or(MarketError::Overflow)
+    }
 
-        // Zero out user's balance
-        env.storage().instance().set(&balance_key, &0i128);
+    /// Buy outcome tokens paying with an accepted basket token instead of the base collateral.
+    ///
+    /// The LMSR cost is computed in base collateral units and converted at the
+    /// oracle-set rate (rounded up in the market's favor). The trade settles
+    /// to base immediately: the cost moves from the oracle's basket float
+    /// (see `fund_basket_float`) into the pool, and the basket tokens go to
+    /// the oracle. A float too small for the cost returns InsufficientFloat.
+    /// Sells and claims always pay out in the base collateral token.
+    ///
+    /// # Arguments
+    /// * `user` - User buying tokens (must authorize)
+    /// * `token` - Accepted basket token to pay with (or the base collateral token)
+    /// * `outcome` - 0 for YES, 1 for NO
+    /// * `amount` - Amount of outcome tokens to buy (scaled by 10^7)
+    /// * `max_cost` - Maximum amount of `token` willing to pay (slippage protection)
+    ///
+    /// # Returns
+    /// Amount of `token` paid
+    pub fn buy_with(
+        env: Env,
+        user: Address,
+        token: Address,
+        outcome: u32,
+        amount: i128,
+        max_cost: i128,
+    ) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
 
-        // Decrement unclaimed winning tokens tracker
-        let unclaimed: i128 = env
+        let collateral_token: Address = env
             .storage()
             .instance()
-            .get(&DataKey::UnclaimedWinningTokens)
-            .unwrap_or(0);
-        env.storage().instance().set(
-            &DataKey::UnclaimedWinningTokens,
-            &(unclaimed - winning_balance),
-        );
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if token == collateral_token {
+            return Self::buy(env, user, outcome, amount, max_cost);
+        }
 
-        // Update collateral pool (only deduct user_payout, fee stays in pool)
-        let pool: i128 = env
+        Self::require_not_resolved(&env)?;
+        Self::require_not_paused(&env)?;
+        Self::require_no_proposal(&env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+        if amount <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+        Self::require_min_trade(&env, amount)?;
+
+        let rate: i128 = env
             .storage()
             .instance()
-            .get(&DataKey::CollateralPool)
+            .get(&DataKey::AcceptedTokenRate(token.clone()))
+            .ok_or(MarketError::TokenNotAccepted)?;
+
+        user.require_auth();
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
             .ok_or(MarketError::StorageCorrupted)?;
 
-        // Guard against pool underflow (should not happen with correct market operation)
-        if pool < user_payout {
-            return Err(MarketError::InsufficientPool);
+        // Cost and protocol fee in base units, converted to basket token units
+        let cost_before = Self::current_cost(&env, q_yes, q_no, b)?;
+        let cost = lmsr::calculate_buy_cost_from(cost_before, q_yes, q_no, amount, outcome, b)?;
+        let (fee, fee_recipient) = Self::protocol_fee(&env, cost)?;
+        let token_cost = Self::base_to_token_units(cost, rate)?;
+        let token_fee = Self::base_to_token_units(fee, rate)?;
+        let token_total = token_cost
+            .checked_add(token_fee)
+            .ok_or(MarketError::Overflow)?;
+
+        if token_total > max_cost {
+            return Err(MarketError::SlippageExceeded);
         }
 
+        Self::update_price_accumulator(&env, q_yes, q_no, b)?;
+
+        // Update state before calling the token (checks-effects-interactions)
+        let float: i128 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::BasketFloat)
+            .unwrap_or(0);
+        if float < cost {
+            return Err(MarketError::InsufficientFloat);
+        }
         env.storage()
             .instance()
-            .set(&DataKey::CollateralPool, &(pool - user_payout));
+            .set(&ExtDataKey::BasketFloat, &(float - cost));
+
+        Self::record_buy(&env, &user, outcome, amount, cost, q_yes, q_no)?;
+        Self::set_current_cost(
+            &env,
+            cost_before.checked_add(cost).ok_or(MarketError::Overflow)?,
+        );
+        Self::add_cost_basis(&env, &user, outcome, fee)?;
+        Self::add_fee_revenue(&env, DataKey::TradeFeeRevenue, fee)?;
 
-        // Transfer collateral to user (minus fee)
         // Note: token_client.transfer() may panic on failure (e.g., insufficient balance,
-        // authorization issues). These panics are appropriate as they indicate contract
-        // state inconsistency or external token contract issues.
-        let collateral_token: Address = env
+        // authorization issues). These panics are appropriate as they indicate the user
+        // does not have sufficient funds or proper authorization; the host
+        // rolls back the state written above.
+        let stored_oracle: Address = env
             .storage()
             .instance()
-            .get(&DataKey::CollateralToken)
+            .get(&DataKey::Oracle)
             .ok_or(MarketError::StorageCorrupted)?;
-        let token_client = token::Client::new(&env, &collateral_token);
-        token_client.transfer(&env.current_contract_address(), &user, &user_payout);
+        let token_client = token::Client::new(&env, &token);
+        token_client.transfer(&user, &stored_oracle, &token_cost);
+        if let Some(recipient) = fee_recipient {
+            token_client.transfer(&user, &recipient, &token_fee);
+        }
 
-        env.events()
-            .publish((symbol_short!("claim"), user), user_payout);
+        let total_cost = cost.checked_add(fee).ok_or(MarketError::Overflow)?;
+        env.events().publish(
+            (symbol_short!("buy"), user, outcome),
+            (amount, total_cost, collateral_token),
+        );
 
-        Ok(user_payout)
+        Ok(token_total)
     }
 
-    /// Withdraw remaining pool after market resolution (oracle only).
-    ///
-    /// Withdraws only the excess funds (losers' bets + fees) while reserving
-    /// enough collateral for unclaimed winning tokens. This prevents the oracle
-    /// from withdrawing funds that winners haven't claimed yet.
+    /// Accept a basket token at a fixed rate (oracle only).
     ///
     /// # Arguments
     /// * `oracle` - Must match the oracle set at initialization
-    ///
-    /// # Returns
-    /// Amount of collateral withdrawn
-    pub fn withdraw_remaining(env: Env, oracle: Address) -> Result<i128, MarketError> {
+    /// * `token` - Token to accept in `buy_with`
+    /// * `rate` - Base collateral units per token unit, scaled by 10^7
+    ///   (10_000_000 = 1:1). A rate of 0 stops accepting the token.
+    pub fn set_accepted_token(
+        env: Env,
+        oracle: Address,
+        token: Address,
+        rate: i128,
+    ) -> Result<(), MarketError> {
         Self::require_initialized(&env)?;
-        Self::require_resolved(&env)?;
+        Self::require_not_resolved(&env)?;
 
-        // Verify caller is oracle
         let stored_oracle: Address = env
             .storage()
             .instance()
@@ -499,135 +720,142 @@ impl LmsrMarket {
         }
         oracle.require_auth();
 
-        // Get remaining pool
-        let pool: i128 = env
-            .storage()
-            .instance()
-            .get(&DataKey::CollateralPool)
-            .ok_or(MarketError::StorageCorrupted)?;
-
-        // Calculate reserved amount for unclaimed winning tokens
-        // Each unclaimed token needs (100% - 2% fee) = 98% of collateral reserved
-        let unclaimed: i128 = env
-            .storage()
-            .instance()
-            .get(&DataKey::UnclaimedWinningTokens)
-            .unwrap_or(0);
-        let reserved = unclaimed
-            .checked_mul(BPS_DENOMINATOR - CLAIM_FEE_BPS)
-            .ok_or(MarketError::Overflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(MarketError::Overflow)?;
-
-        // Only withdraw excess (pool minus reserved for unclaimed)
-        let withdrawable = pool.checked_sub(reserved).ok_or(MarketError::Overflow)?;
-
-        if withdrawable <= 0 {
-            return Err(MarketError::NothingToClaim);
-        }
-
-        // Update pool to reserved amount (for future claims)
-        env.storage()
-            .instance()
-            .set(&DataKey::CollateralPool, &reserved);
-
-        // Transfer withdrawable to oracle
         let collateral_token: Address = env
             .storage()
             .instance()
             .get(&DataKey::CollateralToken)
             .ok_or(MarketError::StorageCorrupted)?;
-        let token_client = token::Client::new(&env, &collateral_token);
-        token_client.transfer(&env.current_contract_address(), &oracle, &withdrawable);
+        if rate < 0 || token == collateral_token || token == env.current_contract_address() {
+            return Err(MarketError::InvalidConfig);
+        }
 
-        env.events()
-            .publish((symbol_short!("withdraw"), oracle), withdrawable);
+        let rate_key = DataKey::AcceptedTokenRate(token);
+        if rate == 0 {
+            env.storage().instance().remove(&rate_key);
+        } else {
+            env.storage().instance().set(&rate_key, &rate);
+        }
 
-        Ok(withdrawable)
+        Ok(())
     }
 
-    /// Get the current price of an outcome.
+    /// Deposit base collateral for `buy_with` to settle basket buys against
+    /// (oracle only).
+    ///
+    /// Each basket buy moves its base cost from this float into the pool and
+    /// pays the oracle in basket tokens, so the pool only ever counts base
+    /// collateral the market actually holds.
     ///
     /// # Returns
-    /// Price scaled by 10^7 (5_000_000 = 0.5 = 50%)
-    pub fn get_price(env: Env, outcome: u32) -> Result<i128, MarketError> {
+    /// The float after the deposit
+    pub fn fund_basket_float(env: Env, oracle: Address, amount: i128) -> Result<i128, MarketError> {
         Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
 
-        let b: i128 = env
+        let stored_oracle: Address = env
             .storage()
             .instance()
-            .get(&DataKey::LiquidityParam)
+            .get(&DataKey::Oracle)
             .ok_or(MarketError::StorageCorrupted)?;
-        let q_yes: i128 = env
-            .storage()
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        if amount <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        let float = Self::get_basket_float(env.clone())
+            .checked_add(amount)
+            .ok_or(MarketError::Overflow)?;
+        env.storage()
             .instance()
-            .get(&DataKey::YesSold)
-            .ok_or(MarketError::StorageCorrupted)?;
-        let q_no: i128 = env
+            .set(&ExtDataKey::BasketFloat, &float);
+
+        let collateral_token: Address = env
             .storage()
             .instance()
-            .get(&DataKey::NoSold)
+            .get(&DataKey::CollateralToken)
             .ok_or(MarketError::StorageCorrupted)?;
-
-        lmsr::calculate_price(q_yes, q_no, outcome, b)
+        Self::transfer_in(
+            &env,
+            &token::Client::new(&env, &collateral_token),
+            &oracle,
+            amount,
+        )?;
+
+        Ok(float)
     }
 
-    /// Get a quote for buying tokens.
+    /// Withdraw base collateral from the basket float (oracle only).
+    ///
+    /// The float is not part of the pool, so it can be withdrawn at any time,
+    /// including after resolution or cancellation.
     ///
     /// # Returns
-    /// (cost, price_after) both scaled by 10^7
-    pub fn get_quote(env: Env, outcome: u32, amount: i128) -> Result<(i128, i128), MarketError> {
+    /// The float left after the withdrawal
+    pub fn withdraw_basket_float(
+        env: Env,
+        oracle: Address,
+        amount: i128,
+    ) -> Result<i128, MarketError> {
         Self::require_initialized(&env)?;
-        Self::require_not_resolved(&env)?;
-
-        if !is_valid_outcome(outcome) {
-            return Err(MarketError::InvalidOutcome);
-        }
-        if amount <= 0 {
-            return Err(MarketError::InvalidAmount);
-        }
 
-        let b: i128 = env
+        let stored_oracle: Address = env
             .storage()
             .instance()
-            .get(&DataKey::LiquidityParam)
+            .get(&DataKey::Oracle)
             .ok_or(MarketError::StorageCorrupted)?;
-        let q_yes: i128 = env
-            .storage()
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        let float = Self::get_basket_float(env.clone());
+        if amount <= 0 || amount > float {
+            return Err(MarketError::InvalidAmount);
+        }
+        env.storage()
             .instance()
-            .get(&DataKey::YesSold)
-            .ok_or(MarketError::StorageCorrupted)?;
-        let q_no: i128 = env
+            .set(&ExtDataKey::BasketFloat, &(float - amount));
+
+        let collateral_token: Address = env
             .storage()
             .instance()
-            .get(&DataKey::NoSold)
+            .get(&DataKey::CollateralToken)
             .ok_or(MarketError::StorageCorrupted)?;
-
-        let cost = lmsr::calculate_buy_cost(q_yes, q_no, amount, outcome, b)?;
-
-        // Calculate price after purchase
-        let (new_q_yes, new_q_no) = if outcome == OUTCOME_YES {
-            (q_yes + amount, q_no)
-        } else {
-            (q_yes, q_no + amount)
-        };
-
-        let price_after = lmsr::calculate_price(new_q_yes, new_q_no, outcome, b)?;
-
-        Ok((cost, price_after))
+        Self::transfer_out(
+            &env,
+            &token::Client::new(&env, &collateral_token),
+            &oracle,
+            amount,
+        )?;
+
+        Ok(float - amount)
     }
 
-    /// Get a quote for selling tokens.
+    /// Sell outcome tokens.
+    ///
+    /// # Arguments
+    /// * `user` - User selling tokens (must authorize)
+    /// * `outcome` - 0 for YES, 1 for NO
+    /// * `amount` - Amount of tokens to sell (scaled by 10^7)
+    /// * `min_return` - Minimum collateral to receive (slippage protection)
     ///
     /// # Returns
-    /// (return_amount, price_after) both scaled by 10^7
-    pub fn get_sell_quote(
+    /// Actual collateral received
+    pub fn sell(
         env: Env,
+        user: Address,
         outcome: u32,
         amount: i128,
-    ) -> Result<(i128, i128), MarketError> {
+        min_return: i128,
+    ) -> Result<i128, MarketError> {
         Self::require_initialized(&env)?;
         Self::require_not_resolved(&env)?;
+        Self::require_not_paused(&env)?;
+        Self::require_no_proposal(&env)?;
 
         if !is_valid_outcome(outcome) {
             return Err(MarketError::InvalidOutcome);
@@ -636,6 +864,21 @@ impl LmsrMarket {
             return Err(MarketError::InvalidAmount);
         }
 
+        user.require_auth();
+
+        // Check user has sufficient balance
+        let balance_key = DataKey::UserBalance(user.clone(), outcome);
+        let current_balance: i128 = env.storage().instance().get(&balance_key).unwrap_or(0);
+        if current_balance < amount {
+            return Err(MarketError::InsufficientBalance);
+        }
+        // Selling a whole position is always allowed, so a remainder below
+        // the minimum can't get stuck
+        if amount < current_balance {
+            Self::require_min_trade(&env, amount)?;
+        }
+
+        // Get current state
         let b: i128 = env
             .storage()
             .instance()
@@ -652,1006 +895,7966 @@ impl LmsrMarket {
             .get(&DataKey::NoSold)
             .ok_or(MarketError::StorageCorrupted)?;
 
-        let return_amount = lmsr::calculate_sell_return(q_yes, q_no, amount, outcome, b)?;
+        // Calculate return; the protocol fee comes out of it
+        let cost_before = Self::current_cost(&env, q_yes, q_no, b)?;
+        let return_amount =
+            lmsr::calculate_sell_return_from(cost_before, q_yes, q_no, amount, outcome, b)?;
+        let (fee, fee_recipient) = Self::protocol_fee(&env, return_amount)?;
+        let net_return = return_amount
+            .checked_sub(fee)
+            .ok_or(MarketError::Overflow)?;
 
-        // Calculate price after sale
-        let (new_q_yes, new_q_no) = if outcome == OUTCOME_YES {
-            (q_yes - amount, q_no)
-        } else {
-            (q_yes, q_no - amount)
-        };
+        if net_return < min_return {
+            return Err(MarketError::ReturnTooLow);
+        }
 
-        let price_after = lmsr::calculate_price(new_q_yes, new_q_no, outcome, b)?;
+        // Weight the elapsed period by the price in effect before this trade
+        Self::update_price_accumulator(&env, q_yes, q_no, b)?;
 
-        Ok((return_amount, price_after))
-    }
+        // Update state
+        if outcome == OUTCOME_YES {
+            env.storage()
+                .instance()
+                .set(&DataKey::YesSold, &(q_yes - amount));
+        } else {
+            env.storage()
+                .instance()
+                .set(&DataKey::NoSold, &(q_no - amount));
+        }
+        Self::set_current_cost(&env, cost_before - return_amount);
 
-    /// Get user's token balance for an outcome.
-    pub fn get_balance(env: Env, user: Address, outcome: u32) -> i128 {
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        // Guard against pool underflow (should not happen with correct LMSR math)
+        if pool < return_amount {
+            return Err(MarketError::InsufficientPool);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &(pool - return_amount));
+
+        // Update user balance
+        env.storage()
+            .instance()
+            .set(&balance_key, &(current_balance - amount));
+        Self::update_holder_count(&env, outcome, current_balance, current_balance - amount);
+        Self::release_callback_if_flat(&env, &user);
+
+        // Realize P&L against the average cost of the tokens sold
+        let basis_sold = Self::reduce_cost_basis(&env, &user, outcome, amount, current_balance)?;
+        Self::add_realized_pnl(&env, &user, net_return - basis_sold)?;
+        Self::add_fee_revenue(&env, DataKey::TradeFeeRevenue, fee)?;
+        Self::record_trade(&env, return_amount, ExtDataKey::SellCount)?;
+
+        // Transfer collateral to user
+        // A rejected transfer (e.g. a deauthorized recipient) returns TransferFailed,
+        // which rolls back the state changes above
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let token_client = token::Client::new(&env, &collateral_token);
+        Self::transfer_out(&env, &token_client, &user, net_return)?;
+        if let Some(recipient) = fee_recipient {
+            Self::transfer_out(&env, &token_client, &recipient, fee)?;
+        }
+
+        env.events().publish(
+            (symbol_short!("sell"), user, outcome),
+            (amount, net_return, collateral_token),
+        );
+
+        Ok(net_return)
+    }
+
+    /// Sell a user's entire balance of an outcome.
+    ///
+    /// Reads the balance inside the same call, so the amount cannot go stale
+    /// between a balance lookup and the sell.
+    ///
+    /// # Returns
+    /// Collateral returned, scaled by 10^7
+    pub fn sell_all(
+        env: Env,
+        user: Address,
+        outcome: u32,
+        min_return: i128,
+    ) -> Result<i128, MarketError> {
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+        let balance: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::UserBalance(user.clone(), outcome))
+            .unwrap_or(0);
+        if balance <= 0 {
+            return Err(MarketError::InsufficientBalance);
+        }
+
+        Self::sell(env, user, outcome, balance, min_return)
+    }
+
+    /// Move outcome tokens from one address to another, e.g. for OTC trades
+    /// or gifts.
+    ///
+    /// The tokens keep their cost basis: the sender's average cost for the
+    /// amount moved goes with them to the recipient. Trading quantities and
+    /// the pool are unchanged, so prices don't move.
+    ///
+    /// # Arguments
+    /// * `from` - Current holder (must authorize)
+    /// * `to` - Recipient, must differ from `from`
+    /// * `outcome` - 0 for YES, 1 for NO
+    /// * `amount` - Tokens to move (scaled by 10^7)
+    pub fn transfer_position(
+        env: Env,
+        from: Address,
+        to: Address,
+        outcome: u32,
+        amount: i128,
+    ) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+        if amount <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+        if from == to {
+            return Err(MarketError::SelfTransfer);
+        }
+
+        from.require_auth();
+
+        let from_key = DataKey::UserBalance(from.clone(), outcome);
+        let from_balance: i128 = env.storage().instance().get(&from_key).unwrap_or(0);
+        if from_balance < amount {
+            return Err(MarketError::InsufficientBalance);
+        }
+        let to_key = DataKey::UserBalance(to.clone(), outcome);
+        let to_balance: i128 = env.storage().instance().get(&to_key).unwrap_or(0);
+
+        let new_from_balance = from_balance - amount;
+        let new_to_balance = to_balance
+            .checked_add(amount)
+            .ok_or(MarketError::Overflow)?;
+        env.storage().instance().set(&from_key, &new_from_balance);
+        env.storage().instance().set(&to_key, &new_to_balance);
+        Self::update_holder_count(&env, outcome, from_balance, new_from_balance);
+        Self::update_holder_count(&env, outcome, to_balance, new_to_balance);
+        Self::release_callback_if_flat(&env, &from);
+
+        let basis = Self::reduce_cost_basis(&env, &from, outcome, amount, from_balance)?;
+        Self::add_cost_basis(&env, &to, outcome, basis)?;
+
+        env.events()
+            .publish((symbol_short!("pos_xfer"), from, to), (outcome, amount));
+
+        Ok(())
+    }
+
+    /// Burn matched YES and NO tokens for collateral before resolution.
+    ///
+    /// A YES and NO pair always pays exactly 1 at settlement, and burning
+    /// one lowers the LMSR cost by exactly 1, so the pair is redeemed at
+    /// face value with no protocol fee and prices don't move. Selling both
+    /// sides returns the same gross amount but pays the fee on each sell.
+    ///
+    /// # Arguments
+    /// * `user` - Holder of both outcomes (must authorize)
+    /// * `amount` - Pairs to burn (scaled by 10^7)
+    ///
+    /// # Returns
+    /// Collateral returned, equal to `amount`
+    pub fn redeem_pair(env: Env, user: Address, amount: i128) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+        Self::require_not_paused(&env)?;
+        Self::require_no_proposal(&env)?;
+
+        if amount <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        user.require_auth();
+
+        let yes_key = DataKey::UserBalance(user.clone(), OUTCOME_YES);
+        let no_key = DataKey::UserBalance(user.clone(), OUTCOME_NO);
+        let yes_balance: i128 = env.storage().instance().get(&yes_key).unwrap_or(0);
+        let no_balance: i128 = env.storage().instance().get(&no_key).unwrap_or(0);
+        if yes_balance < amount || no_balance < amount {
+            return Err(MarketError::InsufficientBalance);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        // What's left must still pay out the larger side in full
+        let new_q_yes = q_yes - amount;
+        let new_q_no = q_no - amount;
+        let new_pool = pool - amount;
+        if new_pool < new_q_yes.max(new_q_no) {
+            return Err(MarketError::InsufficientPool);
+        }
+
+        Self::update_price_accumulator(&env, q_yes, q_no, b)?;
+
+        env.storage().instance().set(&DataKey::YesSold, &new_q_yes);
+        env.storage().instance().set(&DataKey::NoSold, &new_q_no);
+        Self::set_current_cost(&env, lmsr::cost(new_q_yes, new_q_no, b)?);
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &new_pool);
+
+        env.storage()
+            .instance()
+            .set(&yes_key, &(yes_balance - amount));
+        env.storage()
+            .instance()
+            .set(&no_key, &(no_balance - amount));
+        Self::update_holder_count(&env, OUTCOME_YES, yes_balance, yes_balance - amount);
+        Self::update_holder_count(&env, OUTCOME_NO, no_balance, no_balance - amount);
+        Self::release_callback_if_flat(&env, &user);
+
+        let basis_yes = Self::reduce_cost_basis(&env, &user, OUTCOME_YES, amount, yes_balance)?;
+        let basis_no = Self::reduce_cost_basis(&env, &user, OUTCOME_NO, amount, no_balance)?;
+        Self::add_realized_pnl(&env, &user, amount - basis_yes - basis_no)?;
+
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let token_client = token::Client::new(&env, &collateral_token);
+        Self::transfer_out(&env, &token_client, &user, amount)?;
+
+        env.events()
+            .publish((symbol_short!("pair_rdm"), user), amount);
+
+        Ok(amount)
+    }
+
+    /// Resolve the market (resolver only).
+    ///
+    /// Markets with a dispute window only propose the outcome here: trading
+    /// stops, and the outcome becomes final once `finalize` is called after
+    /// the window, unless `dispute` resets it first. After MAX_DISPUTES
+    /// disputed proposals this returns DisputeLimitReached, and the market
+    /// can only be cancelled.
+    ///
+    /// # Arguments
+    /// * `oracle` - Must match the resolver (the oracle unless delegated at initialization)
+    /// * `winning_outcome` - 0 for YES, 1 for NO
+    pub fn resolve(env: Env, oracle: Address, winning_outcome: u32) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+        Self::require_no_proposal(&env)?;
+
+        if !is_valid_outcome(winning_outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+
+        // Spent disputes must not leave a later proposal unchallengeable
+        let disputes: u32 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::DisputeCount)
+            .unwrap_or(0);
+        if disputes >= MAX_DISPUTES {
+            return Err(MarketError::DisputeLimitReached);
+        }
+
+        // Verify caller is the resolver
+        if oracle != Self::get_resolver(env.clone())? {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        let require_winner: bool = env
+            .storage()
+            .instance()
+            .get(&DataKey::RequireNonZeroWinner)
+            .unwrap_or(false);
+        if require_winner {
+            let sold_key = if winning_outcome == OUTCOME_YES {
+                DataKey::YesSold
+            } else {
+                DataKey::NoSold
+            };
+            let sold: i128 = env
+                .storage()
+                .instance()
+                .get(&sold_key)
+                .ok_or(MarketError::StorageCorrupted)?;
+            if sold == 0 {
+                return Err(MarketError::EmptyWinningSide);
+            }
+        }
+
+        let window: Option<u32> = env.storage().instance().get(&DataKey::DisputeWindow);
+        if let Some(window) = window {
+            let resolve_at = env
+                .ledger()
+                .sequence()
+                .checked_add(window)
+                .ok_or(MarketError::Overflow)?;
+            env.storage()
+                .instance()
+                .set(&DataKey::ProposedOutcome, &winning_outcome);
+            env.storage()
+                .instance()
+                .set(&DataKey::ResolveAt, &resolve_at);
+
+            env.events()
+                .publish((symbol_short!("propose"), oracle), winning_outcome);
+            return Ok(());
+        }
+
+        Self::finalize_resolution(&env, oracle, winning_outcome)
+    }
+
+    /// Resolve the market and record the evidence behind the outcome.
+    ///
+    /// Same as `resolve`, plus `evidence` (e.g. the IPFS hash of the published
+    /// source) is stored for `get_resolution_evidence`. A dispute clears it
+    /// together with the proposed outcome.
+    pub fn resolve_with_evidence(
+        env: Env,
+        oracle: Address,
+        winning_outcome: u32,
+        evidence: String,
+    ) -> Result<(), MarketError> {
+        env.storage()
+            .instance()
+            .set(&DataKey::ResolutionEvidence, &evidence);
+        Self::resolve(env, oracle, winning_outcome)
+    }
+
+    /// Get the evidence recorded by `resolve_with_evidence`, if any.
+    pub fn get_resolution_evidence(env: Env) -> Result<Option<String>, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(env.storage().instance().get(&DataKey::ResolutionEvidence))
+    }
+
+    /// Lock in a proposed resolution once its dispute window has passed.
+    /// Anyone can call this.
+    pub fn finalize(env: Env) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        let (winning_outcome, resolve_at) =
+            Self::get_proposed_resolution(env.clone())?.ok_or(MarketError::NotResolved)?;
+        if env.ledger().sequence() < resolve_at {
+            return Err(MarketError::DisputeWindowOpen);
+        }
+
+        env.storage().instance().remove(&DataKey::ProposedOutcome);
+        env.storage().instance().remove(&DataKey::ResolveAt);
+
+        let resolver = Self::get_resolver(env.clone())?;
+        Self::finalize_resolution(&env, resolver, winning_outcome)
+    }
+
+    /// Challenge a proposed resolution during its dispute window.
+    ///
+    /// Clears the proposal so the resolver can resolve again. Only position
+    /// holders can dispute, each once per market. Every proposal stays open
+    /// to dispute; after MAX_DISPUTES the resolver can't propose again, so
+    /// repeated disputes end in cancellation rather than an unchallengeable
+    /// outcome.
+    pub fn dispute(env: Env, challenger: Address) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        let (proposed, resolve_at) =
+            Self::get_proposed_resolution(env.clone())?.ok_or(MarketError::NotResolved)?;
+        if env.ledger().sequence() >= resolve_at {
+            return Err(MarketError::DisputeWindowClosed);
+        }
+
+        let (yes_balance, no_balance) = Self::get_balances(env.clone(), challenger.clone());
+        if yes_balance <= 0 && no_balance <= 0 {
+            return Err(MarketError::InsufficientBalance);
+        }
+        let disputed_key = ExtDataKey::Disputed(challenger.clone());
+        let disputes: u32 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::DisputeCount)
+            .unwrap_or(0);
+        if env.storage().instance().has(&disputed_key) {
+            return Err(MarketError::DisputeLimitReached);
+        }
+
+        challenger.require_auth();
+
+        env.storage().instance().set(&disputed_key, &true);
+        env.storage()
+            .instance()
+            .set(&ExtDataKey::DisputeCount, &(disputes + 1));
+        env.storage().instance().remove(&DataKey::ProposedOutcome);
+        env.storage().instance().remove(&DataKey::ResolveAt);
+        env.storage()
+            .instance()
+            .remove(&DataKey::ResolutionEvidence);
+
+        env.events()
+            .publish((symbol_short!("dispute"), challenger), proposed);
+
+        Ok(())
+    }
+
+    /// Get the pending proposed resolution as (outcome, first ledger it can be finalized).
+    /// None if nothing is awaiting finalization.
+    pub fn get_proposed_resolution(env: Env) -> Result<Option<(u32, u32)>, MarketError> {
+        Self::require_initialized(&env)?;
+        let outcome: Option<u32> = env.storage().instance().get(&DataKey::ProposedOutcome);
+        match outcome {
+            Some(outcome) => {
+                let resolve_at: u32 = env
+                    .storage()
+                    .instance()
+                    .get(&DataKey::ResolveAt)
+                    .ok_or(MarketError::StorageCorrupted)?;
+                Ok(Some((outcome, resolve_at)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Register or clear the contract notified when this market resolves.
+    ///
+    /// Once resolved, `notify_callbacks` calls `on_resolved(market,
+    /// winning_outcome)` on registered callbacks. Only holders with a
+    /// nonzero position can register, and the registration is dropped when
+    /// their position goes back to zero, so slots can't be squatted.
+    ///
+    /// # Arguments
+    /// * `holder` - Position holder registering the callback (must authorize)
+    /// * `callback` - Contract to notify, or None to remove the registration
+    pub fn set_resolution_callback(
+        env: Env,
+        holder: Address,
+        callback: Option<Address>,
+    ) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        holder.require_auth();
+
+        let mut holders: Vec<Address> = env
+            .storage()
+            .instance()
+            .get(&DataKey::CallbackHolders)
+            .unwrap_or(Vec::new(&env));
+        let index = holders.first_index_of(&holder);
+        let key = DataKey::ResolutionCallback(holder.clone());
+
+        match callback {
+            Some(callback) => {
+                let (yes_balance, no_balance) = Self::get_balances(env.clone(), holder.clone());
+                if yes_balance <= 0 && no_balance <= 0 {
+                    return Err(MarketError::InsufficientBalance);
+                }
+                if index.is_none() {
+                    if holders.len() >= MAX_RESOLUTION_CALLBACKS {
+                        return Err(MarketError::TooManyCallbacks);
+                    }
+                    holders.push_back(holder);
+                }
+                env.storage().instance().set(&key, &callback);
+            }
+            None => {
+                if let Some(index) = index {
+                    holders.remove(index);
+                }
+                env.storage().instance().remove(&key);
+            }
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::CallbackHolders, &holders);
+
+        Ok(())
+    }
+
+    /// Notify the resolution callbacks of the given holders (anyone can call).
+    ///
+    /// Runs after resolution rather than inside it, so a callback that
+    /// fails or exhausts the budget can't block resolving the market. Each
+    /// callback is notified at most once: its registration is removed before
+    /// the call. Holders without a pending registration are skipped, so a
+    /// hostile callback can be left out of the list.
+    ///
+    /// # Returns
+    /// Number of callbacks called
+    pub fn notify_callbacks(env: Env, holders: Vec<Address>) -> Result<u32, MarketError> {
+        let winning_outcome = Self::get_winning_outcome(env.clone())?;
+
+        // Take the registrations off the list before calling anything
+        let mut pending: Vec<Address> = env
+            .storage()
+            .instance()
+            .get(&DataKey::CallbackHolders)
+            .unwrap_or(Vec::new(&env));
+        let mut due: Vec<(Address, Address)> = Vec::new(&env);
+        for holder in holders.iter() {
+            let Some(index) = pending.first_index_of(&holder) else {
+                continue;
+            };
+            pending.remove(index);
+            let key = DataKey::ResolutionCallback(holder.clone());
+            let callback: Option<Address> = env.storage().instance().get(&key);
+            env.storage().instance().remove(&key);
+            if let Some(callback) = callback {
+                due.push_back((holder, callback));
+            }
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::CallbackHolders, &pending);
+
+        let func = Symbol::new(&env, "on_resolved");
+        let market = env.current_contract_address();
+        for (holder, callback) in due.iter() {
+            let args = vec![&env, market.into_val(&env), winning_outcome.into_val(&env)];
+            let notified = matches!(
+                env.try_invoke_contract::<(), soroban_sdk::Error>(&callback, &func, args),
+                Ok(Ok(()))
+            );
+
+            env.events().publish(
+                (symbol_short!("notify"), holder, callback),
+                (winning_outcome, notified),
+            );
+        }
+
+        Ok(due.len())
+    }
+
+    /// Get the holders whose resolution callbacks haven't been notified yet,
+    /// in registration order.
+    pub fn get_callback_holders(env: Env) -> Result<Vec<Address>, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(env
+            .storage()
+            .instance()
+            .get(&DataKey::CallbackHolders)
+            .unwrap_or(Vec::new(&env)))
+    }
+
+    /// Get the resolution callback registered by `holder`, if any.
+    pub fn get_resolution_callback(
+        env: Env,
+        holder: Address,
+    ) -> Result<Option<Address>, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(env
+            .storage()
+            .instance()
+            .get(&DataKey::ResolutionCallback(holder)))
+    }
+
+    /// Cancel the market when the event is void (oracle only).
+    ///
+    /// Trading, resolution and claims stop; holders recover collateral via
+    /// `refund` at the prices in effect at cancellation.
+    pub fn cancel(env: Env, oracle: Address) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        let stored_oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        // Snapshot prices so every refund uses the same attribution
+        let price_yes = Self::get_price(env.clone(), OUTCOME_YES)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::RefundPrice(OUTCOME_YES), &price_yes);
+        env.storage().instance().set(
+            &DataKey::RefundPrice(OUTCOME_NO),
+            &(SCALE_FACTOR - price_yes),
+        );
+
+        env.storage().instance().set(&DataKey::Cancelled, &true);
+        env.storage().instance().remove(&DataKey::ProposedOutcome);
+        env.storage().instance().remove(&DataKey::ResolveAt);
+
+        env.events()
+            .publish((symbol_short!("cancel"), oracle), price_yes);
+
+        Ok(())
+    }
+
+    /// Refund a user's position on a cancelled market.
+    ///
+    /// Each token is worth its outcome's price at cancellation, so a user
+    /// holding equal YES and NO gets back one unit of collateral per pair.
+    /// Balances are zeroed; no claim fee applies.
+    ///
+    /// # Returns
+    /// Collateral refunded
+    pub fn refund(env: Env, user: Address) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        if !Self::is_cancelled(&env) {
+            return Err(MarketError::NotCancelled);
+        }
+
+        user.require_auth();
+
+        let mut refund_amount: i128 = 0;
+        let mut basis: i128 = 0;
+        for (outcome, sold_key) in [
+            (OUTCOME_YES, DataKey::YesSold),
+            (OUTCOME_NO, DataKey::NoSold),
+        ] {
+            let balance_key = DataKey::UserBalance(user.clone(), outcome);
+            let balance: i128 = env.storage().instance().get(&balance_key).unwrap_or(0);
+            if balance <= 0 {
+                continue;
+            }
+
+            let price: i128 = env
+                .storage()
+                .instance()
+                .get(&DataKey::RefundPrice(outcome))
+                .ok_or(MarketError::StorageCorrupted)?;
+            let value = balance
+                .checked_mul(price)
+                .ok_or(MarketError::Overflow)?
+                .checked_div(SCALE_FACTOR)
+                .ok_or(MarketError::Overflow)?;
+            refund_amount = refund_amount
+                .checked_add(value)
+                .ok_or(MarketError::Overflow)?;
+
+            // Outstanding quantities back the reserve in withdraw_remaining
+            let sold: i128 = env
+                .storage()
+                .instance()
+                .get(&sold_key)
+                .ok_or(MarketError::StorageCorrupted)?;
+            env.storage().instance().set(&sold_key, &(sold - balance));
+            env.storage().instance().set(&balance_key, &0i128);
+            Self::update_holder_count(&env, outcome, balance, 0);
+
+            basis = basis
+                .checked_add(Self::reduce_cost_basis(
+                    &env, &user, outcome, balance, balance,
+                )?)
+                .ok_or(MarketError::Overflow)?;
+        }
+
+        if refund_amount <= 0 {
+            return Err(MarketError::NothingToClaim);
+        }
+
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if pool < refund_amount {
+            return Err(MarketError::InsufficientPool);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &(pool - refund_amount));
+
+        Self::add_realized_pnl(&env, &user, refund_amount - basis)?;
+
+        // A rejected transfer (e.g. a deauthorized recipient) returns TransferFailed,
+        // which rolls back the state changes above
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let token_client = token::Client::new(&env, &collateral_token);
+        Self::transfer_out(&env, &token_client, &user, refund_amount)?;
+
+        env.events().publish(
+            (symbol_short!("refund"), user),
+            (refund_amount, collateral_token),
+        );
+
+        Ok(refund_amount)
+    }
+
+    /// Claim winnings after market resolution.
+    /// Each winning token is redeemable for 1 unit of collateral (1:1 redemption),
+    /// minus the market's claim fee (2% by default), which the oracle collects via withdraw_fees
+    /// after any rewards share is paid out.
+    /// Note: Losing tokens have zero value and are not claimed.
+    ///
+    /// # Arguments
+    /// * `user` - User claiming (must authorize)
+    ///
+    /// # Returns
+    /// Amount of collateral claimed (after fee deduction)
+    pub fn claim(env: Env, user: Address) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_resolved(&env)?;
+        Self::require_claim_open(&env)?;
+
+        user.require_auth();
+
+        let (winning_outcome, winning_balance) = Self::winning_balance(&env, &user)?;
+        Self::redeem_winning(
+            &env,
+            user,
+            winning_outcome,
+            winning_balance,
+            winning_balance,
+        )
+    }
+
+    /// Claim part of a winning position after market resolution.
+    ///
+    /// Redeems `amount` winning tokens with the same claim fee as `claim` and
+    /// leaves the rest claimable later.
+    ///
+    /// # Arguments
+    /// * `user` - User claiming (must authorize)
+    /// * `amount` - Winning tokens to redeem (at most the user's balance)
+    ///
+    /// # Returns
+    /// Amount of collateral claimed (after fee deduction)
+    pub fn claim_partial(env: Env, user: Address, amount: i128) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_resolved(&env)?;
+        Self::require_claim_open(&env)?;
+
+        user.require_auth();
+
+        let (winning_outcome, winning_balance) = Self::winning_balance(&env, &user)?;
+        if amount <= 0 || amount > winning_balance {
+            return Err(MarketError::InvalidAmount);
+        }
+        Self::redeem_winning(&env, user, winning_outcome, winning_balance, amount)
+    }
+
+    /// Withdraw remaining pool after market resolution or cancellation (oracle only).
+    ///
+    /// Withdraws only the excess funds (losers' bets and leftover funding)
+    /// while reserving enough collateral for unclaimed winning tokens, or for
+    /// outstanding refunds on a cancelled market. This prevents the oracle
+    /// from withdrawing funds that holders haven't claimed yet. Claim fees
+    /// are kept apart and withdrawn with `withdraw_fees`.
+    ///
+    /// # Arguments
+    /// * `oracle` - Must match the oracle set at initialization
+    ///
+    /// # Returns
+    /// Amount of collateral withdrawn
+    pub fn withdraw_remaining(env: Env, oracle: Address) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        if !Self::is_cancelled(&env) {
+            Self::require_resolved(&env)?;
+        }
+
+        // Verify caller is oracle
+        let stored_oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        let (withdrawable, reserved) = Self::withdrawable_pool(&env)?;
+
+        if withdrawable <= 0 {
+            return Err(MarketError::NothingToClaim);
+        }
+
+        // Update pool to reserved amount (for future claims)
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &reserved);
+
+        // Transfer withdrawable to oracle; a rejected transfer returns
+        // TransferFailed and rolls back the pool update
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let token_client = token::Client::new(&env, &collateral_token);
+        Self::transfer_out(&env, &token_client, &oracle, withdrawable)?;
+
+        env.events()
+            .publish((symbol_short!("withdraw"), oracle), withdrawable);
+
+        Ok(withdrawable)
+    }
+
+    /// Withdraw the claim fees collected so far (oracle only).
+    ///
+    /// Independent of `withdraw_remaining`: fees are never part of the
+    /// residual pool, so the two together pay out the whole surplus.
+    ///
+    /// # Returns
+    /// Amount of collateral withdrawn
+    pub fn withdraw_fees(env: Env, oracle: Address) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+
+        let stored_oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        let fees = Self::get_accrued_fees(env.clone())?;
+        if fees <= 0 {
+            return Err(MarketError::NothingToClaim);
+        }
+
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let remaining = pool.checked_sub(fees).ok_or(MarketError::Overflow)?;
+        if remaining < 0 {
+            return Err(MarketError::InsufficientPool);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &remaining);
+        env.storage().instance().set(&DataKey::AccruedFees, &0i128);
+
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let token_client = token::Client::new(&env, &collateral_token);
+        Self::transfer_out(&env, &token_client, &oracle, fees)?;
+
+        env.events().publish((symbol_short!("fees"), oracle), fees);
+
+        Ok(fees)
+    }
+
+    /// Get the claim fees collected and not yet withdrawn with `withdraw_fees`.
+    pub fn get_accrued_fees(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(env
+            .storage()
+            .instance()
+            .get(&DataKey::AccruedFees)
+            .unwrap_or(0))
+    }
+
+    /// Propose a new oracle (current oracle only).
+    ///
+    /// The transfer completes only when `new_oracle` calls `accept_oracle`, so
+    /// a mistyped address can't take over the market. A later proposal
+    /// replaces an earlier one.
+    pub fn propose_oracle(
+        env: Env,
+        current_oracle: Address,
+        new_oracle: Address,
+    ) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+
+        let stored_oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if current_oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        current_oracle.require_auth();
+
+        env.storage()
+            .instance()
+            .set(&DataKey::PendingOracle, &new_oracle);
+
+        env.events()
+            .publish((symbol_short!("orc_prop"), current_oracle), new_oracle);
+
+        Ok(())
+    }
+
+    /// Accept a pending oracle transfer (proposed oracle only).
+    pub fn accept_oracle(env: Env, new_oracle: Address) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+
+        let pending: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::PendingOracle)
+            .ok_or(MarketError::NoPendingOracle)?;
+        if new_oracle != pending {
+            return Err(MarketError::Unauthorized);
+        }
+        new_oracle.require_auth();
+
+        env.storage().instance().set(&DataKey::Oracle, &new_oracle);
+        env.storage().instance().remove(&DataKey::PendingOracle);
+
+        env.events()
+            .publish((symbol_short!("orc_acpt"), new_oracle), ());
+
+        Ok(())
+    }
+
+    /// Pause or unpause trading on behalf of the deploying factory.
+    ///
+    /// Only the factory recorded at initialization can call this, giving the
+    /// venue admin a kill switch across all of its markets. Markets deployed
+    /// without a factory reject it.
+    pub fn factory_set_paused(env: Env, paused: bool) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+
+        let factory: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Factory)
+            .ok_or(MarketError::Unauthorized)?;
+        factory.require_auth();
+
+        env.storage().instance().set(&DataKey::Paused, &paused);
+
+        env.events()
+            .publish((symbol_short!("paused"), factory), paused);
+
+        Ok(())
+    }
+
+    /// Deepen the market by raising the liquidity parameter (oracle only).
+    ///
+    /// The oracle deposits the increase in the cost function at the current
+    /// quantities, C(q, b + additional_b) - C(q, b), which keeps the pool's
+    /// margin over the cost function and so its solvency unchanged. User
+    /// balances are untouched; prices move toward 50/50 because q/b shrinks,
+    /// and any trading back to the old prices is funded by this deposit.
+    ///
+    /// # Returns
+    /// Collateral deposited
+    pub fn add_liquidity(
+        env: Env,
+        oracle: Address,
+        additional_b: i128,
+    ) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+        Self::require_no_proposal(&env)?;
+
+        let stored_oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        if additional_b <= 0 {
+            return Err(MarketError::InvalidLiquidity);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let new_b = b
+            .checked_add(additional_b)
+            .ok_or(MarketError::InvalidLiquidity)?;
+        let new_cost = lmsr::cost(q_yes, q_no, new_b)?;
+        let deposit = new_cost
+            .checked_sub(Self::current_cost(&env, q_yes, q_no, b)?)
+            .ok_or(MarketError::Overflow)?;
+
+        // Prices change here, so close out the accumulator period at the old b
+        Self::update_price_accumulator(&env, q_yes, q_no, b)?;
+
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let new_pool = pool.checked_add(deposit).ok_or(MarketError::Overflow)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &new_pool);
+        env.storage()
+            .instance()
+            .set(&DataKey::LiquidityParam, &new_b);
+        Self::set_current_cost(&env, new_cost);
+
+        // Transfer the deposit from the oracle
+        // Note: token_client.transfer() may panic on insufficient balance
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let token_client = token::Client::new(&env, &collateral_token);
+        Self::transfer_in(&env, &token_client, &oracle, deposit)?;
+
+        env.events()
+            .publish((symbol_short!("liquidity"), oracle), (new_b, deposit));
+
+        Ok(deposit)
+    }
+
+    /// Replace this market's code with an uploaded WASM (oracle only).
+    ///
+    /// Storage is kept, so the new code must read the same keys. While the
+    /// market trades, the oracle can replace its code and so is trusted with
+    /// the pool; the factory's banned WASM list is not consulted. Once a
+    /// resolution is proposed, or the market is resolved or cancelled, the
+    /// pool is reserved for holders' winnings and refunds, so upgrades return
+    /// DisputeWindowOpen, AlreadyResolved or Cancelled.
+    ///
+    /// # Arguments
+    /// * `oracle` - Must match the stored oracle
+    /// * `new_wasm_hash` - Hash of WASM already uploaded to the network
+    pub fn upgrade(
+        env: Env,
+        oracle: Address,
+        new_wasm_hash: BytesN<32>,
+    ) -> Result<(), MarketError> {
+        Self::require_initialized(&env)?;
+
+        let stored_oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        Self::require_not_resolved(&env)?;
+        if env.storage().instance().has(&DataKey::ProposedOutcome) {
+            return Err(MarketError::DisputeWindowOpen);
+        }
+
+        env.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+
+        env.events()
+            .publish((symbol_short!("upgrade"), oracle), new_wasm_hash);
+
+        Ok(())
+    }
+
+    /// Pause trading (oracle only).
+    ///
+    /// Circuit breaker for pricing bugs found after deployment. While paused,
+    /// `buy`, `buy_with` and `sell` reject with `Paused`; selling stays blocked
+    /// because it prices through the same code. Resolution, claims, cancellation
+    /// and refunds are unaffected.
+    pub fn pause(env: Env, oracle: Address) -> Result<(), MarketError> {
+        Self::set_oracle_paused(&env, oracle, true)
+    }
+
+    /// Resume trading after `pause` (oracle only).
+    ///
+    /// Does not lift a pause set by the factory.
+    pub fn unpause(env: Env, oracle: Address) -> Result<(), MarketError> {
+        Self::set_oracle_paused(&env, oracle, false)
+    }
+
+    /// Check whether trading is paused by the oracle or the factory.
+    pub fn is_paused(env: Env) -> Result<bool, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(Self::require_not_paused(&env).is_err())
+    }
+
+    /// Get the current price of an outcome.
+    ///
+    /// # Returns
+    /// Price scaled by 10^7 (5_000_000 = 0.5 = 50%)
+    pub fn get_price(env: Env, outcome: u32) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        lmsr::calculate_price(q_yes, q_no, outcome, b)
+    }
+
+    /// Get both outcome prices adjusted to sum to exactly 10^7.
+    ///
+    /// Each price from `get_price` rounds down, so the pair can fall a unit
+    /// or two short of 1. Here the lower price keeps its rounded value and
+    /// the higher one (YES on a tie) is 10^7 minus it, so the rounding
+    /// remainder always goes to the favourite.
+    ///
+    /// # Returns
+    /// (yes_price, no_price), scaled by 10^7
+    pub fn get_normalized_prices(env: Env) -> Result<(i128, i128), MarketError> {
+        let price_yes = Self::get_price(env.clone(), OUTCOME_YES)?;
+        let price_no = Self::get_price(env, OUTCOME_NO)?;
+
+        if price_yes >= price_no {
+            Ok((SCALE_FACTOR - price_no, price_no))
+        } else {
+            Ok((price_yes, SCALE_FACTOR - price_yes))
+        }
+    }
+
+    /// Get the LMSR cost function C(q_yes, q_no) at the current state.
+    ///
+    /// Lets clients check their own pricing code against the contract: a buy
+    /// costs the difference in C before and after the trade (plus any fee).
+    pub fn get_cost(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        Self::get_cost_at(env, q_yes, q_no)
+    }
+
+    /// Get the LMSR cost function for hypothetical quantities sold,
+    /// using this market's liquidity parameter.
+    pub fn get_cost_at(env: Env, q_yes: i128, q_no: i128) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+
+        if q_yes < 0 || q_no < 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        lmsr::cost(q_yes, q_no, b)
+    }
+
+    /// Get the price of an outcome for hypothetical quantities sold,
+    /// using this market's liquidity parameter.
+    ///
+    /// # Returns
+    /// Price scaled by 10^7 (5000000 = 50%)
+    pub fn get_price_at(
+        env: Env,
+        q_yes: i128,
+        q_no: i128,
+        outcome: u32,
+    ) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+
+        if q_yes < 0 || q_no < 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        lmsr::calculate_price(q_yes, q_no, outcome, b)
+    }
+
+    /// Get a quote for buying tokens.
+    ///
+    /// # Returns
+    /// (cost, price_after) both scaled by 10^7
+    pub fn get_quote(env: Env, outcome: u32, amount: i128) -> Result<(i128, i128), MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+        if amount <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let cost = lmsr::calculate_buy_cost(q_yes, q_no, amount, outcome, b)?;
+        let (fee, _) = Self::protocol_fee(&env, cost)?;
+        let total_cost = cost.checked_add(fee).ok_or(MarketError::Overflow)?;
+
+        // Calculate price after purchase
+        let (new_q_yes, new_q_no) = if outcome == OUTCOME_YES {
+            (
+                q_yes.checked_add(amount).ok_or(MarketError::Overflow)?,
+                q_no,
+            )
+        } else {
+            (
+                q_yes,
+                q_no.checked_add(amount).ok_or(MarketError::Overflow)?,
+            )
+        };
+
+        let price_after = lmsr::calculate_price(new_q_yes, new_q_no, outcome, b)?;
+
+        Ok((total_cost, price_after))
+    }
+
+    /// Get a buy quote with the average fill price and price impact.
+    ///
+    /// `cost` matches `get_quote` (protocol fee included), and
+    /// `avg_price = cost * 10^7 / amount`.
+    ///
+    /// # Returns
+    /// (cost, avg_price, price_before, price_after), prices scaled by 10^7
+    pub fn get_quote_detailed(
+        env: Env,
+        outcome: u32,
+        amount: i128,
+    ) -> Result<(i128, i128, i128, i128), MarketError> {
+        let price_before = Self::get_price(env.clone(), outcome)?;
+        let (cost, price_after) = Self::get_quote(env, outcome, amount)?;
+        let avg_price = cost
+            .checked_mul(SCALE_FACTOR)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(amount)
+            .ok_or(MarketError::Overflow)?;
+
+        Ok((cost, avg_price, price_before, price_after))
+    }
+
+    /// Get a quote for selling tokens.
+    ///
+    /// # Returns
+    /// (return_amount, price_after) both scaled by 10^7
+    pub fn get_sell_quote(
+        env: Env,
+        outcome: u32,
+        amount: i128,
+    ) -> Result<(i128, i128), MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+        if amount <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let return_amount = lmsr::calculate_sell_return(q_yes, q_no, amount, outcome, b)?;
+        let (fee, _) = Self::protocol_fee(&env, return_amount)?;
+        let net_return = return_amount
+            .checked_sub(fee)
+            .ok_or(MarketError::Overflow)?;
+
+        // Calculate price after sale
+        let (new_q_yes, new_q_no) = if outcome == OUTCOME_YES {
+            (q_yes - amount, q_no)
+        } else {
+            (q_yes, q_no - amount)
+        };
+
+        let price_after = lmsr::calculate_price(new_q_yes, new_q_no, outcome, b)?;
+
+        Ok((net_return, price_after))
+    }
+
+    /// Get the all-in cost of a one-token round trip: buying one token of
+    /// `outcome` and selling it straight back, with the protocol fee on both
+    /// legs. LMSR itself charges nothing for the round trip, so this is the
+    /// fee drag a round-tripping trader pays.
+    ///
+    /// # Returns
+    /// Buy cost minus sell return for 1 token, scaled by 10^7
+    pub fn get_effective_spread(env: Env, outcome: u32) -> Result<i128, MarketError> {
+        let (_, _, spread) = Self::quote_spread(&env, outcome, SCALE_FACTOR, true)?;
+        Ok(spread)
+    }
+
+    /// Get the bid-ask spread for `amount` tokens of `outcome` at the current
+    /// state: what buying them costs versus what selling them returns, both
+    /// quoted from the same quantities and including the protocol fee.
+    ///
+    /// Selling needs `amount` tokens of `outcome` outstanding, otherwise this
+    /// returns InsufficientBalance.
+    ///
+    /// # Returns
+    /// (buy_cost, sell_return, spread) where spread = buy_cost - sell_return
+    pub fn get_spread(
+        env: Env,
+        outcome: u32,
+        amount: i128,
+    ) -> Result<(i128, i128, i128), MarketError> {
+        Self::quote_spread(&env, outcome, amount, false)
+    }
+
+    /// Get how many `outcome` tokens to buy to move its price to `target_price`.
+    ///
+    /// Inverts the price formula: the outcome's price is p once it leads the
+    /// other side by b * ln(p / (1 - p)) tokens, so the amount is that lead
+    /// minus the current one.
+    ///
+    /// # Arguments
+    /// * `target_price` - Scaled by 10^7, strictly between 0 and 10^7;
+    ///   anything else returns InvalidAmount
+    ///
+    /// # Returns
+    /// Tokens to buy, scaled by 10^7 (0 if the price is already at or above
+    /// the target)
+    pub fn tokens_to_reach_price(
+        env: Env,
+        outcome: u32,
+        target_price: i128,
+    ) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        lmsr::calculate_amount_to_price(q_yes, q_no, target_price, outcome, b)
+    }
+
+    /// Get the cost of buying enough of `outcome` to push its price to
+    /// `CERTAINTY_PRICE` (0.99), i.e. the buy pressure needed to "win" the
+    /// market by trading. Scales linearly with b.
+    ///
+    /// # Returns
+    /// Cost in collateral including the protocol fee, scaled by 10^7
+    /// (0 if the price is already there)
+    pub fn get_cost_to_certainty(env: Env, outcome: u32) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let amount = lmsr::calculate_amount_to_price(q_yes, q_no, CERTAINTY_PRICE, outcome, b)?;
+        if amount == 0 {
+            return Ok(0);
+        }
+        let cost = lmsr::calculate_buy_cost(q_yes, q_no, amount, outcome, b)?;
+        let (fee, _) = Self::protocol_fee(&env, cost)?;
+        cost.checked_add(fee).ok_or(MarketError::Overflow)
+    }
+
+    /// Get the expected value of buying `amount` tokens at the current implied probability.
+    ///
+    /// Computed as `amount * price / SCALE_FACTOR - cost`: the collateral the
+    /// position is worth at today's price minus what it costs to buy. Price
+    /// impact makes this slightly negative at equilibrium.
+    ///
+    /// # Returns
+    /// Expected value in collateral, scaled by 10^7 (may be negative)
+    pub fn get_expected_value(env: Env, outcome: u32, amount: i128) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+        if amount <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let price = lmsr::calculate_price(q_yes, q_no, outcome, b)?;
+        let cost = lmsr::calculate_buy_cost(q_yes, q_no, amount, outcome, b)?;
+        let (fee, _) = Self::protocol_fee(&env, cost)?;
+        let cost = cost.checked_add(fee).ok_or(MarketError::Overflow)?;
+
+        let value = amount
+            .checked_mul(price)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(SCALE_FACTOR)
+            .ok_or(MarketError::Overflow)?;
+
+        value.checked_sub(cost).ok_or(MarketError::Overflow)
+    }
+
+    /// Get a quote for closing a user's entire position.
+    ///
+    /// Sells the full YES balance and then the full NO balance, applying each
+    /// leg to the quantities left by the previous one so the total reflects
+    /// price impact.
+    ///
+    /// # Returns
+    /// Total collateral returned, scaled by 10^7 (0 if the user holds nothing)
+    pub fn get_close_all_quote(env: Env, user: Address) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_not_resolved(&env)?;
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let mut q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let mut q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let mut total: i128 = 0;
+        for outcome in [OUTCOME_YES, OUTCOME_NO] {
+            let balance: i128 = env
+                .storage()
+                .instance()
+                .get(&DataKey::UserBalance(user.clone(), outcome))
+                .unwrap_or(0);
+            if balance <= 0 {
+                continue;
+            }
+
+            let return_amount = lmsr::calculate_sell_return(q_yes, q_no, balance, outcome, b)?;
+            let (fee, _) = Self::protocol_fee(&env, return_amount)?;
+            total = total
+                .checked_add(return_amount - fee)
+                .ok_or(MarketError::Overflow)?;
+
+            if outcome == OUTCOME_YES {
+                q_yes -= balance;
+            } else {
+                q_no -= balance;
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Get a user's realized profit and loss from sells and claims.
+    ///
+    /// Each exit realizes the collateral received minus the average cost basis
+    /// of the tokens given up. Positive means profit.
+    pub fn get_realized_pnl(env: Env, user: Address) -> i128 {
+        env.storage()
+            .instance()
+            .get(&DataKey::UserRealizedPnl(user))
+            .unwrap_or(0)
+    }
+
+    /// Get the collateral a user has paid for their current tokens of an outcome.
+    ///
+    /// Buys add their cost (including any protocol fee); sells and claims remove
+    /// the sold share of the basis pro rata. Unrealized P&L is the position's
+    /// current value minus this basis.
+    pub fn get_cost_basis(env: Env, user: Address, outcome: u32) -> Result<i128, MarketError> {
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+        Ok(env
+            .storage()
+            .instance()
+            .get(&DataKey::UserCostBasis(user, outcome))
+            .unwrap_or(0))
+    }
+
+    /// Get the accepted rate for a basket token (base units per token unit, scaled by 10^7).
+    pub fn get_accepted_token_rate(env: Env, token: Address) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        env.storage()
+            .instance()
+            .get(&DataKey::AcceptedTokenRate(token))
+            .ok_or(MarketError::TokenNotAccepted)
+    }
+
+    /// Get the base collateral left in the basket float for `buy_with`.
+    pub fn get_basket_float(env: Env) -> i128 {
+        env.storage()
+            .instance()
+            .get(&ExtDataKey::BasketFloat)
+            .unwrap_or(0)
+    }
+
+    /// Get user's token balance for an outcome.
+    pub fn get_balance(env: Env, user: Address, outcome: u32) -> i128 {
         let balance_key = DataKey::UserBalance(user, outcome);
         env.storage().instance().get(&balance_key).unwrap_or(0)
     }
 
-    /// Get market state.
-    ///
-    /// # Returns
-    /// (yes_sold, no_sold, collateral_pool, is_resolved)
-    pub fn get_state(env: Env) -> Result<(i128, i128, i128, bool), MarketError> {
-        Self::require_initialized(&env)?;
+    /// Get user's token balances for both outcomes in one call.
+    ///
+    /// # Returns
+    /// (yes_balance, no_balance)
+    pub fn get_balances(env: Env, user: Address) -> (i128, i128) {
+        (
+            Self::get_balance(env.clone(), user.clone(), OUTCOME_YES),
+            Self::get_balance(env, user, OUTCOME_NO),
+        )
+    }
+
+    /// Get the outcomes a user holds, skipping those with a zero balance.
+    ///
+    /// # Returns
+    /// (outcome, balance) pairs in outcome order
+    pub fn get_user_positions(env: Env, user: Address) -> Vec<(u32, i128)> {
+        let mut positions = Vec::new(&env);
+        for outcome in 0..OUTCOME_COUNT {
+            let balance = Self::get_balance(env.clone(), user.clone(), outcome);
+            if balance != 0 {
+                positions.push_back((outcome, balance));
+            }
+        }
+        positions
+    }
+
+    /// Get market state.
+    ///
+    /// # Returns
+    /// (yes_sold, no_sold, collateral_pool, is_resolved)
+    pub fn get_state(env: Env) -> Result<(i128, i128, i128, bool), MarketError> {
+        Self::require_initialized(&env)?;
+
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        Ok((q_yes, q_no, pool, Self::is_resolved(&env)))
+    }
+
+    /// Get market state together with both outcome prices, in one call.
+    ///
+    /// # Returns
+    /// (yes_sold, no_sold, collateral_pool, is_resolved, yes_price, no_price),
+    /// prices scaled by 10^7
+    pub fn get_full_state(env: Env) -> Result<(i128, i128, i128, bool, i128, i128), MarketError> {
+        let (q_yes, q_no, pool, resolved) = Self::get_state(env.clone())?;
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let price_yes = lmsr::calculate_price(q_yes, q_no, OUTCOME_YES, b)?;
+        let price_no = lmsr::calculate_price(q_yes, q_no, OUTCOME_NO, b)?;
+
+        Ok((q_yes, q_no, pool, resolved, price_yes, price_no))
+    }
+
+    /// Get the key market views in one call, for dashboards.
+    ///
+    /// Each field matches its individual getter; see `MarketSummary`.
+    pub fn get_summary(env: Env) -> Result<MarketSummary, MarketError> {
+        let (yes_sold, no_sold, pool, resolved, yes_price, no_price) =
+            Self::get_full_state(env.clone())?;
+        let winning_outcome = if resolved {
+            Some(Self::get_winning_outcome(env.clone())?)
+        } else {
+            None
+        };
+
+        Ok(MarketSummary {
+            yes_price,
+            no_price,
+            yes_sold,
+            no_sold,
+            pool,
+            resolved,
+            winning_outcome,
+            oracle: Self::get_oracle(env.clone())?,
+            liquidity_param: Self::get_liquidity_param(env)?,
+        })
+    }
+
+    /// Get the collateral `account` can recover via `withdraw_remaining`.
+    /// The oracle is the sole claimant on the residual pool, so any other
+    /// account gets 0. Only available after resolution or cancellation.
+    pub fn get_pool_share(env: Env, account: Address) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        if !Self::is_cancelled(&env) {
+            Self::require_resolved(&env)?;
+        }
+
+        let oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if account != oracle {
+            return Ok(0);
+        }
+
+        let (withdrawable, _) = Self::withdrawable_pool(&env)?;
+        Ok(withdrawable.max(0))
+    }
+
+    /// Get lightweight market metrics for display.
+    ///
+    /// Volume counts the collateral of every buy and sell before protocol
+    /// fees. Markets deployed before these were tracked report an age of 0
+    /// and only count trades made since.
+    ///
+    /// # Returns
+    /// (age_in_ledgers, total_volume, trade_count)
+    pub fn get_analytics(env: Env) -> Result<(u32, i128, u32), MarketError> {
+        Self::require_initialized(&env)?;
+        let age = env
+            .storage()
+            .instance()
+            .get::<_, u32>(&DataKey::CreatedLedger)
+            .map_or(0, |created| env.ledger().sequence().saturating_sub(created));
+        let volume: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TotalVolume)
+            .unwrap_or(0);
+        let trades: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TradeCount)
+            .unwrap_or(0);
+        Ok((age, volume, trades))
+    }
+
+    /// Get cumulative trading volume with buys and sells counted apart.
+    ///
+    /// Volume is in collateral units, before protocol fees, as in
+    /// `get_analytics`. Markets deployed before the split was tracked only
+    /// count buys and sells made since.
+    ///
+    /// # Returns
+    /// (total_volume, buy_count, sell_count)
+    pub fn get_stats(env: Env) -> Result<(i128, u32, u32), MarketError> {
+        Self::require_initialized(&env)?;
+        let volume: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TotalVolume)
+            .unwrap_or(0);
+        let buys: u32 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::BuyCount)
+            .unwrap_or(0);
+        let sells: u32 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::SellCount)
+            .unwrap_or(0);
+        Ok((volume, buys, sells))
+    }
+
+    /// Get the collateral the market was funded with at initialization.
+    ///
+    /// Later `add_liquidity` deposits are not included, so subtracting this
+    /// from the pool gives the net flow from trading. Markets deployed before
+    /// it was stored report 0.
+    pub fn get_initial_funding(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::InitialFunding)
+            .unwrap_or(0))
+    }
+
+    /// Compare the pool with the collateral it must hold, to monitor drift.
+    ///
+    /// While trading is live the minimum is the net inflow LMSR predicts,
+    /// C(q_yes, q_no) - C(0, 0); the initial funding on top of it is the
+    /// market's loss buffer. Once resolved or cancelled it is the collateral
+    /// reserved for unclaimed winnings or outstanding refunds, plus claim
+    /// fees not yet withdrawn.
+    ///
+    /// # Returns
+    /// (pool, theoretical_min); a healthy market has pool >= theoretical_min
+    pub fn check_solvency(env: Env) -> Result<(i128, i128), MarketError> {
+        Self::require_initialized(&env)?;
+
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        if Self::is_resolved(&env) || Self::is_cancelled(&env) {
+            let (_, reserved) = Self::withdrawable_pool(&env)?;
+            return Ok((pool, reserved));
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let theoretical_min = lmsr::cost(q_yes, q_no, b)?
+            .checked_sub(lmsr::cost(0, 0, b)?)
+            .ok_or(MarketError::Overflow)?;
+
+        Ok((pool, theoretical_min))
+    }
+
+    /// Run the core accounting checks in one call, for auditors and monitoring.
+    ///
+    /// Each entry pairs a check name with whether it holds:
+    /// - `solvent`: pool >= the minimum reported by `check_solvency`
+    /// - `prices`: the outcome prices sum to 1, within rounding
+    /// - `reconcile`: the contract's collateral balance covers the pool and
+    ///   the basket float, converted to the token's decimals.
+    /// - `sold_pos`: neither outcome's sold quantity is negative
+    pub fn get_invariants(env: Env) -> Result<Vec<(Symbol, bool)>, MarketError> {
+        let (pool, theoretical_min) = Self::check_solvency(env.clone())?;
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let price_sum = lmsr::calculate_price(q_yes, q_no, OUTCOME_YES, b)?
+            .checked_add(lmsr::calculate_price(q_yes, q_no, OUTCOME_NO, b)?)
+            .ok_or(MarketError::Overflow)?;
+        let price_gap = SCALE_FACTOR
+            .checked_sub(price_sum)
+            .ok_or(MarketError::Overflow)?;
+        let balance =
+            token::Client::new(&env, &collateral_token).balance(&env.current_contract_address());
+
+        let float: i128 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::BasketFloat)
+            .unwrap_or(0);
+        let pool_in_token_units = Self::to_token_units(
+            &env,
+            pool.checked_add(float).ok_or(MarketError::Overflow)?,
+            false,
+        )?;
+
+        Ok(vec![
+            &env,
+            (symbol_short!("solvent"), pool >= theoretical_min),
+            (
+                symbol_short!("prices"),
+                (0..=PRICE_SUM_TOLERANCE).contains(&price_gap),
+            ),
+            (symbol_short!("reconcile"), balance >= pool_in_token_units),
+            (symbol_short!("sold_pos"), q_yes >= 0 && q_no >= 0),
+        ])
+    }
+
+    /// Get the oracle's worst-case loss as market maker.
+    ///
+    /// LMSR bounds the loss at b * ln(outcome_count), i.e. b * ln(2) for a
+    /// binary market, at the current b. Claim fees stay in the pool for the
+    /// oracle and offset it; protocol fees go to the fee recipient and don't.
+    ///
+    /// # Returns
+    /// b * ln(2) minus claim fees collected so far, floored at 0
+    pub fn get_max_loss(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let claim_fees: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ClaimFeeRevenue)
+            .unwrap_or(0);
+
+        let bound = lmsr::initial_liquidity(b)?
+            .checked_sub(claim_fees)
+            .ok_or(MarketError::Overflow)?;
+        Ok(bound.max(0))
+    }
+
+    /// Get the most collateral the market may still have to pay holders.
+    ///
+    /// Before resolution this is the larger of the quantities sold, i.e.
+    /// what is owed if that side wins, before claim fees. Once resolved or
+    /// cancelled it is what holders can still claim or be refunded.
+    pub fn max_payout_liability(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+
+        if Self::is_resolved(&env) || Self::is_cancelled(&env) {
+            return Self::holder_reserve(&env);
+        }
+
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        Ok(q_yes.max(q_no))
+    }
+
+    /// Check whether the pool covers `max_payout_liability`.
+    ///
+    /// Claim fees awaiting `withdraw_fees` belong to the oracle, so they
+    /// don't count toward covering holders.
+    pub fn is_solvent(env: Env) -> Result<bool, MarketError> {
+        let liability = Self::max_payout_liability(env.clone())?;
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let accrued_fees: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::AccruedFees)
+            .unwrap_or(0);
+        Ok(pool - accrued_fees >= liability)
+    }
+
+    /// Get the initial funding `initialize` requires for liquidity parameter
+    /// `liquidity_param`: b * ln(n) for the market's n outcomes, i.e.
+    /// b * ln(2). A `safety_multiplier_bps` in the config raises it further.
+    pub fn required_funding(_env: Env, liquidity_param: i128) -> Result<i128, MarketError> {
+        lmsr::initial_liquidity(liquidity_param)
+    }
+
+    /// Get the market phase as a plain string for lightweight clients.
+    ///
+    /// One of "open" (trading), "closed" (trading paused or a resolution
+    /// awaiting finalization), "resolved" or "cancelled". Void events are
+    /// handled by `cancel`, so there is no separate "voided" phase.
+    pub fn get_status(env: Env) -> Result<String, MarketError> {
+        Self::require_initialized(&env)?;
+
+        let status = if Self::is_cancelled(&env) {
+            "cancelled"
+        } else if Self::is_resolved(&env) {
+            "resolved"
+        } else if Self::require_not_paused(&env).is_err()
+            || Self::require_no_proposal(&env).is_err()
+        {
+            "closed"
+        } else {
+            "open"
+        };
+        Ok(String::from_str(&env, status))
+    }
+
+    /// Get the address allowed to resolve the market.
+    ///
+    /// This is the oracle unless resolution was delegated at initialization.
+    pub fn get_resolver(env: Env) -> Result<Address, MarketError> {
+        Self::require_initialized(&env)?;
+        match env.storage().instance().get(&DataKey::Resolver) {
+            Some(resolver) => Ok(resolver),
+            None => env
+                .storage()
+                .instance()
+                .get(&DataKey::Oracle)
+                .ok_or(MarketError::StorageCorrupted),
+        }
+    }
+
+    /// Get the oracle address.
+    pub fn get_oracle(env: Env) -> Result<Address, MarketError> {
+        Self::require_initialized(&env)?;
+        env.storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)
+    }
+
+    /// Get the liquidity parameter.
+    pub fn get_liquidity_param(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        env.storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)
+    }
+
+    /// Get the winning outcome (only valid after resolution).
+    pub fn get_winning_outcome(env: Env) -> Result<u32, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_resolved(&env)?;
+        env.storage()
+            .instance()
+            .get(&DataKey::WinningOutcome)
+            .ok_or(MarketError::StorageCorrupted)
+    }
+
+    /// Get the number of addresses still holding winning tokens.
+    ///
+    /// Claiming zeroes a holder's balance, so this counts winners who have
+    /// not claimed yet. Only available after resolution.
+    pub fn get_winner_count(env: Env) -> Result<u32, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_resolved(&env)?;
+
+        let winning_outcome: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::WinningOutcome)
+            .ok_or(MarketError::StorageCorrupted)?;
+        Ok(env
+            .storage()
+            .instance()
+            .get(&DataKey::HolderCount(winning_outcome))
+            .unwrap_or(0))
+    }
+
+    /// Get the time-weighted settlement price of an outcome (only valid after resolution).
+    ///
+    /// Averaged over the pre-resolution window rather than taken from the last
+    /// spot price, so a single trade just before resolution can't move it much.
+    ///
+    /// # Returns
+    /// Price scaled by 10^7
+    pub fn get_settlement_price(env: Env, outcome: u32) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Self::require_resolved(&env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+
+        env.storage()
+            .instance()
+            .get(&DataKey::SettlementPrice(outcome))
+            .ok_or(MarketError::StorageCorrupted)
+    }
+
+    /// Get the metadata hash (IPFS CID for market metadata JSON).
+    pub fn get_metadata_hash(env: Env) -> Result<String, MarketError> {
+        Self::require_initialized(&env)?;
+        env.storage()
+            .instance()
+            .get(&DataKey::MetadataHash)
+            .ok_or(MarketError::StorageCorrupted)
+    }
+
+    /// Get the full metadata URI: the configured base URI followed by the
+    /// metadata hash. Returns the bare hash if no base URI was set.
+    pub fn get_metadata_uri(env: Env) -> Result<String, MarketError> {
+        Self::require_initialized(&env)?;
+
+        let hash: String = env
+            .storage()
+            .instance()
+            .get(&DataKey::MetadataHash)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let Some(base_uri) = env
+            .storage()
+            .instance()
+            .get::<_, String>(&DataKey::MetadataBaseUri)
+        else {
+            return Ok(hash);
+        };
+
+        let base_len = base_uri.len() as usize;
+        let total_len = base_len + hash.len() as usize;
+        if total_len > MAX_METADATA_URI_LEN {
+            return Err(MarketError::StorageCorrupted);
+        }
+
+        let mut buf = [0u8; MAX_METADATA_URI_LEN];
+        base_uri.copy_into_slice(&mut buf[..base_len]);
+        hash.copy_into_slice(&mut buf[base_len..total_len]);
+        Ok(String::from_bytes(&env, &buf[..total_len]))
+    }
+
+    /// Get the smallest token amount a single buy or sell may move (0 = none).
+    pub fn get_min_trade_amount(env: Env) -> i128 {
+        env.storage()
+            .instance()
+            .get(&DataKey::MinTradeAmount)
+            .unwrap_or(0)
+    }
+
+    /// Get the display label of an outcome: the one set at initialization,
+    /// or "YES" / "NO" if none were given.
+    pub fn get_outcome_label(env: Env, outcome: u32) -> Result<String, MarketError> {
+        Self::require_initialized(&env)?;
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+
+        let labels: Option<Vec<String>> = env.storage().instance().get(&DataKey::OutcomeLabels);
+        match labels {
+            Some(labels) => labels.get(outcome).ok_or(MarketError::StorageCorrupted),
+            None if outcome == OUTCOME_YES => Ok(String::from_str(&env, "YES")),
+            None => Ok(String::from_str(&env, "NO")),
+        }
+    }
+
+    /// Get the claim fee charged on winnings, in basis points.
+    pub fn get_claim_fee_bps(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(Self::claim_fee_bps(&env))
+    }
+
+    /// Get the collateral paid per winning token after the claim fee.
+    ///
+    /// # Returns
+    /// Net payout per token (scaled), e.g. 9_800_000 (0.98) with the default 2% fee
+    pub fn get_winner_payout_rate(env: Env) -> Result<i128, MarketError> {
+        Self::require_initialized(&env)?;
+        SCALE_FACTOR
+            .checked_mul(BPS_DENOMINATOR - Self::claim_fee_bps(&env))
+            .and_then(|v| v.checked_div(BPS_DENOMINATOR))
+            .ok_or(MarketError::Overflow)
+    }
+
+    /// Get the fees collected so far, in base collateral units.
+    ///
+    /// # Returns
+    /// (trade_fees, claim_fees): protocol fees on buys and sells (paid to the
+    /// fee recipient), and claim fees on winnings kept in the pool, net of
+    /// any rewards share
+    pub fn get_fee_revenue(env: Env) -> Result<(i128, i128), MarketError> {
+        Self::require_initialized(&env)?;
+        let trade_fees: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TradeFeeRevenue)
+            .unwrap_or(0);
+        let claim_fees: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ClaimFeeRevenue)
+            .unwrap_or(0);
+        Ok((trade_fees, claim_fees))
+    }
+
+    /// Get the last ledger at which winnings can be claimed.
+    ///
+    /// None if the market has no claim window or is not resolved yet.
+    pub fn get_claim_deadline(env: Env) -> Result<Option<u32>, MarketError> {
+        Self::require_initialized(&env)?;
+        Ok(env.storage().instance().get(&DataKey::ClaimDeadline))
+    }
+
+    /// Get the collateral token address.
+    pub fn get_collateral_token(env: Env) -> Result<Address, MarketError> {
+        Self::require_initialized(&env)?;
+        env.storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)
+    }
+
+    /// Get the units behind `amount` and `cost` values.
+    ///
+    /// Share amounts (`amount`, balances) are in units of 1/SHARE_SCALE of a
+    /// share. Costs, returns and payouts are internal 7-decimal amounts;
+    /// transfers convert them to the collateral token's own decimals, read
+    /// from the token at initialization.
+    ///
+    /// # Returns
+    /// (share_scale, collateral_scale), e.g. (10^7, 10^6) for 6-decimal collateral
+    pub fn get_units_info(env: Env) -> Result<(i128, i128), MarketError> {
+        Self::require_initialized(&env)?;
+        let decimals: u32 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::CollateralDecimals)
+            .unwrap_or(COLLATERAL_DECIMALS);
+        let collateral_scale = 10i128.checked_pow(decimals).ok_or(MarketError::Overflow)?;
+        Ok((SHARE_SCALE, collateral_scale))
+    }
+
+    // --- Internal helpers ---
+
+    /// Quote buying `amount` tokens of `outcome` against selling them, both
+    /// with the protocol fee. The sell is quoted from the current state, or
+    /// from the state after the buy when `round_trip` is set.
+    fn quote_spread(
+        env: &Env,
+        outcome: u32,
+        amount: i128,
+        round_trip: bool,
+    ) -> Result<(i128, i128, i128), MarketError> {
+        Self::require_initialized(env)?;
+        Self::require_not_resolved(env)?;
+
+        if !is_valid_outcome(outcome) {
+            return Err(MarketError::InvalidOutcome);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let cost = lmsr::calculate_buy_cost(q_yes, q_no, amount, outcome, b)?;
+        let (buy_fee, _) = Self::protocol_fee(env, cost)?;
+        let buy_cost = cost.checked_add(buy_fee).ok_or(MarketError::Overflow)?;
+
+        let (sell_q_yes, sell_q_no) = match (round_trip, outcome) {
+            (false, _) => (q_yes, q_no),
+            (true, OUTCOME_YES) => (
+                q_yes.checked_add(amount).ok_or(MarketError::Overflow)?,
+                q_no,
+            ),
+            (true, _) => (
+                q_yes,
+                q_no.checked_add(amount).ok_or(MarketError::Overflow)?,
+            ),
+        };
+        let return_amount = lmsr::calculate_sell_return(sell_q_yes, sell_q_no, amount, outcome, b)?;
+        let (sell_fee, _) = Self::protocol_fee(env, return_amount)?;
+        let sell_return = return_amount
+            .checked_sub(sell_fee)
+            .ok_or(MarketError::Overflow)?;
+
+        let spread = buy_cost
+            .checked_sub(sell_return)
+            .ok_or(MarketError::Overflow)?;
+        Ok((buy_cost, sell_return, spread))
+    }
+
+    /// Validate initialization parameters.
+    ///
+    /// Configuration mistakes (addresses pointing at the market itself or at
+    /// each other, out-of-range settings) return InvalidConfig. Liquidity and
+    /// funding amount problems keep their specific errors.
+    fn validate_config(
+        env: &Env,
+        oracle: &Address,
+        collateral_token: &Address,
+        liquidity_param: i128,
+        metadata_hash: &String,
+        initial_funding: i128,
+        config: &MarketConfig,
+    ) -> Result<(), MarketError> {
+        let self_address = env.current_contract_address();
+        if *oracle == self_address || *collateral_token == self_address {
+            return Err(MarketError::InvalidConfig);
+        }
+        if oracle == collateral_token {
+            return Err(MarketError::InvalidConfig);
+        }
+        if let Some(resolver) = &config.resolver {
+            if *resolver == self_address || resolver == collateral_token {
+                return Err(MarketError::InvalidConfig);
+            }
+        }
+        if let Some(funder) = &config.funder {
+            if *funder == self_address || funder == collateral_token {
+                return Err(MarketError::InvalidConfig);
+            }
+        }
+
+        if liquidity_param <= 0 {
+            return Err(MarketError::InvalidLiquidity);
+        }
+
+        // Verify initial funding covers the worst-case loss b * ln(2). That is
+        // the mathematical minimum; operators may demand a buffer on top.
+        let mut required = lmsr::initial_liquidity(liquidity_param)?;
+        if config.safety_multiplier_bps > 0 {
+            if (config.safety_multiplier_bps as i128) < BPS_DENOMINATOR {
+                return Err(MarketError::InvalidConfig);
+            }
+            required = required
+                .checked_mul(config.safety_multiplier_bps as i128)
+                .ok_or(MarketError::Overflow)?
+                .checked_div(BPS_DENOMINATOR)
+                .ok_or(MarketError::Overflow)?;
+        }
+        if initial_funding < required {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        // Verify initial funding doesn't risk overflow in pool operations
+        // Using i128::MAX / 2 as a safe upper bound for funding
+        const MAX_FUNDING: i128 = i128::MAX / 2;
+        if initial_funding > MAX_FUNDING {
+            return Err(MarketError::InvalidConfig);
+        }
+
+        // A protocol fee needs somewhere to go and can't exceed the trade
+        if config.protocol_fee_bps as i128 > BPS_DENOMINATOR
+            || (config.protocol_fee_bps > 0 && config.protocol_fee_recipient.is_none())
+        {
+            return Err(MarketError::InvalidConfig);
+        }
+
+        if config.claim_fee_bps < 0 || config.claim_fee_bps > BPS_DENOMINATOR {
+            return Err(MarketError::InvalidFee);
+        }
+
+        // A rewards share needs somewhere to go and can't exceed the claim fee
+        if config.rewards_share_bps as i128 > BPS_DENOMINATOR {
+            return Err(MarketError::InvalidFee);
+        }
+        if let Some(rewards_address) = &config.rewards_address {
+            if *rewards_address == self_address || rewards_address == collateral_token {
+                return Err(MarketError::InvalidConfig);
+            }
+        } else if config.rewards_share_bps > 0 {
+            return Err(MarketError::InvalidConfig);
+        }
+
+        // A zero-length claim window would expire winnings at resolution
+        if config.claim_deadline_ledgers == Some(0) {
+            return Err(MarketError::InvalidConfig);
+        }
+        // Use None rather than a zero-length dispute window for instant finality
+        if config.dispute_window_ledgers == Some(0) {
+            return Err(MarketError::InvalidConfig);
+        }
+        // A zero-length TWAP window would settle at the spot price
+        if config.twap_window_ledgers == Some(0) {
+            return Err(MarketError::InvalidConfig);
+        }
+
+        if config.min_trade_amount < 0 {
+            return Err(MarketError::InvalidConfig);
+        }
+
+        // One non-empty label per outcome
+        if let Some(labels) = &config.outcome_labels {
+            if labels.len() != OUTCOME_COUNT || labels.iter().any(|label| label.is_empty()) {
+                return Err(MarketError::InvalidConfig);
+            }
+        }
+
+        // The full metadata URI must fit get_metadata_uri's buffer
+        if let Some(base_uri) = &config.metadata_base_uri {
+            let uri_len = base_uri.len() as usize + metadata_hash.len() as usize;
+            if uri_len > MAX_METADATA_URI_LEN {
+                return Err(MarketError::InvalidConfig);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Split the pool into (withdrawable, reserved), where reserved covers
+    /// unclaimed winning tokens, or outstanding refunds if the market was
+    /// cancelled, plus claim fees awaiting `withdraw_fees`.
+    fn withdrawable_pool(env: &Env) -> Result<(i128, i128), MarketError> {
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let accrued_fees: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::AccruedFees)
+            .unwrap_or(0);
+
+        let reserved = Self::holder_reserve(env)?
+            .checked_add(accrued_fees)
+            .ok_or(MarketError::Overflow)?;
+        let withdrawable = pool.checked_sub(reserved).ok_or(MarketError::Overflow)?;
+        Ok((withdrawable, reserved))
+    }
+
+    /// Collateral still owed to holders, or to the accrued fees once they
+    /// claim: unclaimed winning tokens at face value, or refunds on a
+    /// cancelled market.
+    fn holder_reserve(env: &Env) -> Result<i128, MarketError> {
+        if Self::is_cancelled(env) {
+            // Refunds round down per holder, so rounding the total down is safe
+            let mut reserved: i128 = 0;
+            for (outcome, sold_key) in [
+                (OUTCOME_YES, DataKey::YesSold),
+                (OUTCOME_NO, DataKey::NoSold),
+            ] {
+                let sold: i128 = env
+                    .storage()
+                    .instance()
+                    .get(&sold_key)
+                    .ok_or(MarketError::StorageCorrupted)?;
+                let price: i128 = env
+                    .storage()
+                    .instance()
+                    .get(&DataKey::RefundPrice(outcome))
+                    .ok_or(MarketError::StorageCorrupted)?;
+                let owed = sold
+                    .checked_mul(price)
+                    .ok_or(MarketError::Overflow)?
+                    .checked_div(SCALE_FACTOR)
+                    .ok_or(MarketError::Overflow)?;
+                reserved = reserved.checked_add(owed).ok_or(MarketError::Overflow)?;
+            }
+            return Ok(reserved);
+        }
+
+        // Once the claim window closes, unclaimed winnings revert to the pool
+        if Self::require_claim_open(env).is_err() {
+            return Ok(0);
+        }
+
+        // Each unclaimed token keeps its full unit reserved: the payout goes to
+        // the holder and the claim fee moves to the accrued fees when claimed
+        Ok(env
+            .storage()
+            .instance()
+            .get(&DataKey::UnclaimedWinningTokens)
+            .unwrap_or(0))
+    }
+
+    fn require_min_trade(env: &Env, amount: i128) -> Result<(), MarketError> {
+        if amount < Self::get_min_trade_amount(env.clone()) {
+            return Err(MarketError::BelowMinimum);
+        }
+        Ok(())
+    }
+
+    fn require_initialized(env: &Env) -> Result<(), MarketError> {
+        if !env.storage().instance().has(&DataKey::Oracle) {
+            return Err(MarketError::NotInitialized);
+        }
+        Ok(())
+    }
+
+    fn require_not_resolved(env: &Env) -> Result<(), MarketError> {
+        if Self::is_cancelled(env) {
+            return Err(MarketError::Cancelled);
+        }
+        let resolved: bool = env
+            .storage()
+            .instance()
+            .get(&DataKey::Resolved)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if resolved {
+            return Err(MarketError::AlreadyResolved);
+        }
+        Ok(())
+    }
+
+    /// Apply a paid-for buy to market state: quantities, pool, and user balance.
+    fn record_buy(
+        env: &Env,
+        user: &Address,
+        outcome: u32,
+        amount: i128,
+        cost: i128,
+        q_yes: i128,
+        q_no: i128,
+    ) -> Result<(), MarketError> {
+        if outcome == OUTCOME_YES {
+            let new_q_yes = q_yes.checked_add(amount).ok_or(MarketError::Overflow)?;
+            env.storage().instance().set(&DataKey::YesSold, &new_q_yes);
+        } else {
+            let new_q_no = q_no.checked_add(amount).ok_or(MarketError::Overflow)?;
+            env.storage().instance().set(&DataKey::NoSold, &new_q_no);
+        }
+
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let new_pool = pool.checked_add(cost).ok_or(MarketError::Overflow)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &new_pool);
+
+        // Update user balance
+        let balance_key = DataKey::UserBalance(user.clone(), outcome);
+        let current_balance: i128 = env.storage().instance().get(&balance_key).unwrap_or(0);
+        let new_balance = current_balance
+            .checked_add(amount)
+            .ok_or(MarketError::Overflow)?;
+        env.storage().instance().set(&balance_key, &new_balance);
+        Self::update_holder_count(env, outcome, current_balance, new_balance);
+
+        Self::record_trade(env, cost, ExtDataKey::BuyCount)?;
+        Self::add_cost_basis(env, user, outcome, cost)
+    }
+
+    /// C(q_yes, q_no) at the current state, from the cache kept by every trade.
+    /// Markets deployed before the cache existed compute it on first use.
+    fn current_cost(env: &Env, q_yes: i128, q_no: i128, b: i128) -> Result<i128, MarketError> {
+        match env.storage().instance().get(&DataKey::LastCost) {
+            Some(cost) => Ok(cost),
+            None => lmsr::cost(q_yes, q_no, b),
+        }
+    }
+
+    /// Cache C(q_yes, q_no) after a change to quantities or b.
+    fn set_current_cost(env: &Env, cost: i128) {
+        env.storage().instance().set(&DataKey::LastCost, &cost);
+    }
+
+    /// Count a trade of `volume` collateral toward the market's analytics.
+    /// `side` is ExtDataKey::BuyCount or ExtDataKey::SellCount.
+    fn record_trade(env: &Env, volume: i128, side: ExtDataKey) -> Result<(), MarketError> {
+        let total: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TotalVolume)
+            .unwrap_or(0);
+        env.storage().instance().set(
+            &DataKey::TotalVolume,
+            &total.checked_add(volume).ok_or(MarketError::Overflow)?,
+        );
+        let count: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TradeCount)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKey::TradeCount, &count.saturating_add(1));
+        let side_count: u32 = env.storage().instance().get(&side).unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&side, &side_count.saturating_add(1));
+        Ok(())
+    }
+
+    /// Get the winning outcome and the user's balance of it.
+    fn winning_balance(env: &Env, user: &Address) -> Result<(u32, i128), MarketError> {
+        let winning_outcome: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::WinningOutcome)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let winning_balance: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::UserBalance(user.clone(), winning_outcome))
+            .unwrap_or(0);
+
+        if winning_balance <= 0 {
+            return Err(MarketError::NothingToClaim);
+        }
+        Ok((winning_outcome, winning_balance))
+    }
+
+    /// Redeem `amount` of a user's winning tokens for collateral minus the claim fee.
+    fn redeem_winning(
+        env: &Env,
+        user: Address,
+        winning_outcome: u32,
+        winning_balance: i128,
+        amount: i128,
+    ) -> Result<i128, MarketError> {
+        // Each winning token is worth 1 unit of collateral
+        let gross_payout = amount;
+
+        // Calculate fee (2% = 200 basis points by default)
+        // Fee is held apart in AccruedFees; oracle collects it via withdraw_fees()
+        // Note: Integer division truncates, so dust-level amounts may have zero fee
+        let fee = gross_payout
+            .checked_mul(Self::claim_fee_bps(env))
+            .ok_or(MarketError::Overflow)?
+            .checked_div(BPS_DENOMINATOR)
+            .ok_or(MarketError::Overflow)?;
+        let user_payout = gross_payout.checked_sub(fee).ok_or(MarketError::Overflow)?;
+        let (rewards, rewards_address) = Self::rewards_share(env, fee)?;
+        let kept_fee = fee - rewards;
+
+        // Reduce user's balance
+        let remaining = winning_balance - amount;
+        env.storage().instance().set(
+            &DataKey::UserBalance(user.clone(), winning_outcome),
+            &remaining,
+        );
+        Self::update_holder_count(env, winning_outcome, winning_balance, remaining);
+
+        // Realize the payout against the redeemed share of the winning basis and
+        // all of the losing basis (losing tokens are worthless from here on)
+        let losing_outcome = 1 - winning_outcome;
+        let losing_balance: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::UserBalance(user.clone(), losing_outcome))
+            .unwrap_or(0);
+        let basis_won =
+            Self::reduce_cost_basis(env, &user, winning_outcome, amount, winning_balance)?;
+        let basis_lost =
+            Self::reduce_cost_basis(env, &user, losing_outcome, losing_balance, losing_balance)?;
+        let pnl = user_payout
+            .checked_sub(basis_won)
+            .and_then(|pnl| pnl.checked_sub(basis_lost))
+            .ok_or(MarketError::Overflow)?;
+        Self::add_realized_pnl(env, &user, pnl)?;
+        Self::add_fee_revenue(env, DataKey::ClaimFeeRevenue, kept_fee)?;
+        Self::add_fee_revenue(env, DataKey::AccruedFees, kept_fee)?;
+
+        // Decrement unclaimed winning tokens tracker
+        let unclaimed: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::UnclaimedWinningTokens)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKey::UnclaimedWinningTokens, &(unclaimed - amount));
+
+        // Update collateral pool (deduct user_payout and any rewards share;
+        // the rest of the fee stays in pool)
+        let pool: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralPool)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let paid_out = user_payout
+            .checked_add(rewards)
+            .ok_or(MarketError::Overflow)?;
+
+        // Guard against pool underflow (should not happen with correct market operation)
+        if pool < paid_out {
+            return Err(MarketError::InsufficientPool);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::CollateralPool, &(pool - paid_out));
+
+        // Transfer collateral to user (minus fee)
+        // A rejected transfer (e.g. a deauthorized recipient) returns TransferFailed,
+        // which rolls back the state changes above
+        let collateral_token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::CollateralToken)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let token_client = token::Client::new(env, &collateral_token);
+        Self::transfer_out(env, &token_client, &user, user_payout)?;
+        if let Some(rewards_address) = rewards_address {
+            Self::transfer_out(env, &token_client, &rewards_address, rewards)?;
+        }
+
+        env.events().publish(
+            (symbol_short!("claim"), user),
+            (user_payout, collateral_token),
+        );
+
+        Ok(user_payout)
+    }
+
+    /// Track how many addresses hold `outcome` as a balance moves from
+    /// `before` to `after`.
+    fn update_holder_count(env: &Env, outcome: u32, before: i128, after: i128) {
+        let key = DataKey::HolderCount(outcome);
+        let count: u32 = env.storage().instance().get(&key).unwrap_or(0);
+        if before <= 0 && after > 0 {
+            env.storage().instance().set(&key, &(count + 1));
+        } else if before > 0 && after <= 0 {
+            env.storage().instance().set(&key, &count.saturating_sub(1));
+        }
+    }
+
+    /// Accumulate collateral paid into a user's cost basis for P&L tracking.
+    fn add_cost_basis(
+        env: &Env,
+        user: &Address,
+        outcome: u32,
+        paid: i128,
+    ) -> Result<(), MarketError> {
+        if paid == 0 {
+            return Ok(());
+        }
+        let basis_key = DataKey::UserCostBasis(user.clone(), outcome);
+        let basis: i128 = env.storage().instance().get(&basis_key).unwrap_or(0);
+        env.storage().instance().set(
+            &basis_key,
+            &basis.checked_add(paid).ok_or(MarketError::Overflow)?,
+        );
+        Ok(())
+    }
+
+    /// Protocol fee owed on a trade moving `amount` of collateral, with the
+    /// address it goes to. Markets without a protocol fee return (0, None).
+    fn protocol_fee(env: &Env, amount: i128) -> Result<(i128, Option<Address>), MarketError> {
+        let fee_bps: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ProtocolFeeBps)
+            .unwrap_or(0);
+        if fee_bps == 0 {
+            return Ok((0, None));
+        }
+        let recipient: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::ProtocolFeeRecipient)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        let fee = amount
+            .checked_mul(fee_bps as i128)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(BPS_DENOMINATOR)
+            .ok_or(MarketError::Overflow)?;
+        if fee == 0 {
+            return Ok((0, None));
+        }
+        Ok((fee, Some(recipient)))
+    }
+
+    /// Share of a claim fee owed to the rewards address, with the address it
+    /// goes to. Markets without rewards return (0, None).
+    fn rewards_share(env: &Env, fee: i128) -> Result<(i128, Option<Address>), MarketError> {
+        let rewards_address: Option<Address> =
+            env.storage().instance().get(&ExtDataKey::RewardsAddress);
+        let Some(rewards_address) = rewards_address else {
+            return Ok((0, None));
+        };
+        let share_bps: u32 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::RewardsShareBps)
+            .unwrap_or(0);
+
+        let rewards = fee
+            .checked_mul(share_bps as i128)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(BPS_DENOMINATOR)
+            .ok_or(MarketError::Overflow)?;
+        if rewards == 0 {
+            return Ok((0, None));
+        }
+        Ok((rewards, Some(rewards_address)))
+    }
+
+    /// Convert a base collateral amount to basket token units at `rate`,
+    /// rounding up so the market never receives less than it's owed.
+    fn base_to_token_units(amount: i128, rate: i128) -> Result<i128, MarketError> {
+        amount
+            .checked_mul(SCALE_FACTOR)
+            .ok_or(MarketError::Overflow)?
+            .checked_add(rate - 1)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(rate)
+            .ok_or(MarketError::Overflow)
+    }
+
+    /// Remove the cost basis attributable to `amount` of `balance_before` tokens.
+    ///
+    /// Uses average cost: `basis * amount / balance_before`. Removing the whole
+    /// balance clears the basis exactly, leaving no rounding residue.
+    ///
+    /// # Returns
+    /// Basis removed
+    fn reduce_cost_basis(
+        env: &Env,
+        user: &Address,
+        outcome: u32,
+        amount: i128,
+        balance_before: i128,
+    ) -> Result<i128, MarketError> {
+        let basis_key = DataKey::UserCostBasis(user.clone(), outcome);
+        let basis: i128 = env.storage().instance().get(&basis_key).unwrap_or(0);
+        if basis == 0 || balance_before <= 0 {
+            return Ok(0);
+        }
+
+        let removed = if amount >= balance_before {
+            basis
+        } else {
+            basis
+                .checked_mul(amount)
+                .ok_or(MarketError::Overflow)?
+                .checked_div(balance_before)
+                .ok_or(MarketError::Overflow)?
+        };
+
+        env.storage().instance().set(&basis_key, &(basis - removed));
+
+        Ok(removed)
+    }
+
+    fn add_realized_pnl(env: &Env, user: &Address, delta: i128) -> Result<(), MarketError> {
+        let pnl_key = DataKey::UserRealizedPnl(user.clone());
+        let pnl: i128 = env.storage().instance().get(&pnl_key).unwrap_or(0);
+        env.storage().instance().set(
+            &pnl_key,
+            &pnl.checked_add(delta).ok_or(MarketError::Overflow)?,
+        );
+        Ok(())
+    }
+
+    /// Add a collected fee to the running total under `key`.
+    fn add_fee_revenue(env: &Env, key: DataKey, fee: i128) -> Result<(), MarketError> {
+        let total: i128 = env.storage().instance().get(&key).unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&key, &total.checked_add(fee).ok_or(MarketError::Overflow)?);
+        Ok(())
+    }
+
+    /// Advance the TWAP accumulator to the current ledger.
+    ///
+    /// Must run before quantities change so the elapsed period is weighted by
+    /// the price that was in effect during it. Rotates checkpoints once the
+    /// current one is a full window old. The price is constant since the last
+    /// update, so after a quiet stretch covering the window start the new
+    /// previous checkpoint is placed exactly one window back; otherwise it is
+    /// the old checkpoint, which is then less than two windows old.
+    fn update_price_accumulator(
+        env: &Env,
+        q_yes: i128,
+        q_no: i128,
+        b: i128,
+    ) -> Result<(), MarketError> {
+        let now = env.ledger().sequence();
+        let last: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::PriceLastLedger)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let at_last: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::PriceCumulative)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        // Cumulative at `ledger` in [last, now], over which the price held
+        let mut price_yes = 0;
+        let cumulative_at = |ledger: u32, price_yes: i128| {
+            price_yes
+                .checked_mul((ledger - last) as i128)
+                .and_then(|weighted| at_last.checked_add(weighted))
+                .ok_or(MarketError::Overflow)
+        };
+
+        let mut cumulative = at_last;
+        if now > last {
+            price_yes = lmsr::calculate_price(q_yes, q_no, OUTCOME_YES, b)?;
+            cumulative = cumulative_at(now, price_yes)?;
+            env.storage()
+                .instance()
+                .set(&DataKey::PriceCumulative, &cumulative);
+            env.storage()
+                .instance()
+                .set(&DataKey::PriceLastLedger, &now);
+        }
+
+        let window = Self::twap_window(env);
+        let checkpoint: (u32, i128) = env
+            .storage()
+            .instance()
+            .get(&DataKey::TwapCheckpoint)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if now.saturating_sub(checkpoint.0) >= window {
+            let window_start = now - window;
+            let prev = if window_start >= last {
+                (window_start, cumulative_at(window_start, price_yes)?)
+            } else {
+                checkpoint
+            };
+            env.storage()
+                .instance()
+                .set(&DataKey::TwapPrevCheckpoint, &prev);
+            env.storage()
+                .instance()
+                .set(&DataKey::TwapCheckpoint, &(now, cumulative));
+        }
+
+        Ok(())
+    }
+
+    /// Compute the YES TWAP ending at the current ledger.
+    ///
+    /// Averages from the previous checkpoint when one exists, covering at
+    /// least one TWAP window and less than three, otherwise from market
+    /// creation. Falls back to the spot price if no ledgers have elapsed.
+    fn settlement_twap(env: &Env) -> Result<i128, MarketError> {
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        Self::update_price_accumulator(env, q_yes, q_no, b)?;
+
+        let now = env.ledger().sequence();
+        let cumulative: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::PriceCumulative)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let start: (u32, i128) = match env.storage().instance().get(&DataKey::TwapPrevCheckpoint) {
+            Some(prev) => prev,
+            None => env
+                .storage()
+                .instance()
+                .get(&DataKey::TwapCheckpoint)
+                .ok_or(MarketError::StorageCorrupted)?,
+        };
+
+        let elapsed = now.saturating_sub(start.0);
+        if elapsed == 0 {
+            return lmsr::calculate_price(q_yes, q_no, OUTCOME_YES, b);
+        }
+
+        cumulative
+            .checked_sub(start.1)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(elapsed as i128)
+            .ok_or(MarketError::Overflow)
+    }
+
+    fn twap_window(env: &Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&ExtDataKey::TwapWindow)
+            .unwrap_or(TWAP_WINDOW_LEDGERS)
+    }
+
+    fn claim_fee_bps(env: &Env) -> i128 {
+        // Markets deployed before the fee was configurable charge the default
+        env.storage()
+            .instance()
+            .get(&DataKey::ClaimFeeBps)
+            .unwrap_or(CLAIM_FEE_BPS)
+    }
+
+    /// Lenient resolution flag for read-only views: a missing key reads as
+    /// unresolved. State-changing paths use require_(not_)resolved, which
+    /// treat a missing key as StorageCorrupted.
+    fn is_resolved(env: &Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::Resolved)
+            .unwrap_or(false)
+    }
+
+    fn is_cancelled(env: &Env) -> bool {
+        // Markets deployed before cancellation existed have no Cancelled key
+        env.storage()
+            .instance()
+            .get(&DataKey::Cancelled)
+            .unwrap_or(false)
+    }
+
+    fn require_not_paused(env: &Env) -> Result<(), MarketError> {
+        // Markets deployed before pausing existed have no Paused keys
+        let paused = |key: &DataKey| -> bool { env.storage().instance().get(key).unwrap_or(false) };
+        if paused(&DataKey::Paused) || paused(&DataKey::OraclePaused) {
+            return Err(MarketError::Paused);
+        }
+        Ok(())
+    }
+
+    fn set_oracle_paused(env: &Env, oracle: Address, paused: bool) -> Result<(), MarketError> {
+        Self::require_initialized(env)?;
+
+        let stored_oracle: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Oracle)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if oracle != stored_oracle {
+            return Err(MarketError::Unauthorized);
+        }
+        oracle.require_auth();
+
+        env.storage()
+            .instance()
+            .set(&DataKey::OraclePaused, &paused);
+
+        env.events()
+            .publish((symbol_short!("paused"), oracle), paused);
+
+        Ok(())
+    }
+
+    /// Largest amount of `outcome` tokens whose cost plus protocol fee fits in `budget`.
+    /// Returns ReturnTooLow if the budget can't buy a single unit.
+    fn amount_for_budget(env: &Env, outcome: u32, budget: i128) -> Result<i128, MarketError> {
+        if budget <= 0 {
+            return Err(MarketError::InvalidAmount);
+        }
+
+        let b: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::LiquidityParam)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_yes: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::YesSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+        let q_no: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NoSold)
+            .ok_or(MarketError::StorageCorrupted)?;
+
+        // Leave room for the protocol fee charged on top of the cost
+        let fee_bps: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ProtocolFeeBps)
+            .unwrap_or(0);
+        let cost_budget = budget
+            .checked_mul(BPS_DENOMINATOR)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(BPS_DENOMINATOR + fee_bps as i128)
+            .ok_or(MarketError::Overflow)?;
+        if cost_budget <= 0 {
+            return Err(MarketError::ReturnTooLow);
+        }
+
+        let amount = lmsr::calculate_amount_for_budget(q_yes, q_no, cost_budget, outcome, b)?;
+        if amount == 0 {
+            return Err(MarketError::ReturnTooLow);
+        }
+        Ok(amount)
+    }
+
+    /// Record the winning outcome and open claims.
+    fn finalize_resolution(
+        env: &Env,
+        resolver: Address,
+        winning_outcome: u32,
+    ) -> Result<(), MarketError> {
+        // Capture the time-weighted settlement price before finalizing
+        let twap_yes = Self::settlement_twap(env)?;
+        env.storage()
+            .instance()
+            .set(&DataKey::SettlementPrice(OUTCOME_YES), &twap_yes);
+        env.storage().instance().set(
+            &DataKey::SettlementPrice(OUTCOME_NO),
+            &(SCALE_FACTOR - twap_yes),
+        );
+
+        // Mark as resolved
+        env.storage().instance().set(&DataKey::Resolved, &true);
+        env.storage()
+            .instance()
+            .set(&DataKey::WinningOutcome, &winning_outcome);
+
+        // Track total unclaimed winning tokens for withdraw_remaining protection
+        let winning_tokens: i128 = if winning_outcome == OUTCOME_YES {
+            env.storage()
+                .instance()
+                .get(&DataKey::YesSold)
+                .ok_or(MarketError::StorageCorrupted)?
+        } else {
+            env.storage()
+                .instance()
+                .get(&DataKey::NoSold)
+                .ok_or(MarketError::StorageCorrupted)?
+        };
+        env.storage()
+            .instance()
+            .set(&DataKey::UnclaimedWinningTokens, &winning_tokens);
+
+        // Start the claim window, if the market has one
+        let window: Option<u32> = env.storage().instance().get(&DataKey::ClaimWindow);
+        if let Some(window) = window {
+            let deadline = env
+                .ledger()
+                .sequence()
+                .checked_add(window)
+                .ok_or(MarketError::Overflow)?;
+            env.storage()
+                .instance()
+                .set(&DataKey::ClaimDeadline, &deadline);
+        }
+
+        env.events()
+            .publish((symbol_short!("resolve"), resolver), winning_outcome);
+
+        Ok(())
+    }
+
+    /// Drop `user`'s resolution callback once they hold no tokens, freeing
+    /// the slot for holders with a position.
+    fn release_callback_if_flat(env: &Env, user: &Address) {
+        let key = DataKey::ResolutionCallback(user.clone());
+        if !env.storage().instance().has(&key) {
+            return;
+        }
+        let (yes_balance, no_balance) = Self::get_balances(env.clone(), user.clone());
+        if yes_balance > 0 || no_balance > 0 {
+            return;
+        }
+
+        env.storage().instance().remove(&key);
+        let mut holders: Vec<Address> = env
+            .storage()
+            .instance()
+            .get(&DataKey::CallbackHolders)
+            .unwrap_or(Vec::new(env));
+        if let Some(index) = holders.first_index_of(user) {
+            holders.remove(index);
+            env.storage()
+                .instance()
+                .set(&DataKey::CallbackHolders, &holders);
+        }
+    }
+
+    /// Transfer collateral from the market, returning TransferFailed instead of
+    /// aborting if the token rejects it.
+    fn transfer_out(
+        env: &Env,
+        token_client: &token::Client,
+        to: &Address,
+        amount: i128,
+    ) -> Result<(), MarketError> {
+        let token_amount = Self::to_token_units(env, amount, false)?;
+        Self::transfer_token_units_out(env, token_client, to, token_amount)
+    }
+
+    /// `transfer_out` for an amount already in the token's own units, e.g.
+    /// basket tokens, which don't use the collateral's decimals.
+    fn transfer_token_units_out(
+        env: &Env,
+        token_client: &token::Client,
+        to: &Address,
+        token_amount: i128,
+    ) -> Result<(), MarketError> {
+        match token_client.try_transfer(&env.current_contract_address(), to, &token_amount) {
+            Ok(Ok(())) => Ok(()),
+            _ => Err(MarketError::TransferFailed),
+        }
+    }
+
+    /// Transfer collateral from `from` into the market. Panics like
+    /// `token_client.transfer()` if `from` can't pay.
+    fn transfer_in(
+        env: &Env,
+        token_client: &token::Client,
+        from: &Address,
+        amount: i128,
+    ) -> Result<(), MarketError> {
+        let token_amount = Self::to_token_units(env, amount, true)?;
+        token_client.transfer(from, &env.current_contract_address(), &token_amount);
+        Ok(())
+    }
+
+    /// Transfer a protocol fee from the trader straight to its recipient.
+    fn transfer_fee(
+        env: &Env,
+        token_client: &token::Client,
+        from: &Address,
+        recipient: &Address,
+        fee: i128,
+    ) -> Result<(), MarketError> {
+        let token_amount = Self::to_token_units(env, fee, false)?;
+        token_client.transfer(from, recipient, &token_amount);
+        Ok(())
+    }
+
+    /// Convert an internal amount (COLLATERAL_DECIMALS) to collateral token
+    /// units. Tokens with fewer decimals lose precision: amounts paid into
+    /// the market round up and everything else rounds down, so the dust
+    /// stays with the market.
+    fn to_token_units(env: &Env, amount: i128, round_up: bool) -> Result<i128, MarketError> {
+        let decimals: u32 = env
+            .storage()
+            .instance()
+            .get(&ExtDataKey::CollateralDecimals)
+            .unwrap_or(COLLATERAL_DECIMALS);
+        if decimals >= COLLATERAL_DECIMALS {
+            let factor = 10i128.pow(decimals - COLLATERAL_DECIMALS);
+            return amount.checked_mul(factor).ok_or(MarketError::Overflow);
+        }
+
+        let divisor = 10i128.pow(COLLATERAL_DECIMALS - decimals);
+        let rounded = if round_up {
+            amount
+                .checked_add(divisor - 1)
+                .ok_or(MarketError::Overflow)?
+        } else {
+            amount
+        };
+        rounded.checked_div(divisor).ok_or(MarketError::Overflow)
+    }
+
+    fn require_no_proposal(env: &Env) -> Result<(), MarketError> {
+        if env.storage().instance().has(&DataKey::ProposedOutcome) {
+            return Err(MarketError::DisputeWindowOpen);
+        }
+        Ok(())
+    }
+
+    fn require_claim_open(env: &Env) -> Result<(), MarketError> {
+        let deadline: Option<u32> = env.storage().instance().get(&DataKey::ClaimDeadline);
+        match deadline {
+            Some(deadline) if env.ledger().sequence() > deadline => Err(MarketError::ClaimExpired),
+            _ => Ok(()),
+        }
+    }
+
+    fn require_resolved(env: &Env) -> Result<(), MarketError> {
+        if Self::is_cancelled(env) {
+            return Err(MarketError::Cancelled);
+        }
+        let resolved: bool = env
+            .storage()
+            .instance()
+            .get(&DataKey::Resolved)
+            .ok_or(MarketError::StorageCorrupted)?;
+        if !resolved {
+            return Err(MarketError::NotResolved);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::LN2_SCALED;
+    use soroban_sdk::{
+        testutils::{Address as _, Events, Ledger},
+        token::StellarAssetClient,
+        vec, Env, IntoVal, Val, Vec,
+    };
+
+    /// Config with every optional setting left at its neutral value.
+    fn test_config() -> MarketConfig {
+        MarketConfig {
+            factory: None,
+            metadata_base_uri: None,
+            protocol_fee_bps: 0,
+            protocol_fee_recipient: None,
+            claim_fee_bps: CLAIM_FEE_BPS,
+            claim_deadline_ledgers: None,
+            resolver: None,
+            dispute_window_ledgers: None,
+            safety_multiplier_bps: 0,
+            outcome_labels: None,
+            funder: None,
+            min_trade_amount: 0,
+            require_nonzero_winner: false,
+            rewards_address: None,
+            rewards_share_bps: 0,
+            twap_window_ledgers: None,
+        }
+    }
+
+    /// Set up token and oracle, then register initialized market contract.
+    /// Returns (env, contract_id, oracle, token_address)
+    fn setup_test() -> (Env, Address, Address, Address) {
+        setup_test_with_params(100 * SCALE_FACTOR, 70 * SCALE_FACTOR)
+    }
+
+    /// Set up with custom liquidity and funding params.
+    fn setup_test_with_params(
+        liquidity_param: i128,
+        initial_funding: i128,
+    ) -> (Env, Address, Address, Address) {
+        setup_test_with_config(liquidity_param, initial_funding, test_config())
+    }
+
+    /// Set up with custom params and settings, e.g.
+    /// `MarketConfig { claim_fee_bps: 0, ..test_config() }`.
+    /// Returns (env, contract_id, oracle, token_address)
+    fn setup_test_with_config(
+        liquidity_param: i128,
+        initial_funding: i128,
+        config: MarketConfig,
+    ) -> (Env, Address, Address, Address) {
+        let env = Env::default();
+        let (contract_id, oracle, token_address) =
+            register_with_config(&env, liquidity_param, initial_funding, config);
+        (env, contract_id, oracle, token_address)
+    }
+
+    /// Register a market in an existing env, for settings holding addresses
+    /// or strings that must be created in it.
+    /// Returns (contract_id, oracle, token_address)
+    fn register_with_config(
+        env: &Env,
+        liquidity_param: i128,
+        initial_funding: i128,
+        config: MarketConfig,
+    ) -> (Address, Address, Address) {
+        env.mock_all_auths();
+
+        let oracle = Address::generate(env);
+
+        // Create a test token
+        let token_admin = Address::generate(env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+
+        // Mint tokens to oracle for initial funding
+        StellarAssetClient::new(env, &token_address).mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        // Register contract with constructor args (this calls __constructor which calls initialize)
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address.clone(),
+                liquidity_param,
+                String::from_str(env, "QmTest"),
+                initial_funding,
+                config,
+            ),
+        );
+
+        (contract_id, oracle, token_address)
+    }
+
+    /// Market where `user` holds 10 YES tokens.
+    /// Returns (env, contract_id, oracle, token_address, user)
+    fn setup_with_position(config: MarketConfig) -> (Env, Address, Address, Address, Address) {
+        let (env, contract_id, oracle, token_address) =
+            setup_test_with_config(100 * SCALE_FACTOR, 70 * SCALE_FACTOR, config);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        LmsrMarketClient::new(&env, &contract_id).buy(
+            &user,
+            &0,
+            &(10 * SCALE_FACTOR),
+            &(50 * SCALE_FACTOR),
+        );
+
+        (env, contract_id, oracle, token_address, user)
+    }
+
+    /// Market resolved YES where `user` holds 10 winning YES tokens.
+    /// Returns (env, contract_id, oracle, token_address, user)
+    fn setup_resolved_with_config(
+        config: MarketConfig,
+    ) -> (Env, Address, Address, Address, Address) {
+        let (env, contract_id, oracle, token_address, user) = setup_with_position(config);
+        LmsrMarketClient::new(&env, &contract_id).resolve(&oracle, &0);
+        (env, contract_id, oracle, token_address, user)
+    }
+
+    #[test]
+    fn test_initialize() {
+        // setup_test() now registers with constructor which initializes
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let b = 100 * SCALE_FACTOR;
+
+        // Verify initialization worked
+        assert_eq!(client.get_oracle(), oracle);
+        assert_eq!(client.get_liquidity_param(), b);
+    }
+
+    #[test]
+    fn test_buy() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Create a user and mint tokens
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy YES tokens
+        let amount = 10 * SCALE_FACTOR;
+        let max_cost = 50 * SCALE_FACTOR;
+        let cost = client.buy(&user, &0, &amount, &max_cost);
+
+        assert!(cost > 0);
+        assert_eq!(client.get_balance(&user, &0), amount);
+    }
+
+    #[test]
+    fn test_resolve_and_claim() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // User buys YES tokens
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        let amount = 10 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+
+        // Resolve market with YES winning
+        client.resolve(&oracle, &0);
+
+        // User claims winnings (minus 2% fee)
+        let payout = client.claim(&user);
+        let expected_payout = amount - (amount * CLAIM_FEE_BPS / BPS_DENOMINATOR);
+        assert_eq!(payout, expected_payout);
+    }
+
+    #[test]
+    fn test_price_at_equilibrium() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // At equilibrium (no tokens sold), price should be ~0.5
+        let price_yes = client.get_price(&0);
+        let price_no = client.get_price(&1);
+
+        // Allow for some floating point error
+        assert!(
+            (4_900_000..=5_100_000).contains(&price_yes),
+            "Expected price_yes near 5_000_000, got {}",
+            price_yes
+        );
+        assert!(
+            (4_900_000..=5_100_000).contains(&price_no),
+            "Expected price_no near 5_000_000, got {}",
+            price_no
+        );
+    }
+
+    // --- Authorization tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_resolve_by_non_oracle_fails() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Try to resolve with non-oracle address
+        let attacker = Address::generate(&env);
+        client.resolve(&attacker, &0); // Should panic with Unauthorized
+    }
+
+    // --- Double-claim prevention tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #13)")] // NothingToClaim = 13
+    fn test_double_claim_fails() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // User buys YES tokens
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        let amount = 10 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+
+        // Resolve market with YES winning
+        client.resolve(&oracle, &0);
+
+        // First claim succeeds
+        client.claim(&user);
+
+        // Second claim should fail
+        client.claim(&user); // Should panic with NothingToClaim
+    }
+
+    // --- Slippage protection tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #8)")] // SlippageExceeded = 8
+    fn test_buy_slippage_exceeded() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Try to buy with max_cost = 0 (impossible)
+        let amount = 10 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &0); // Should panic with SlippageExceeded
+    }
+
+    #[test]
+    fn test_buy_limit_price_trips_despite_generous_budget() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        let amount = 10 * SCALE_FACTOR;
+        let (cost, _) = client.get_quote(&0, &amount);
+        let avg_price = cost * SCALE_FACTOR / amount;
+        // The whole balance would pass `buy`'s max_cost check
+        assert!(cost < 100 * SCALE_FACTOR);
+
+        // Just below the average fill price reverts
+        let result = client.try_buy_limit_price(&user, &0, &amount, &(avg_price - 1));
+        assert_eq!(result, Err(Ok(MarketError::SlippageExceeded)));
+        assert_eq!(client.get_balance(&user, &0), 0);
+
+        let paid = client.buy_limit_price(&user, &0, &amount, &avg_price);
+        assert_eq!(paid, cost);
+        assert_eq!(client.get_balance(&user, &0), amount);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #9)")] // ReturnTooLow = 9
+    fn test_sell_min_return_not_met() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // First buy some tokens
+        let amount = 10 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+
+        // Try to sell with impossibly high min_return
+        client.sell(&user, &0, &amount, &(i128::MAX / 2)); // Should panic with ReturnTooLow
+    }
+
+    // --- Sell function tests ---
+
+    #[test]
+    fn test_sell_basic() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy tokens
+        let amount = 10 * SCALE_FACTOR;
+        let buy_cost = client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_balance(&user, &0), amount);
+
+        // Sell half the tokens
+        let sell_amount = 5 * SCALE_FACTOR;
+        let sell_return = client.sell(&user, &0, &sell_amount, &0);
+        assert!(sell_return > 0, "Sell return should be positive");
+        assert_eq!(client.get_balance(&user, &0), amount - sell_amount);
+
+        // Sell return should be less than buy cost (round-trip loss expected)
+        assert!(
+            sell_return < buy_cost,
+            "Selling should return less than buying cost due to price movement"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #7)")] // InsufficientBalance = 7
+    fn test_sell_insufficient_balance() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy a small amount
+        client.buy(&user, &0, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Try to sell more than owned
+        client.sell(&user, &0, &(10 * SCALE_FACTOR), &0); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #3)")] // AlreadyResolved = 3
+    fn test_sell_after_resolution_fails() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy tokens
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Resolve market
+        client.resolve(&oracle, &0);
+
+        // Try to sell after resolution
+        client.sell(&user, &0, &(5 * SCALE_FACTOR), &0); // Should panic
+    }
+
+    #[test]
+    fn test_sell_all_exits_position() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Build the position over several buys
+        client.buy(&user, &0, &(3 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &0, &(4 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &0, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_balance(&user, &0), 12 * SCALE_FACTOR);
+
+        let quoted = client.get_close_all_quote(&user);
+        let balance_before = token_client.balance(&user);
+
+        let returned = client.sell_all(&user, &0, &0);
+
+        assert!(returned > 0);
+        assert_eq!(returned, quoted);
+        assert_eq!(client.get_balance(&user, &0), 0);
+        assert_eq!(token_client.balance(&user), balance_before + returned);
+        let (yes_sold, _, _, _) = client.get_state();
+        assert_eq!(yes_sold, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #7)")] // InsufficientBalance = 7
+    fn test_sell_all_empty_position_fails() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.sell_all(&Address::generate(&env), &0, &0); // Should panic
+    }
+
+    #[test]
+    fn test_transfer_position_then_recipient_claims() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&alice, &(100 * SCALE_FACTOR));
+        client.buy(&alice, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        let basis = client.get_cost_basis(&alice, &0);
+        let state_before = client.get_state();
+
+        client.transfer_position(&alice, &bob, &0, &(4 * SCALE_FACTOR));
+
+        assert_eq!(client.get_balance(&alice, &0), 6 * SCALE_FACTOR);
+        assert_eq!(client.get_balance(&bob, &0), 4 * SCALE_FACTOR);
+        assert_eq!(
+            client.get_cost_basis(&alice, &0) + client.get_cost_basis(&bob, &0),
+            basis
+        );
+        assert_eq!(client.get_state(), state_before);
+
+        client.resolve(&oracle, &0);
+        assert_eq!(client.get_winner_count(), 2);
+
+        let payout = client.claim(&bob);
+        assert!(payout > 0);
+        assert_eq!(token_client.balance(&bob), payout);
+        assert_eq!(client.get_balance(&bob, &0), 0);
+    }
+
+    #[test]
+    fn test_transfer_position_rejects_self_and_overdraw() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&alice, &(100 * SCALE_FACTOR));
+        client.buy(&alice, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        assert_eq!(
+            client.try_transfer_position(&alice, &alice, &1, &SCALE_FACTOR),
+            Err(Ok(MarketError::SelfTransfer))
+        );
+        assert_eq!(
+            client.try_transfer_position(&alice, &bob, &1, &(6 * SCALE_FACTOR)),
+            Err(Ok(MarketError::InsufficientBalance))
+        );
+        assert_eq!(
+            client.try_transfer_position(&alice, &bob, &0, &SCALE_FACTOR),
+            Err(Ok(MarketError::InsufficientBalance))
+        );
+        assert_eq!(client.get_balance(&alice, &1), 5 * SCALE_FACTOR);
+        assert_eq!(client.get_balance(&bob, &1), 0);
+    }
+
+    // --- Market lifecycle error state tests ---
+
+    // Note: test_buy_on_uninitialized_contract was removed because with the constructor,
+    // contracts are always initialized at deployment time.
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #3)")] // AlreadyResolved = 3
+    fn test_buy_on_resolved_market() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Resolve immediately
+        client.resolve(&oracle, &0);
+
+        // Try to buy after resolution
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #4)")] // NotResolved = 4
+    fn test_claim_on_unresolved_market() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy tokens but don't resolve
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Try to claim without resolution
+        client.claim(&user); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #13)")] // NothingToClaim = 13
+    fn test_loser_cannot_claim() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // User buys NO tokens
+        client.buy(&user, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Resolve market with YES winning (user loses)
+        client.resolve(&oracle, &0);
+
+        // Loser tries to claim
+        client.claim(&user); // Should panic with NothingToClaim
+    }
+
+    // --- Withdraw remaining tests ---
+
+    #[test]
+    fn test_withdraw_remaining_after_claims() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let winner = Address::generate(&env);
+        let loser = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&winner, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&loser, &(100 * SCALE_FACTOR));
+
+        // Winner buys YES, loser buys NO
+        let yes_cost = client.buy(&winner, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        let no_cost = client.buy(&loser, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Check pool increased (initial_funding is 70 * SCALE_FACTOR from setup_test)
+        let (_, _, pool_before, _) = client.get_state();
+        assert_eq!(pool_before, 70 * SCALE_FACTOR + yes_cost + no_cost);
+
+        // Resolve: YES wins
+        client.resolve(&oracle, &0);
+
+        // Winner claims (minus 2% fee)
+        let payout = client.claim(&winner);
+        let expected_payout =
+            10 * SCALE_FACTOR - (10 * SCALE_FACTOR * CLAIM_FEE_BPS / BPS_DENOMINATOR);
+        assert_eq!(payout, expected_payout); // 1:1 redemption minus 2% fee
+
+        // Pool should have remaining funds (loser's money + part of initial funding)
+        let (_, _, pool_after_claim, _) = client.get_state();
+        assert!(pool_after_claim > 0, "Pool should have remaining funds");
+
+        // Oracle withdraws the residual pool and the claim fee separately
+        let withdrawn = client.withdraw_remaining(&oracle);
+        let fees = client.withdraw_fees(&oracle);
+        assert_eq!(fees, 10 * SCALE_FACTOR - expected_payout);
+        assert_eq!(withdrawn + fees, pool_after_claim);
+
+        // Pool should be zero now
+        let (_, _, pool_final, _) = client.get_state();
+        assert_eq!(pool_final, 0);
+    }
+
+    #[test]
+    fn test_fees_and_residual_pool_withdraw_independently() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let winner = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&winner, &(100 * SCALE_FACTOR));
+        client.buy(&winner, &0, &(50 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        client.resolve(&oracle, &0);
+
+        // Nothing accrues until someone claims
+        assert_eq!(client.get_accrued_fees(), 0);
+        assert_eq!(
+            client.try_withdraw_fees(&oracle),
+            Err(Ok(MarketError::NothingToClaim))
+        );
+
+        // The residual pool excludes the fees still to come from the claim
+        let residual = client.withdraw_remaining(&oracle);
+
+        client.claim(&winner);
+        let fees = client.get_accrued_fees();
+        assert_eq!(fees, 50 * SCALE_FACTOR * CLAIM_FEE_BPS / BPS_DENOMINATOR);
+        assert_eq!(client.get_state().2, fees);
+
+        // Fees stay out of withdraw_remaining and only go through withdraw_fees
+        assert_eq!(
+            client.try_withdraw_remaining(&oracle),
+            Err(Ok(MarketError::NothingToClaim))
+        );
+        assert_eq!(
+            client.try_withdraw_fees(&Address::generate(&env)),
+            Err(Ok(MarketError::Unauthorized))
+        );
+        let oracle_before = token_client.balance(&oracle);
+        assert_eq!(client.withdraw_fees(&oracle), fees);
+        assert_eq!(token_client.balance(&oracle), oracle_before + fees);
+
+        assert_eq!(client.get_accrued_fees(), 0);
+        assert_eq!(client.get_state().2, 0);
+        assert!(residual > 0);
+    }
+
+    #[test]
+    fn test_withdraw_remaining_no_trades() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // No trades, just resolve
+        client.resolve(&oracle, &0);
+
+        // Oracle can withdraw entire initial funding (70 * SCALE_FACTOR from setup_test)
+        let withdrawn = client.withdraw_remaining(&oracle);
+        assert_eq!(withdrawn, 70 * SCALE_FACTOR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #4)")] // NotResolved = 4
+    fn test_withdraw_remaining_before_resolve() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Try to withdraw before resolution
+        client.withdraw_remaining(&oracle); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_withdraw_remaining_non_oracle() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.resolve(&oracle, &0);
+
+        // Non-oracle tries to withdraw
+        let attacker = Address::generate(&env);
+        client.withdraw_remaining(&attacker); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #13)")] // NothingToClaim = 13
+    fn test_withdraw_remaining_twice() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.resolve(&oracle, &0);
+
+        // First withdrawal succeeds
+        client.withdraw_remaining(&oracle);
+
+        // Second withdrawal should fail
+        client.withdraw_remaining(&oracle); // Should panic
+    }
+
+    #[test]
+    fn test_withdraw_remaining_reserves_for_unclaimed() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let winner = Address::generate(&env);
+        let loser = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&winner, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&loser, &(100 * SCALE_FACTOR));
+
+        // Winner buys YES, loser buys NO
+        let yes_cost = client.buy(&winner, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        let no_cost = client.buy(&loser, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Initial funding is 70 * SCALE_FACTOR
+        let initial_funding = 70 * SCALE_FACTOR;
+        let pool_before = initial_funding + yes_cost + no_cost;
+
+        // Resolve: YES wins
+        client.resolve(&oracle, &0);
+
+        // Winner does NOT claim yet
+        // Oracle tries to withdraw - should only get losers' funds, not winners' reserved funds
+
+        // Reserved for winner: all 10 tokens, covering the 9.8 payout and the
+        // claim fee that accrues when it is claimed
+        let winner_tokens = 10 * SCALE_FACTOR;
+        let reserved = winner_tokens;
+        let fee = winner_tokens * CLAIM_FEE_BPS / BPS_DENOMINATOR;
+
+        // Withdrawable = pool - reserved
+        let expected_withdrawable = pool_before - reserved;
+
+        let withdrawn = client.withdraw_remaining(&oracle);
+        assert_eq!(withdrawn, expected_withdrawable);
+
+        // Pool should now have exactly reserved amount
+        let (_, _, pool_after, _) = client.get_state();
+        assert_eq!(pool_after, reserved);
+
+        // Now winner claims - should still work
+        let payout = client.claim(&winner);
+        assert_eq!(payout, reserved - fee);
+
+        // Only the accrued claim fee is left
+        let (_, _, pool_final, _) = client.get_state();
+        assert_eq!(pool_final, fee);
+        assert_eq!(client.get_accrued_fees(), fee);
+    }
+
+    #[test]
+    fn test_winner_can_claim_after_oracle_withdrawal() {
+        // Verify that the bug is fixed: winner can claim even after oracle withdraws
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let winner = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&winner, &(100 * SCALE_FACTOR));
+
+        // Winner buys YES
+        client.buy(&winner, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Resolve: YES wins
+        client.resolve(&oracle, &0);
+
+        // Oracle withdraws first (before winner claims)
+        let withdrawn = client.withdraw_remaining(&oracle);
+        assert!(withdrawn > 0, "Oracle should withdraw something");
+
+        // Winner claims after oracle withdrawal - THIS USED TO FAIL
+        let payout = client.claim(&winner);
+        let expected = 10 * SCALE_FACTOR * (BPS_DENOMINATOR - CLAIM_FEE_BPS) / BPS_DENOMINATOR;
+        assert_eq!(payout, expected);
+    }
+
+    // --- Funding buffer tests ---
+
+    #[test]
+    fn test_safety_multiplier_accepts_buffered_funding() {
+        // 1.5x of b * ln(2) ≈ 103.97
+        setup_test_with_config(
+            100 * SCALE_FACTOR,
+            104 * SCALE_FACTOR,
+            MarketConfig {
+                safety_multiplier_bps: 15_000,
+                ..test_config()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidAmount")]
+    fn test_safety_multiplier_rejects_minimum_funding() {
+        // Enough for the base requirement, short of the 1.5x buffer
+        setup_test_with_config(
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                safety_multiplier_bps: 15_000,
+                ..test_config()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidConfig")]
+    fn test_safety_multiplier_below_one_rejected() {
+        setup_test_with_config(
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                safety_multiplier_bps: 5_000,
+                ..test_config()
+            },
+        );
+    }
+
+    #[test]
+    fn test_required_funding_is_initialize_minimum() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let required = client.required_funding(&(100 * SCALE_FACTOR));
+        assert_eq!(required, 100 * LN2_SCALED);
+        setup_test_with_config(
+            100 * SCALE_FACTOR,
+            required,
+            MarketConfig {
+                safety_multiplier_bps: 0,
+                ..test_config()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidAmount")]
+    fn test_required_funding_minus_one_rejected() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let required = client.required_funding(&(100 * SCALE_FACTOR));
+        setup_test_with_config(
+            100 * SCALE_FACTOR,
+            required - 1,
+            MarketConfig {
+                safety_multiplier_bps: 0,
+                ..test_config()
+            },
+        );
+    }
+
+    // --- Quote validation tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
+    fn test_get_quote_invalid_outcome() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Try to get quote with invalid outcome
+        client.get_quote(&99, &(10 * SCALE_FACTOR));
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
+    fn test_get_quote_zero_amount() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Try to get quote with zero amount
+        client.get_quote(&0, &0);
+    }
+
+    #[test]
+    fn test_get_quote_detailed_price_impact() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let amount = 30 * SCALE_FACTOR;
+        let (cost, avg_price, price_before, price_after) = client.get_quote_detailed(&0, &amount);
+
+        assert_eq!((cost, price_after), client.get_quote(&0, &amount));
+        assert_eq!(price_before, client.get_price(&0));
+        assert!(price_before <= avg_price && avg_price <= price_after);
+        assert!(price_before < price_after);
+    }
+
+    // --- Double resolution prevention test ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #3)")] // AlreadyResolved = 3
+    fn test_double_resolve_fails() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // First resolution succeeds
+        client.resolve(&oracle, &0);
+
+        // Second resolution should fail
+        client.resolve(&oracle, &1); // Should panic with AlreadyResolved
+    }
+
+    // --- Insufficient initial funding test ---
+
+    #[test]
+    #[should_panic(expected = "InvalidAmount")]
+    fn test_initialize_insufficient_funding() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let oracle = Address::generate(&env);
+
+        // Create a test token
+        let token_admin = Address::generate(&env);
+        let token_contract = env.register_stellar_asset_contract_v2(token_admin.clone());
+        let token_address = token_contract.address();
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        let b = 100 * SCALE_FACTOR;
+        // Required funding is b * ln(2) ≈ 69.3, so 50 is insufficient
+        let insufficient_funding = 50 * SCALE_FACTOR;
+
+        // Constructor should panic with InvalidAmount
+        let _contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address.clone(),
+                b,
+                String::from_str(&env, "QmTest"),
+                insufficient_funding,
+                test_config(),
+            ),
+        );
+    }
+
+    // --- Invalid outcome tests for buy/sell/resolve ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
+    fn test_buy_invalid_outcome() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Try to buy with invalid outcome (99)
+        client.buy(&user, &99, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
+    fn test_sell_invalid_outcome() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // First buy some valid tokens
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Try to sell with invalid outcome (99)
+        client.sell(&user, &99, &(5 * SCALE_FACTOR), &0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
+    fn test_resolve_invalid_outcome() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Try to resolve with invalid outcome (99)
+        client.resolve(&oracle, &99);
+    }
+
+    // --- Zero/negative amount tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
+    fn test_buy_zero_amount() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Try to buy zero amount
+        client.buy(&user, &0, &0, &(50 * SCALE_FACTOR));
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
+    fn test_buy_negative_amount() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Try to buy negative amount
+        client.buy(&user, &0, &(-10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+    }
+
+    // --- Multiple users scenario ---
+
+    #[test]
+    fn test_multiple_users_claim_correctly() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+
+        // User1 buys YES, User2 buys NO
+        let user1 = Address::generate(&env);
+        let user2 = Address::generate(&env);
+        token_admin_client.mint(&user1, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&user2, &(100 * SCALE_FACTOR));
+
+        let amount1 = 10 * SCALE_FACTOR;
+        let amount2 = 8 * SCALE_FACTOR;
+        client.buy(&user1, &0, &amount1, &(50 * SCALE_FACTOR)); // YES
+        client.buy(&user2, &1, &amount2, &(50 * SCALE_FACTOR)); // NO
+
+        // Resolve with YES winning
+        client.resolve(&oracle, &0);
+
+        // User1 (winner) claims successfully (minus 2% fee)
+        let payout1 = client.claim(&user1);
+        let expected_payout1 = amount1 - (amount1 * CLAIM_FEE_BPS / BPS_DENOMINATOR);
+        assert_eq!(payout1, expected_payout1);
+
+        // User2's balance should be 0 for winning outcome
+        assert_eq!(client.get_balance(&user2, &0), 0);
+    }
+
+    // --- Claim fee tests ---
+
+    #[test]
+    fn test_claim_fee_calculation() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy exactly 100 tokens (for easy math)
+        let amount = 100 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(100 * SCALE_FACTOR));
+
+        let (_, _, pool_before, _) = client.get_state();
+
+        // Resolve: YES wins
+        client.resolve(&oracle, &0);
+
+        // Claim and verify 2% fee
+        let payout = client.claim(&user);
+
+        // 2% of 100 = 2, so payout should be 98
+        let expected_fee = 2 * SCALE_FACTOR;
+        let expected_payout = amount - expected_fee;
+        assert_eq!(payout, expected_payout, "Expected 98% payout after 2% fee");
+
+        // Verify fee stayed in pool
+        let (_, _, pool_after, _) = client.get_state();
+        // Pool should have: pool_before - payout = pool_before - (amount - fee) = pool_before - amount + fee
+        // Since winning tokens are 1:1 redeemable from pool, the fee portion remains
+        assert_eq!(
+            pool_after,
+            pool_before - expected_payout,
+            "Fee should remain in pool"
+        );
+    }
+
+    #[test]
+    fn test_winner_payout_rate_matches_claim() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+
+        let rate = client.get_winner_payout_rate();
+        assert_eq!(rate, 9_800_000);
+
+        let amount = 100 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(100 * SCALE_FACTOR));
+        client.resolve(&oracle, &0);
+
+        let payout = client.claim(&user);
+        assert_eq!(payout * SCALE_FACTOR / amount, rate);
+    }
+
+    #[test]
+    fn test_oracle_collects_accumulated_fees_from_multiple_claims() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+
+        // Three users: two winners (YES), one loser (NO)
+        let winner1 = Address::generate(&env);
+        let winner2 = Address::generate(&env);
+        let loser = Address::generate(&env);
+        token_admin_client.mint(&winner1, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&winner2, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&loser, &(100 * SCALE_FACTOR));
+
+        // Winner1 buys 100 YES, Winner2 buys 50 YES, Loser buys 30 NO
+        let amount1 = 100 * SCALE_FACTOR;
+        let amount2 = 50 * SCALE_FACTOR;
+        let amount3 = 30 * SCALE_FACTOR;
+        client.buy(&winner1, &0, &amount1, &(100 * SCALE_FACTOR));
+        client.buy(&winner2, &0, &amount2, &(100 * SCALE_FACTOR));
+        client.buy(&loser, &1, &amount3, &(50 * SCALE_FACTOR));
+
+        let (_, _, _pool_after_buys, _) = client.get_state();
+
+        // Resolve: YES wins
+        client.resolve(&oracle, &0);
+
+        // Both winners claim
+        let payout1 = client.claim(&winner1);
+        let payout2 = client.claim(&winner2);
+
+        // Verify payouts are 98% of token amounts
+        let expected_fee1 = amount1 * CLAIM_FEE_BPS / BPS_DENOMINATOR; // 2% of 100 = 2
+        let expected_fee2 = amount2 * CLAIM_FEE_BPS / BPS_DENOMINATOR; // 2% of 50 = 1
+        assert_eq!(payout1, amount1 - expected_fee1);
+        assert_eq!(payout2, amount2 - expected_fee2);
+
+        // Total fees accumulated: 2 + 1 = 3 SCALE_FACTOR
+        let total_fees = expected_fee1 + expected_fee2;
+
+        // Pool should have: initial_funding + all_buy_costs - winner_payouts
+        // Which includes: loser's bet + accumulated fees
+        let (_, _, pool_after_claims, _) = client.get_state();
+        assert!(pool_after_claims > 0, "Pool should have remaining funds");
+
+        // Oracle withdraws the accumulated fees and the residual pool
+        // (loser's funds and leftover initial funding) separately
+        assert_eq!(client.get_accrued_fees(), total_fees);
+        let fees = client.withdraw_fees(&oracle);
+        assert_eq!(fees, total_fees);
+        let withdrawn = client.withdraw_remaining(&oracle);
+        assert_eq!(withdrawn + fees, pool_after_claims);
+
+        // Pool should be empty now
+        let (_, _, pool_final, _) = client.get_state();
+        assert_eq!(pool_final, 0);
+    }
+
+    #[test]
+    fn test_claim_fee_small_amount_truncation() {
+        // Documents expected behavior: fee rounds to 0 for tiny amounts
+        // due to integer division truncation.
+        // This is acceptable as these are dust-level amounts.
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy 49 units (below threshold where fee = 0)
+        // Fee calculation: 49 * 200 / 10000 = 9800 / 10000 = 0 (integer truncation)
+        let tiny_amount: i128 = 49;
+        client.buy(&user, &0, &tiny_amount, &SCALE_FACTOR);
+
+        client.resolve(&oracle, &0);
+
+        let payout = client.claim(&user);
+
+        // With amount = 49, fee = 49 * 200 / 10000 = 0 (truncated)
+        // So user gets full amount back (no fee on dust amounts)
+        assert_eq!(
+            payout, tiny_amount,
+            "Dust amounts should have zero fee due to integer truncation"
+        );
+    }
+
+    // --- Sell all tokens to equilibrium ---
+
+    #[test]
+    fn test_sell_all_returns_to_near_equilibrium() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(200 * SCALE_FACTOR));
+
+        // Buy tokens
+        let amount = 10 * SCALE_FACTOR;
+        let buy_cost = client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+
+        // Get state after buy
+        let (q_yes_after_buy, _, _, _) = client.get_state();
+        assert_eq!(q_yes_after_buy, amount);
+
+        // Sell all back
+        let sell_return = client.sell(&user, &0, &amount, &0);
+
+        // Get state after sell
+        let (q_yes_after_sell, q_no_after_sell, _, _) = client.get_state();
+        assert_eq!(q_yes_after_sell, 0);
+        assert_eq!(q_no_after_sell, 0);
+
+        // User balance should be 0
+        assert_eq!(client.get_balance(&user, &0), 0);
+
+        // LMSR is symmetric: buying and immediately selling the same amount
+        // returns exactly what was paid (no spread). The sell_return should
+        // equal buy_cost when returning to the same state.
+        assert_eq!(
+            sell_return, buy_cost,
+            "LMSR symmetric round-trip: sell_return={}, buy_cost={}",
+            sell_return, buy_cost
+        );
+    }
+
+    // --- get_sell_quote tests ---
+
+    #[test]
+    fn test_get_sell_quote_basic() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Buy tokens first
+        let amount = 10 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+
+        // Get sell quote
+        let (return_amount, price_after) = client.get_sell_quote(&0, &amount);
+
+        assert!(return_amount > 0, "Sell return should be positive");
+        assert!(
+            (0..=SCALE_FACTOR).contains(&price_after),
+            "Price after should be in [0, 1]"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
+    fn test_get_sell_quote_invalid_outcome() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // Try to get sell quote with invalid outcome
+        client.get_sell_quote(&99, &(10 * SCALE_FACTOR));
+    }
+
+    // --- Settlement TWAP tests ---
+
+    #[test]
+    fn test_settlement_price_uses_twap() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let whale = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&whale, &(200 * SCALE_FACTOR));
+
+        // Market sits at 50% for a full window, then a large YES buy lands
+        // right before resolution
+        let start = env.ledger().sequence();
+        env.ledger()
+            .set_sequence_number(start + storage::TWAP_WINDOW_LEDGERS);
+        client.buy(&whale, &0, &(100 * SCALE_FACTOR), &(200 * SCALE_FACTOR));
+        let spot_yes = client.get_price(&0);
+
+        env.ledger()
+            .set_sequence_number(start + storage::TWAP_WINDOW_LEDGERS + 10);
+        client.resolve(&oracle, &0);
+
+        let settlement_yes = client.get_settlement_price(&0);
+        let settlement_no = client.get_settlement_price(&1);
+
+        // Spot jumped well above 50%, but the averaged price barely moved
+        assert!(spot_yes > 7_000_000, "spot_yes = {}", spot_yes);
+        assert!(
+            settlement_yes < spot_yes,
+            "settlement {} should be below spot {}",
+            settlement_yes,
+            spot_yes
+        );
+        assert!(
+            (5_000_000..5_300_000).contains(&settlement_yes),
+            "settlement_yes = {}",
+            settlement_yes
+        );
+        assert_eq!(settlement_yes + settlement_no, SCALE_FACTOR);
+    }
+
+    #[test]
+    fn test_settlement_price_without_elapsed_ledgers_is_spot() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let spot_yes = client.get_price(&0);
+        client.resolve(&oracle, &1);
+
+        assert_eq!(client.get_settlement_price(&0), spot_yes);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #4)")] // NotResolved = 4
+    fn test_settlement_price_before_resolution() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.get_settlement_price(&0);
+    }
+
+    #[test]
+    fn test_settlement_window_bounded_after_quiet_period() {
+        let window = 100;
+        let (env, contract_id, oracle, token_address) = setup_test_with_config(
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                twap_window_ledgers: Some(window),
+                ..test_config()
+            },
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let whale = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&whale, &(200 * SCALE_FACTOR));
+
+        // YES jumps right after creation, then nothing trades for ten windows
+        let start = env.ledger().sequence();
+        env.ledger().set_sequence_number(start + 10);
+        let amount = 100 * SCALE_FACTOR;
+        client.buy(&whale, &0, &amount, &(200 * SCALE_FACTOR));
+        let high = client.get_price(&0);
+
+        env.ledger().set_sequence_number(start + 10 * window);
+        client.sell(&whale, &0, &amount, &0);
+        let low = client.get_price(&0);
+
+        env.ledger()
+            .set_sequence_number(start + 10 * window + window / 2);
+        client.resolve(&oracle, &0);
+
+        // Only the last window of the quiet stretch counts, not all ten
+        let expected =
+            (high * window as i128 + low * (window / 2) as i128) / (window + window / 2) as i128;
+        assert_eq!(client.get_settlement_price(&0), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidConfig")]
+    fn test_zero_twap_window_rejected() {
+        setup_test_with_config(
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                twap_window_ledgers: Some(0),
+                ..test_config()
+            },
+        );
+    }
+
+    // --- Init config validation tests ---
+
+    /// Register a market at a known address so tests can reference it in args.
+    fn register_market_at(
+        env: &Env,
+        market: &Address,
+        oracle: &Address,
+        token_address: &Address,
+        initial_funding: i128,
+    ) {
+        env.register_at(
+            market,
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address.clone(),
+                100 * SCALE_FACTOR,
+                String::from_str(env, "QmTest"),
+                initial_funding,
+                test_config(),
+            ),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "InvalidConfig")]
+    fn test_initialize_oracle_is_market_itself() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let market = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+
+        register_market_at(&env, &market, &market, &token_address, 70 * SCALE_FACTOR);
+    }
+
+    #[test]
+    #[should_panic(expected = "InvalidConfig")]
+    fn test_initialize_collateral_is_market_itself() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let market = Address::generate(&env);
+        let oracle = Address::generate(&env);
+
+        register_market_at(&env, &market, &oracle, &market, 70 * SCALE_FACTOR);
+    }
+
+    #[test]
+    #[should_panic(expected = "InvalidConfig")]
+    fn test_initialize_oracle_is_collateral_token() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let market = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+
+        register_market_at(
+            &env,
+            &market,
+            &token_address,
+            &token_address,
+            70 * SCALE_FACTOR,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "InvalidConfig")]
+    fn test_initialize_funding_above_max() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let market = Address::generate(&env);
+        let oracle = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+
+        register_market_at(&env, &market, &oracle, &token_address, i128::MAX);
+    }
+
+    // --- Close-all quote tests ---
+
+    #[test]
+    fn test_close_all_quote_matches_sequential_sells() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let other = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&other, &(100 * SCALE_FACTOR));
+
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&other, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        let quote = client.get_close_all_quote(&user);
+
+        let yes_return = client.sell(&user, &0, &(20 * SCALE_FACTOR), &0);
+        let no_return = client.sell(&user, &1, &(5 * SCALE_FACTOR), &0);
+
+        assert_eq!(quote, yes_return + no_return);
+        assert_eq!(client.get_close_all_quote(&user), 0);
+    }
+
+    // --- Oracle pause tests ---
+
+    #[test]
+    fn test_oracle_pause_and_unpause() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        assert!(!client.is_paused());
+        client.pause(&oracle);
+        assert!(client.is_paused());
+        assert_eq!(client.get_status(), String::from_str(&env, "closed"));
+
+        let result = client.try_buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(result, Err(Ok(MarketError::Paused)));
+
+        client.unpause(&oracle);
+        assert!(!client.is_paused());
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_balance(&user, &0), 10 * SCALE_FACTOR);
+    }
+
+    #[test]
+    fn test_oracle_pause_allows_claim() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        client.pause(&oracle);
+        client.resolve(&oracle, &0);
+        assert!(client.claim(&user) > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_pause_non_oracle_fails() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.pause(&Address::generate(&env)); // Should panic
+    }
+
+    // --- Factory pause tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #17)")] // Paused = 17
+    fn test_factory_pause_blocks_buy() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let oracle = Address::generate(&env);
+        let factory = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address.clone(),
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                MarketConfig {
+                    factory: Some(factory),
+                    ..test_config()
+                },
+            ),
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.factory_set_paused(&true);
+
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR)); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_factory_pause_without_factory_fails() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // setup_test deploys without a factory
+        client.factory_set_paused(&true);
+    }
+
+    // --- Expected value tests ---
+
+    #[test]
+    fn test_expected_value_near_zero_at_equilibrium() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        // At 50% a position is worth about what it costs; only price impact
+        // (and fixed-point rounding) separates the two
+        let amount = 10 * SCALE_FACTOR;
+        let ev = client.get_expected_value(&0, &amount);
+        assert!(ev.abs() < amount / 20, "EV should be near zero, got {}", ev);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
+    fn test_expected_value_zero_amount() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.get_expected_value(&0, &0);
+    }
+
+    // --- Basket collateral tests ---
+
+    #[test]
+    fn test_buy_with_two_basket_tokens() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let token_a = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        let token_b = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+
+        // Token A trades 1:1 with the base, one unit of token B is worth two
+        client.set_accepted_token(&oracle, &token_a, &SCALE_FACTOR);
+        client.set_accepted_token(&oracle, &token_b, &(2 * SCALE_FACTOR));
+        let float = 100 * SCALE_FACTOR;
+        assert_eq!(client.fund_basket_float(&oracle, &float), float);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_a).mint(&user, &(100 * SCALE_FACTOR));
+        StellarAssetClient::new(&env, &token_b).mint(&user, &(100 * SCALE_FACTOR));
+
+        let base_client = token::Client::new(&env, &token_address);
+        let base_before = base_client.balance(&contract_id);
+        let (_, _, pool_before, _) = client.get_state();
+
+        let (cost_a, _) = client.get_quote(&0, &(10 * SCALE_FACTOR));
+        let paid_a = client.buy_with(
+            &user,
+            &token_a,
+            &0,
+            &(10 * SCALE_FACTOR),
+            &(50 * SCALE_FACTOR),
+        );
+        assert_eq!(paid_a, cost_a);
+
+        let (cost_b, _) = client.get_quote(&1, &(10 * SCALE_FACTOR));
+        let paid_b = client.buy_with(
+            &user,
+            &token_b,
+            &1,
+            &(10 * SCALE_FACTOR),
+            &(50 * SCALE_FACTOR),
+        );
+        assert_eq!(paid_b, (cost_b + 1) / 2);
+
+        // Positions and pool are tracked in base units regardless of payment token
+        assert_eq!(client.get_balance(&user, &0), 10 * SCALE_FACTOR);
+        assert_eq!(client.get_balance(&user, &1), 10 * SCALE_FACTOR);
+        let (_, _, pool_after, _) = client.get_state();
+        assert_eq!(pool_after, pool_before + cost_a + cost_b);
+
+        // The base cost came out of the float; the basket tokens went to the oracle
+        assert_eq!(client.get_basket_float(), float - cost_a - cost_b);
+        assert_eq!(base_client.balance(&contract_id), base_before);
+        assert_eq!(token::Client::new(&env, &token_a).balance(&oracle), paid_a);
+        assert_eq!(token::Client::new(&env, &token_b).balance(&oracle), paid_b);
+        assert_eq!(token::Client::new(&env, &token_a).balance(&contract_id), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #18)")] // TokenNotAccepted = 18
+    fn test_buy_with_unaccepted_token() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let token = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token).mint(&user, &(100 * SCALE_FACTOR));
+
+        client.buy_with(
+            &user,
+            &token,
+            &0,
+            &(10 * SCALE_FACTOR),
+            &(50 * SCALE_FACTOR),
+        );
+    }
+
+    #[test]
+    fn test_buy_with_needs_basket_float() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let basket = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        client.set_accepted_token(&oracle, &basket, &SCALE_FACTOR);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &basket).mint(&user, &(100 * SCALE_FACTOR));
+        assert_eq!(
+            client.try_buy_with(
+                &user,
+                &basket,
+                &0,
+                &(10 * SCALE_FACTOR),
+                &(50 * SCALE_FACTOR)
+            ),
+            Err(Ok(MarketError::InsufficientFloat))
+        );
+
+        // A float smaller than the cost is refused too
+        client.fund_basket_float(&oracle, &SCALE_FACTOR);
+        assert_eq!(
+            client.try_buy_with(
+                &user,
+                &basket,
+                &0,
+                &(10 * SCALE_FACTOR),
+                &(50 * SCALE_FACTOR)
+            ),
+            Err(Ok(MarketError::InsufficientFloat))
+        );
+        assert_eq!(client.get_basket_float(), SCALE_FACTOR);
+    }
+
+    #[test]
+    fn test_basket_buy_then_sell_draws_only_received_base() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let basket = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        client.set_accepted_token(&oracle, &basket, &SCALE_FACTOR);
+        client.fund_basket_float(&oracle, &(100 * SCALE_FACTOR));
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &basket).mint(&user, &(100 * SCALE_FACTOR));
+
+        let base_client = token::Client::new(&env, &token_address);
+        let (_, _, pool_before, _) = client.get_state();
+        let base_before = base_client.balance(&contract_id);
+
+        let amount = 10 * SCALE_FACTOR;
+        let paid = client.buy_with(&user, &basket, &0, &amount, &(50 * SCALE_FACTOR));
+        let returned = client.sell(&user, &0, &amount, &0);
+        assert!(returned <= paid);
+
+        // The sale was paid from base the float moved into the pool, so the
+        // pool never drops below its pre-trade level and the balance still
+        // backs both the pool and what is left of the float
+        let (_, _, pool_after, _) = client.get_state();
+        assert!(pool_after >= pool_before);
+        assert_eq!(base_client.balance(&user), returned);
+        assert_eq!(base_client.balance(&contract_id), base_before - returned);
+        assert_eq!(
+            base_client.balance(&contract_id),
+            pool_after + client.get_basket_float()
+        );
+        assert!(client.get_invariants().iter().all(|(_, holds)| holds));
+    }
+
+    #[test]
+    fn test_withdraw_basket_float() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let base_client = token::Client::new(&env, &token_address);
+        let oracle_before = base_client.balance(&oracle);
+        client.fund_basket_float(&oracle, &(10 * SCALE_FACTOR));
+        assert_eq!(
+            client.try_withdraw_basket_float(&oracle, &(11 * SCALE_FACTOR)),
+            Err(Ok(MarketError::InvalidAmount))
+        );
+        assert_eq!(
+            client.try_withdraw_basket_float(&Address::generate(&env), &SCALE_FACTOR),
+            Err(Ok(MarketError::Unauthorized))
+        );
+
+        // The float stays the oracle's after the market settles
+        client.resolve(&oracle, &0);
+        assert_eq!(
+            client.try_fund_basket_float(&oracle, &SCALE_FACTOR),
+            Err(Ok(MarketError::AlreadyResolved))
+        );
+        assert_eq!(
+            client.withdraw_basket_float(&oracle, &(10 * SCALE_FACTOR)),
+            0
+        );
+        assert_eq!(base_client.balance(&oracle), oracle_before);
+    }
+
+    // --- Trade size tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #19)")] // TradeTooLarge = 19
+    fn test_buy_trade_too_large() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(10_000 * SCALE_FACTOR));
+
+        // b = 100, so 4_600 tokens opens a gap past the supported ~45b
+        client.buy(&user, &0, &(4_600 * SCALE_FACTOR), &(10_000 * SCALE_FACTOR));
+    }
+
+    #[test]
+    fn test_buy_in_balanced_market_beyond_45b() {
+        // b = 1, so both sides reach 60b in steps that keep the gap at 30b
+        let (env, contract_id, _oracle, token_address) =
+            setup_test_with_params(SCALE_FACTOR, 10 * SCALE_FACTOR);
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(200 * SCALE_FACTOR));
+        for outcome in [0u32, 1, 0, 1] {
+            client.buy(&user, &outcome, &(30 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        }
+        assert_eq!(
+            client.get_balances(&user),
+            (60 * SCALE_FACTOR, 60 * SCALE_FACTOR)
+        );
+
+        // Balanced again, so the next buy is priced near 50/50
+        let cost = client.buy(&user, &0, &SCALE_FACTOR, &(10 * SCALE_FACTOR));
+        assert!(
+            cost > SCALE_FACTOR / 2 && cost < SCALE_FACTOR,
+            "cost = {}",
+            cost
+        );
+    }
+
+    // --- Realized P&L tests ---
+
+    #[test]
+    fn test_realized_pnl_through_sell_and_claim() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        let buy_cost = client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_realized_pnl(&user), 0);
+
+        // Selling half realizes the return against half the basis. Sells unwind
+        // the most expensive (last bought) tokens, so this leg is profitable.
+        let sell_return = client.sell(&user, &0, &(10 * SCALE_FACTOR), &0);
+        let basis_sold = buy_cost / 2;
+        let pnl_after_sell = sell_return - basis_sold;
+        assert!(pnl_after_sell > 0, "pnl_after_sell = {}", pnl_after_sell);
+        assert_eq!(client.get_realized_pnl(&user), pnl_after_sell);
+
+        // Claiming the rest realizes the payout against the remaining basis
+        client.resolve(&oracle, &0);
+        let payout = client.claim(&user);
+        let expected = pnl_after_sell + payout - (buy_cost - basis_sold);
+        assert_eq!(client.get_realized_pnl(&user), expected);
+        assert!(expected > 0, "winning position should realize a profit");
+    }
+
+    #[test]
+    fn test_cost_basis_after_partial_sell() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        let buy_cost = client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_cost_basis(&user, &0), buy_cost);
+        assert_eq!(client.get_cost_basis(&user, &1), 0);
+
+        // Selling 4 of 10 tokens removes basis * 4 / 10
+        client.sell(&user, &0, &(4 * SCALE_FACTOR), &0);
+        let residual = buy_cost - buy_cost * 4 / 10;
+        assert_eq!(client.get_cost_basis(&user, &0), residual);
+
+        client.sell(&user, &0, &(6 * SCALE_FACTOR), &0);
+        assert_eq!(client.get_cost_basis(&user, &0), 0);
+    }
+
+    // --- Legacy storage tests ---
+
+    #[test]
+    fn test_views_tolerate_missing_resolved_key() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        env.as_contract(&contract_id, || {
+            env.storage().instance().remove(&DataKey::Resolved);
+        });
+
+        let (_, _, _, resolved) = client.get_state();
+        assert!(!resolved);
+        assert_eq!(client.get_status(), String::from_str(&env, "open"));
+
+        // State-changing paths stay strict
+        let oracle = client.get_oracle();
+        assert_eq!(
+            client.try_resolve(&oracle, &0),
+            Err(Ok(MarketError::StorageCorrupted))
+        );
+    }
+
+    // --- Pool share tests ---
+
+    #[test]
+    fn test_pool_share_matches_withdrawable() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let winner = Address::generate(&env);
+        let loser = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&winner, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&loser, &(100 * SCALE_FACTOR));
+
+        client.buy(&winner, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&loser, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.resolve(&oracle, &0);
+
+        // Winner hasn't claimed yet, so their tokens stay reserved
+        let (_, _, pool, _) = client.get_state();
+        let reserved = 10 * SCALE_FACTOR;
+
+        let oracle_share = client.get_pool_share(&oracle);
+        let trader_share = client.get_pool_share(&winner);
+        assert_eq!(trader_share, 0);
+        assert_eq!(oracle_share + trader_share, pool - reserved);
+
+        assert_eq!(client.withdraw_remaining(&oracle), oracle_share);
+        assert_eq!(client.get_pool_share(&oracle), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #4)")] // NotResolved = 4
+    fn test_pool_share_before_resolve() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.get_pool_share(&oracle); // Should panic
+    }
+
+    // --- Pricing internals tests ---
+
+    #[test]
+    fn test_cost_at_origin_is_b_ln2() {
+        let b = 100 * SCALE_FACTOR;
+        let c = lmsr::debug::cost(0, 0, b).unwrap();
+        let expected = b * LN2_SCALED / SCALE_FACTOR;
+        assert!((c - expected).abs() <= 10, "C(0,0) = {}", c);
+    }
+
+    #[test]
+    fn test_cost_intermediates() {
+        // C(0,0) = b * ln(1 + e^0), so check each step on the way
+        assert_eq!(lmsr::debug::exp_scaled(0).unwrap(), SCALE_FACTOR);
+        assert_eq!(
+            lmsr::debug::ln_scaled(2 * SCALE_FACTOR).unwrap(),
+            LN2_SCALED
+        );
+    }
+
+    // --- Event tests ---
+
+    /// The most recent event, wrapped in a Vec so it compares by value.
+    fn last_event(env: &Env) -> Vec<(Address, Vec<Val>, Val)> {
+        let all = env.events().all();
+        all.slice(all.len() - 1..)
+    }
+
+    #[test]
+    fn test_buy_and_sell_events() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+
+        let amount = 10 * SCALE_FACTOR;
+        let cost = client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        assert_eq!(
+            last_event(&env),
+            vec![
+                &env,
+                (
+                    contract_id.clone(),
+                    (symbol_short!("buy"), user.clone(), 0u32).into_val(&env),
+                    (amount, cost, token_address.clone()).into_val(&env),
+                ),
+            ]
+        );
+
+        let ret = client.sell(&user, &0, &amount, &0);
+        assert_eq!(
+            last_event(&env),
+            vec![
+                &env,
+                (
+                    contract_id.clone(),
+                    (symbol_short!("sell"), user.clone(), 0u32).into_val(&env),
+                    (amount, ret, token_address.clone()).into_val(&env),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_and_claim_events() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        client.resolve(&oracle, &1);
+        assert_eq!(
+            last_event(&env),
+            vec![
+                &env,
+                (
+                    contract_id.clone(),
+                    (symbol_short!("resolve"), oracle.clone()).into_val(&env),
+                    1u32.into_val(&env),
+                ),
+            ]
+        );
+
+        let payout = client.claim(&user);
+        assert_eq!(
+            last_event(&env),
+            vec![
+                &env,
+                (
+                    contract_id.clone(),
+                    (symbol_short!("claim"), user.clone()).into_val(&env),
+                    (payout, token_address.clone()).into_val(&env),
+                ),
+            ]
+        );
+    }
+
+    // --- Metadata URI tests ---
+
+    #[test]
+    fn test_metadata_uri_concatenates_base() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let oracle = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+        StellarAssetClient::new(&env, &token_address).mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle,
+                token_address,
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                MarketConfig {
+                    metadata_base_uri: Some(String::from_str(&env, "ipfs://")),
+                    ..test_config()
+                },
+            ),
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(
+            client.get_metadata_uri(),
+            String::from_str(&env, "ipfs://QmTest")
+        );
+        assert_eq!(client.get_metadata_hash(), String::from_str(&env, "QmTest"));
+    }
+
+    #[test]
+    fn test_metadata_uri_without_base_is_hash() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(client.get_metadata_uri(), client.get_metadata_hash());
+    }
+
+    // --- Cancel and refund tests ---
+
+    #[test]
+    fn test_refund_both_sides_is_fair() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+
+        let amount = 10 * SCALE_FACTOR;
+        let yes_cost = client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        let no_cost = client.buy(&user, &1, &amount, &(50 * SCALE_FACTOR));
+
+        client.cancel(&oracle);
+
+        // A YES+NO pair always pays out one unit, so the pair refunds at face
+        // value, which is what the pair cost at equal quantities
+        let balance_before = token_client.balance(&user);
+        let refunded = client.refund(&user);
+        assert_eq!(refunded, amount);
+        assert!((refunded - (yes_cost + no_cost)).abs() <= 10);
+        assert_eq!(token_client.balance(&user), balance_before + refunded);
+
+        assert_eq!(client.get_balance(&user, &0), 0);
+        assert_eq!(client.get_balance(&user, &1), 0);
+    }
+
+    #[test]
+    fn test_refund_uses_price_at_cancel() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+
+        let amount = 30 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        let price_yes = client.get_price(&0);
+
+        client.cancel(&oracle);
+
+        assert_eq!(client.refund(&user), amount * price_yes / SCALE_FACTOR);
+    }
+
+    #[test]
+    fn test_oracle_withdraw_after_cancel_reserves_refunds() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        client.cancel(&oracle);
+
+        // Oracle goes first; the user's refund must still be fully covered
+        client.withdraw_remaining(&oracle);
+        let refunded = client.refund(&user);
+        assert!(refunded > 0);
+
+        let (_, _, pool, _) = client.get_state();
+        assert!(pool >= 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #20)")] // Cancelled = 20
+    fn test_buy_after_cancel_fails() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.cancel(&oracle);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR)); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #20)")] // Cancelled = 20
+    fn test_resolve_after_cancel_fails() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.cancel(&oracle);
+        client.resolve(&oracle, &0); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #20)")] // Cancelled = 20
+    fn test_claim_after_cancel_fails() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        client.cancel(&oracle);
+        client.claim(&user); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #21)")] // NotCancelled = 21
+    fn test_refund_before_cancel_fails() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        client.refund(&user); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #13)")] // NothingToClaim = 13
+    fn test_double_refund_fails() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        client.cancel(&oracle);
+        client.refund(&user);
+        client.refund(&user); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_cancel_by_non_oracle_fails() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let attacker = Address::generate(&env);
+        client.cancel(&attacker); // Should panic
+    }
+
+    // --- Protocol fee tests ---
+
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidConfig")]
+    fn test_protocol_fee_without_recipient_fails() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let oracle = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+        StellarAssetClient::new(&env, &token_address).mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        env.register(
+            LmsrMarket,
+            (
+                oracle,
+                token_address,
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                MarketConfig {
+                    protocol_fee_bps: 100,
+                    ..test_config()
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn test_fee_revenue_itemizes_trade_and_claim_fees() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let oracle = Address::generate(&env);
+        let fee_recipient = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address.clone(),
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                MarketConfig {
+                    protocol_fee_bps: 100,
+                    protocol_fee_recipient: Some(fee_recipient.clone()),
+                    ..test_config()
+                },
+            ),
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+        assert_eq!(client.get_fee_revenue(), (0, 0));
+
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        let trade_fees = token_client.balance(&fee_recipient);
+        assert!(trade_fees > 0);
+        assert_eq!(client.get_fee_revenue(), (trade_fees, 0));
+
+        // 2% claim fee on 10 winning tokens
+        client.resolve(&oracle, &0);
+        let payout = client.claim(&user);
+        assert_eq!(payout, 98 * SCALE_FACTOR / 10);
+        assert_eq!(
+            client.get_fee_revenue(),
+            (trade_fees, 10 * SCALE_FACTOR - payout)
+        );
+    }
+
+    // --- Initialization ordering tests ---
+
+    #[test]
+    fn test_failed_funding_transfer_leaves_no_state() {
+        extern crate std;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let market = Address::generate(&env);
+        let oracle = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+
+        // Oracle has no funds, so the funding transfer fails
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            register_market_at(&env, &market, &oracle, &token_address, 70 * SCALE_FACTOR);
+        }));
+        assert!(result.is_err());
+
+        // Nothing from the failed attempt persisted: the same address
+        // initializes cleanly once the oracle is funded
+        StellarAssetClient::new(&env, &token_address).mint(&oracle, &(100 * SCALE_FACTOR));
+        register_market_at(&env, &market, &oracle, &token_address, 70 * SCALE_FACTOR);
+
+        let client = LmsrMarketClient::new(&env, &market);
+        assert_eq!(client.get_state(), (0, 0, 70 * SCALE_FACTOR, false));
+        assert_eq!(
+            token::Client::new(&env, &token_address).balance(&market),
+            70 * SCALE_FACTOR
+        );
+    }
+
+    // --- Claim fee tests ---
+
+    #[test]
+    fn test_zero_claim_fee_pays_in_full() {
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_fee_bps: 0,
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(client.get_claim_fee_bps(), 0);
+        assert_eq!(client.claim(&user), 10 * SCALE_FACTOR);
+    }
+
+    #[test]
+    fn test_ten_percent_claim_fee() {
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_fee_bps: 1_000,
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(client.get_claim_fee_bps(), 1_000);
+        assert_eq!(client.claim(&user), 9 * SCALE_FACTOR);
+    }
+
+    #[test]
+    fn test_default_claim_fee() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(client.get_claim_fee_bps(), CLAIM_FEE_BPS);
+    }
+
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidFee")]
+    fn test_claim_fee_above_100_percent_fails() {
+        setup_resolved_with_config(MarketConfig {
+            claim_fee_bps: BPS_DENOMINATOR + 1,
+            ..test_config()
+        });
+    }
+
+    // --- Oracle transfer tests ---
+
+    #[test]
+    fn test_oracle_transfer() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let new_oracle = Address::generate(&env);
+        client.propose_oracle(&oracle, &new_oracle);
+
+        // Proposal alone changes nothing
+        assert_eq!(client.get_oracle(), oracle);
+
+        client.accept_oracle(&new_oracle);
+        assert_eq!(client.get_oracle(), new_oracle);
+
+        client.resolve(&new_oracle, &0);
+        assert_eq!(client.get_winning_outcome(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_old_oracle_cannot_resolve_after_transfer() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let new_oracle = Address::generate(&env);
+        client.propose_oracle(&oracle, &new_oracle);
+        client.accept_oracle(&new_oracle);
+
+        client.resolve(&oracle, &0); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_accept_oracle_by_wrong_address() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.propose_oracle(&oracle, &Address::generate(&env));
+
+        let attacker = Address::generate(&env);
+        client.accept_oracle(&attacker); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #23)")] // NoPendingOracle = 23
+    fn test_accept_oracle_without_proposal() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.accept_oracle(&Address::generate(&env)); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_propose_oracle_by_non_oracle() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let attacker = Address::generate(&env);
+        client.propose_oracle(&attacker, &attacker); // Should panic
+    }
+
+    // --- Winner count tests ---
+
+    #[test]
+    fn test_winner_count() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+
+        let winners = [
+            Address::generate(&env),
+            Address::generate(&env),
+            Address::generate(&env),
+        ];
+        let losers = [Address::generate(&env), Address::generate(&env)];
+        for user in winners.iter().chain(losers.iter()) {
+            token_admin_client.mint(user, &(100 * SCALE_FACTOR));
+        }
+
+        for winner in &winners {
+            client.buy(winner, &0, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        }
+        // Buying twice doesn't count the same holder twice
+        client.buy(&winners[0], &0, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        for loser in &losers {
+            client.buy(loser, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        }
+        // A full exit stops counting as a holder
+        client.sell(&winners[2], &0, &(5 * SCALE_FACTOR), &0);
+
+        client.resolve(&oracle, &0);
+        assert_eq!(client.get_winner_count(), 2);
+
+        client.claim(&winners[0]);
+        assert_eq!(client.get_winner_count(), 1);
+    }
+
+    // --- Status tests ---
+
+    #[test]
+    fn test_status_open_then_resolved() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(client.get_status(), String::from_str(&env, "open"));
+
+        client.resolve(&oracle, &0);
+        assert_eq!(client.get_status(), String::from_str(&env, "resolved"));
+    }
+
+    #[test]
+    fn test_status_cancelled() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.cancel(&oracle);
+        assert_eq!(client.get_status(), String::from_str(&env, "cancelled"));
+    }
+
+    // --- Partial claim tests ---
+
+    #[test]
+    fn test_claim_partial_sums_to_full_claim() {
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_fee_bps: 200,
+                ..test_config()
+            });
+        let full = LmsrMarketClient::new(&env, &contract_id).claim(&user);
+
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_fee_bps: 200,
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let first_amount = 3 * SCALE_FACTOR + 1;
+        let first = client.claim_partial(&user, &first_amount);
+        assert_eq!(
+            client.get_balance(&user, &0),
+            10 * SCALE_FACTOR - first_amount
+        );
+        assert_eq!(client.get_winner_count(), 1);
+
+        let second = client.claim_partial(&user, &(10 * SCALE_FACTOR - first_amount));
+        assert_eq!(client.get_balance(&user, &0), 0);
+        assert_eq!(client.get_winner_count(), 0);
+
+        // Each claim truncates its own fee, so splitting can only pay out a little more
+        let split = first + second;
+        assert!(split >= full && split - full <= 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
+    fn test_claim_partial_exceeding_balance_fails() {
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_fee_bps: 200,
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.claim_partial(&user, &(10 * SCALE_FACTOR + 1)); // Should panic
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
+    fn test_claim_partial_zero_amount_fails() {
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_fee_bps: 200,
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.claim_partial(&user, &0); // Should panic
+    }
+
+    // --- Overflow harness ---
+
+    #[test]
+    fn test_buy_lifecycle_overflow_is_typed() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let oracle = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+
+        // Largest allowed funding, with a b large enough that b * (q/b) in the
+        // cost function leaves i128 range before q/b reaches the exp limit
+        let b = 10i128.pow(31);
+        let funding = i128::MAX / 2;
+        token_admin_client.mint(&oracle, &funding);
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address.clone(),
+                b,
+                String::from_str(&env, "QmTest"),
+                funding,
+                test_config(),
+            ),
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(i128::MAX - funding));
+
+        // Keep buying until the cost can no longer be represented
+        let step = b / 4;
+        let mut bought = 0;
+        let result = loop {
+            match client.try_buy(&user, &0, &step, &i128::MAX) {
+                Ok(_) => bought += step,
+                Err(err) => break err,
+            }
+        };
+
+        // A typed contract error, not a host trap from an unchecked operation
+        assert_eq!(result, Ok(MarketError::Overflow));
+        assert!(bought >= b);
+        assert_eq!(
+            client.try_get_quote(&0, &step),
+            Err(Ok(MarketError::Overflow))
+        );
+
+        // The failed buy left state readable and unchanged
+        let (yes_sold, _, _, _) = client.get_state();
+        assert_eq!(yes_sold, bought);
+        assert_eq!(client.get_balance(&user, &0), bought);
+        assert!(client.get_price(&0) > SCALE_FACTOR / 2);
+
+        // The rest of the lifecycle still settles at this scale
+        client.resolve(&oracle, &0);
+        assert!(client.claim(&user) > 0);
+    }
+
+    // --- Cost function view tests ---
+
+    #[test]
+    fn test_get_cost_increases_after_buy() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // C(0, 0) = b * ln(2), the initial funding requirement
+        let initial = client.get_cost();
+        assert!((initial - 100 * LN2_SCALED).abs() <= 1);
+
+        let mut last = initial;
+        for outcome in [0, 1, 0] {
+            let paid = client.buy(&user, &outcome, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+            let cost = client.get_cost();
+            assert!(cost > last);
+            assert_eq!(cost - last, paid);
+            last = cost;
+        }
+
+        let (yes_sold, no_sold, _, _) = client.get_state();
+        assert_eq!(client.get_cost_at(&yes_sold, &no_sold), last);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
+    fn test_get_cost_at_negative_quantity_fails() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.get_cost_at(&-1, &0); // Should panic
+    }
+
+    #[test]
+    fn test_normalized_prices_sum_to_one() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(500 * SCALE_FACTOR));
+
+        let trades = [(0u32, 7), (1, 30), (0, 3), (1, 1), (0, 45)];
+        for (outcome, amount) in trades {
+            client.buy(
+                &user,
+                &outcome,
+                &(amount * SCALE_FACTOR),
+                &(500 * SCALE_FACTOR),
+            );
+
+            let (yes, no) = client.get_normalized_prices();
+            assert_eq!(yes + no, SCALE_FACTOR);
+
+            // The lower price is unchanged, the higher absorbs the remainder
+            let (raw_yes, raw_no) = (client.get_price(&0), client.get_price(&1));
+            assert_eq!(yes.min(no), raw_yes.min(raw_no));
+            assert!((yes - raw_yes).abs() <= 2 && (no - raw_no).abs() <= 2);
+        }
+    }
+
+    #[test]
+    fn test_get_full_state_matches_separate_calls() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(30 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        let (yes_sold, no_sold, pool, resolved, price_yes, price_no) = client.get_full_state();
+        assert_eq!((yes_sold, no_sold, pool, resolved), client.get_state());
+        assert_eq!(price_yes, client.get_price(&0));
+        assert_eq!(price_no, client.get_price(&1));
+    }
+
+    #[test]
+    fn test_tokens_to_reach_price_lands_on_target() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(500 * SCALE_FACTOR));
+
+        // Push YES to 0.7, then NO back up to 0.6
+        for (outcome, target) in [(0u32, 7_000_000i128), (1, 6_000_000)] {
+            let amount = client.tokens_to_reach_price(&outcome, &target);
+            assert!(amount > 0);
+            client.buy(&user, &outcome, &amount, &(500 * SCALE_FACTOR));
+            let price = client.get_price(&outcome);
+            assert!(
+                (price - target).abs() <= 100,
+                "price {} for {}",
+                price,
+                target
+            );
+        }
+
+        // Already above the target: nothing to buy
+        assert_eq!(client.tokens_to_reach_price(&1, &5_000_000), 0);
+        assert_eq!(
+            client.try_tokens_to_reach_price(&0, &0),
+            Err(Ok(MarketError::InvalidAmount))
+        );
+        assert_eq!(
+            client.try_tokens_to_reach_price(&0, &SCALE_FACTOR),
+            Err(Ok(MarketError::InvalidAmount))
+        );
+    }
+
+    #[test]
+    fn test_get_summary_matches_individual_getters() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(30 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        let summary = client.get_summary();
+        assert_eq!(summary.yes_price, client.get_price(&0));
+        assert_eq!(summary.no_price, client.get_price(&1));
+        assert_eq!(
+            (
+                summary.yes_sold,
+                summary.no_sold,
+                summary.pool,
+                summary.resolved
+            ),
+            client.get_state()
+        );
+        assert_eq!(summary.winning_outcome, None);
+        assert_eq!(summary.oracle, client.get_oracle());
+        assert_eq!(summary.liquidity_param, client.get_liquidity_param());
+
+        client.resolve(&oracle, &1);
+        let summary = client.get_summary();
+        assert!(summary.resolved);
+        assert_eq!(summary.winning_outcome, Some(client.get_winning_outcome()));
+    }
+
+    #[test]
+    fn test_get_price_at_matches_current_price() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(30 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        let (yes_sold, no_sold, _, _) = client.get_state();
+        for outcome in [0, 1] {
+            assert_eq!(
+                client.get_price_at(&yes_sold, &no_sold, &outcome),
+                client.get_price(&outcome)
+            );
+        }
+
+        // Probing further along the curve doesn't touch state
+        let probed = client.get_price_at(&(yes_sold + 50 * SCALE_FACTOR), &no_sold, &0);
+        assert!(probed > client.get_price(&0));
+        assert_eq!(client.get_state().0, yes_sold);
+
+        assert_eq!(
+            client.try_get_price_at(&0, &-1, &0),
+            Err(Ok(MarketError::InvalidAmount))
+        );
+        assert_eq!(
+            client.try_get_price_at(&0, &0, &2),
+            Err(Ok(MarketError::InvalidOutcome))
+        );
+    }
+
+    // --- Claim deadline tests ---
+
+    #[test]
+    fn test_claim_deadline_unset_by_default() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.resolve(&oracle, &0);
+        assert_eq!(client.get_claim_deadline(), None);
+    }
+
+    #[test]
+    fn test_claim_before_deadline_succeeds() {
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_deadline_ledgers: Some(100),
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        let q_yes: i128 = env
-            .storage()
-            .instance()
-            .get(&DataKey::YesSold)
-            .ok_or(MarketError::StorageCorrupted)?;
-        let q_no: i128 = env
-            .storage()
-            .instance()
-            .get(&DataKey::NoSold)
-            .ok_or(MarketError::StorageCorrupted)?;
-        let pool: i128 = env
-            .storage()
-            .instance()
-            .get(&DataKey::CollateralPool)
-            .ok_or(MarketError::StorageCorrupted)?;
-        let resolved: bool = env
-            .storage()
-            .instance()
-            .get(&DataKey::Resolved)
-            .ok_or(MarketError::StorageCorrupted)?;
+        let deadline = env.ledger().sequence() + 100;
+        assert_eq!(client.get_claim_deadline(), Some(deadline));
 
-        Ok((q_yes, q_no, pool, resolved))
+        // The deadline ledger itself is still inside the window
+        env.ledger().set_sequence_number(deadline);
+        assert!(client.claim(&user) > 0);
     }
 
-    /// Get the oracle address.
-    pub fn get_oracle(env: Env) -> Result<Address, MarketError> {
-        Self::require_initialized(&env)?;
-        env.storage()
-            .instance()
-            .get(&DataKey::Oracle)
-            .ok_or(MarketError::StorageCorrupted)
-    }
+    #[test]
+    #[should_panic(expected = "Error(Contract, #24)")] // ClaimExpired = 24
+    fn test_claim_after_deadline_fails() {
+        let (env, contract_id, _oracle, _token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_deadline_ledgers: Some(100),
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-    /// Get the liquidity parameter.
-    pub fn get_liquidity_param(env: Env) -> Result<i128, MarketError> {
-        Self::require_initialized(&env)?;
-        env.storage()
-            .instance()
-            .get(&DataKey::LiquidityParam)
-            .ok_or(MarketError::StorageCorrupted)
+        env.ledger()
+            .set_sequence_number(client.get_claim_deadline().unwrap() + 1);
+        client.claim(&user); // Should panic
     }
 
-    /// Get the winning outcome (only valid after resolution).
-    pub fn get_winning_outcome(env: Env) -> Result<u32, MarketError> {
-        Self::require_initialized(&env)?;
-        Self::require_resolved(&env)?;
-        env.storage()
-            .instance()
-            .get(&DataKey::WinningOutcome)
-            .ok_or(MarketError::StorageCorrupted)
+    #[test]
+    fn test_oracle_sweeps_unclaimed_after_deadline() {
+        let (env, contract_id, oracle, token_address, user) =
+            setup_resolved_with_config(MarketConfig {
+                claim_deadline_ledgers: Some(100),
+                ..test_config()
+            });
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        // Before the deadline the winnings stay reserved
+        let early = client.withdraw_remaining(&oracle);
+        let (_, _, reserved, _) = client.get_state();
+        assert!(reserved > 0);
+
+        env.ledger()
+            .set_sequence_number(client.get_claim_deadline().unwrap() + 1);
+        let oracle_before = token_client.balance(&oracle);
+        let swept = client.withdraw_remaining(&oracle);
+
+        assert_eq!(swept, reserved);
+        assert_eq!(token_client.balance(&oracle), oracle_before + swept);
+        assert!(early > 0);
+        assert_eq!(client.try_claim(&user), Err(Ok(MarketError::ClaimExpired)));
     }
 
-    /// Get the metadata hash (IPFS CID for market metadata JSON).
-    pub fn get_metadata_hash(env: Env) -> Result<String, MarketError> {
-        Self::require_initialized(&env)?;
-        env.storage()
-            .instance()
-            .get(&DataKey::MetadataHash)
-            .ok_or(MarketError::StorageCorrupted)
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidConfig")]
+    fn test_zero_claim_deadline_rejected() {
+        setup_resolved_with_config(MarketConfig {
+            claim_deadline_ledgers: Some(0),
+            ..test_config()
+        });
     }
 
-    /// Get the collateral token address.
-    pub fn get_collateral_token(env: Env) -> Result<Address, MarketError> {
-        Self::require_initialized(&env)?;
-        env.storage()
-            .instance()
-            .get(&DataKey::CollateralToken)
-            .ok_or(MarketError::StorageCorrupted)
-    }
+    // --- Budget buy tests ---
 
-    // --- Internal helpers ---
+    #[test]
+    fn test_buy_with_budget_stays_within_budget() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
 
-    fn require_initialized(env: &Env) -> Result<(), MarketError> {
-        if !env.storage().instance().has(&DataKey::Oracle) {
-            return Err(MarketError::NotInitialized);
-        }
-        Ok(())
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        let budget = 50 * SCALE_FACTOR;
+        let bought = client.buy_with_budget(&user, &0, &budget, &0);
+
+        let spent = 100 * SCALE_FACTOR - token_client.balance(&user);
+        assert!(spent <= budget);
+        assert_eq!(client.get_balance(&user, &0), bought);
+
+        // One more token would have gone over budget
+        let cost_plus_one = client.get_cost_at(&(bought + 1), &0) - client.get_cost_at(&0, &0);
+        assert!(cost_plus_one > budget);
+        assert!(bought > budget);
     }
 
-    fn require_not_resolved(env: &Env) -> Result<(), MarketError> {
-        let resolved: bool = env
-            .storage()
-            .instance()
-            .get(&DataKey::Resolved)
-            .ok_or(MarketError::StorageCorrupted)?;
-        if resolved {
-            return Err(MarketError::AlreadyResolved);
-        }
-        Ok(())
+    #[test]
+    fn test_avg_price_for_budget_exceeds_spot() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let spot = client.get_price(&0);
+        let avg = client.get_avg_price_for_budget(&0, &(50 * SCALE_FACTOR));
+        assert!(avg > spot, "avg {} vs spot {}", avg, spot);
+        assert!(avg < SCALE_FACTOR);
+
+        // A smaller budget moves the price less
+        let small_avg = client.get_avg_price_for_budget(&0, &SCALE_FACTOR);
+        assert!(small_avg < avg);
     }
 
-    fn require_resolved(env: &Env) -> Result<(), MarketError> {
-        let resolved: bool = env
-            .storage()
-            .instance()
-            .get(&DataKey::Resolved)
-            .ok_or(MarketError::StorageCorrupted)?;
-        if !resolved {
-            return Err(MarketError::NotResolved);
-        }
-        Ok(())
+    #[test]
+    #[should_panic(expected = "Error(Contract, #9)")] // ReturnTooLow = 9
+    fn test_buy_with_budget_min_tokens() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // 10 collateral can't buy 20 tokens near a 50% price
+        client.buy_with_budget(&user, &0, &(10 * SCALE_FACTOR), &(20 * SCALE_FACTOR));
+        // Should panic
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use soroban_sdk::{testutils::Address as _, token::StellarAssetClient, Env};
+    // --- Resolver tests ---
 
-    /// Set up token and oracle, then register initialized market contract.
-    /// Returns (env, contract_id, oracle, token_address)
-    fn setup_test() -> (Env, Address, Address, Address) {
-        setup_test_with_params(100 * SCALE_FACTOR, 70 * SCALE_FACTOR)
+    #[test]
+    fn test_resolver_defaults_to_oracle() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(client.get_resolver(), oracle);
     }
 
-    /// Set up with custom liquidity and funding params.
-    fn setup_test_with_params(
-        liquidity_param: i128,
-        initial_funding: i128,
-    ) -> (Env, Address, Address, Address) {
+    #[test]
+    fn test_delegated_resolver_resolves() {
         let env = Env::default();
-        env.mock_all_auths();
+        let resolver = Address::generate(&env);
+        let (contract_id, funder, token_address) = register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                resolver: Some(resolver.clone()),
+                ..test_config()
+            },
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
 
-        let oracle = Address::generate(&env);
+        // Only the funder paid for the market
+        assert_eq!(token_client.balance(&funder), 930 * SCALE_FACTOR);
+        assert_eq!(token_client.balance(&resolver), 0);
+        assert_eq!(client.get_oracle(), funder);
+        assert_eq!(client.get_resolver(), resolver);
 
-        // Create a test token
-        let token_admin = Address::generate(&env);
-        let token_contract = env.register_stellar_asset_contract_v2(token_admin.clone());
-        let token_address = token_contract.address();
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        client.resolve(&resolver, &1);
+        assert_eq!(client.get_winning_outcome(), 1);
 
-        // Mint tokens to oracle for initial funding
-        token_admin_client.mint(&oracle, &(1000 * SCALE_FACTOR));
+        // The funder still recovers the pool
+        assert_eq!(client.withdraw_remaining(&funder), 70 * SCALE_FACTOR);
+    }
 
-        // Register contract with constructor args (this calls __constructor which calls initialize)
-        let contract_id = env.register(
-            LmsrMarket,
-            (
-                oracle.clone(),
-                token_address.clone(),
-                liquidity_param,
-                String::from_str(&env, "QmTest"),
-                initial_funding,
-            ),
+    #[test]
+    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
+    fn test_funder_cannot_resolve_when_delegated() {
+        let env = Env::default();
+        let (contract_id, funder, _token_address) = register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                resolver: Some(Address::generate(&env)),
+                ..test_config()
+            },
         );
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        (env, contract_id, oracle, token_address)
+        client.resolve(&funder, &0); // Should panic
     }
 
+    // --- Dispute window tests ---
+
     #[test]
-    fn test_initialize() {
-        // setup_test() now registers with constructor which initializes
-        let (env, contract_id, oracle, _token_address) = setup_test();
+    fn test_dispute_window_happy_path() {
+        let (env, contract_id, oracle, _token_address, user) = setup_with_position(MarketConfig {
+            dispute_window_ledgers: Some(100),
+            ..test_config()
+        });
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        let b = 100 * SCALE_FACTOR;
+        let start = env.ledger().sequence();
+        client.resolve(&oracle, &0);
+        assert_eq!(client.get_proposed_resolution(), Some((0, start + 100)));
+        assert_eq!(client.get_status(), String::from_str(&env, "closed"));
 
-        // Verify initialization worked
-        assert_eq!(client.get_oracle(), oracle);
-        assert_eq!(client.get_liquidity_param(), b);
+        // Not final yet: claims and trading wait for finalize
+        assert_eq!(client.try_claim(&user), Err(Ok(MarketError::NotResolved)));
+        assert_eq!(
+            client.try_buy(&user, &0, &SCALE_FACTOR, &SCALE_FACTOR),
+            Err(Ok(MarketError::DisputeWindowOpen))
+        );
+        assert_eq!(
+            client.try_finalize(),
+            Err(Ok(MarketError::DisputeWindowOpen))
+        );
+
+        env.ledger().set_sequence_number(start + 100);
+        client.finalize();
+
+        assert_eq!(client.get_proposed_resolution(), None);
+        assert_eq!(client.get_winning_outcome(), 0);
+        assert!(client.claim(&user) > 0);
     }
 
     #[test]
-    fn test_buy() {
-        let (env, contract_id, _oracle, token_address) = setup_test();
+    fn test_dispute_resets_proposal() {
+        let (env, contract_id, oracle, _token_address, user) = setup_with_position(MarketConfig {
+            dispute_window_ledgers: Some(100),
+            ..test_config()
+        });
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Create a user and mint tokens
-        let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.resolve(&oracle, &1);
+        client.dispute(&user);
 
-        // Buy YES tokens
-        let amount = 10 * SCALE_FACTOR;
-        let max_cost = 50 * SCALE_FACTOR;
-        let cost = client.buy(&user, &0, &amount, &max_cost);
+        assert_eq!(client.get_proposed_resolution(), None);
+        assert_eq!(client.get_status(), String::from_str(&env, "open"));
+        assert_eq!(client.try_finalize(), Err(Ok(MarketError::NotResolved)));
 
-        assert!(cost > 0);
-        assert_eq!(client.get_balance(&user, &0), amount);
+        // The oracle proposes again and the new outcome is finalized
+        let start = env.ledger().sequence();
+        client.resolve(&oracle, &0);
+        env.ledger().set_sequence_number(start + 100);
+        client.finalize();
+        assert_eq!(client.get_winning_outcome(), 0);
     }
 
     #[test]
-    fn test_resolve_and_claim() {
-        let (env, contract_id, oracle, token_address) = setup_test();
+    #[should_panic(expected = "Error(Contract, #26)")] // DisputeWindowClosed = 26
+    fn test_dispute_after_window_fails() {
+        let (env, contract_id, oracle, _token_address, user) = setup_with_position(MarketConfig {
+            dispute_window_ledgers: Some(100),
+            ..test_config()
+        });
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // User buys YES tokens
-        let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        let start = env.ledger().sequence();
+        client.resolve(&oracle, &0);
+        env.ledger().set_sequence_number(start + 100);
+        client.dispute(&user); // Should panic
+    }
 
-        let amount = 10 * SCALE_FACTOR;
-        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+    #[test]
+    fn test_disputes_limited_to_holders_once_each() {
+        let (env, contract_id, oracle, _token_address, user) = setup_with_position(MarketConfig {
+            dispute_window_ledgers: Some(100),
+            ..test_config()
+        });
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Resolve market with YES winning
+        // Accounts without a position can't dispute
         client.resolve(&oracle, &0);
+        assert_eq!(
+            client.try_dispute(&Address::generate(&env)),
+            Err(Ok(MarketError::InsufficientBalance))
+        );
 
-        // User claims winnings (minus 2% fee)
-        let payout = client.claim(&user);
-        let expected_payout = amount - (amount * CLAIM_FEE_BPS / BPS_DENOMINATOR);
-        assert_eq!(payout, expected_payout);
+        // Each holder disputes once
+        client.dispute(&user);
+        client.resolve(&oracle, &0);
+        assert_eq!(
+            client.try_dispute(&user),
+            Err(Ok(MarketError::DisputeLimitReached))
+        );
+
+        // The new proposal stands and is finalized after its window
+        env.ledger()
+            .set_sequence_number(env.ledger().sequence() + 100);
+        client.finalize();
+        assert_eq!(client.get_winning_outcome(), 0);
     }
 
     #[test]
-    fn test_price_at_equilibrium() {
-        let (env, contract_id, _oracle, _token_address) = setup_test();
+    fn test_resolver_cannot_exhaust_disputes() {
+        let (env, contract_id, oracle, token_address, _user) = setup_with_position(MarketConfig {
+            dispute_window_ledgers: Some(100),
+            ..test_config()
+        });
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // At equilibrium (no tokens sold), price should be ~0.5
-        let price_yes = client.get_price(&0);
-        let price_no = client.get_price(&1);
+        // The resolver's own accounts hold dust positions
+        let mut puppets = Vec::new(&env);
+        for _ in 0..MAX_DISPUTES {
+            let puppet = Address::generate(&env);
+            give_position(&env, &client, &token_address, &puppet);
+            puppets.push_back(puppet);
+        }
 
-        // Allow for some floating point error
-        assert!(
-            (4_900_000..=5_100_000).contains(&price_yes),
-            "Expected price_yes near 5_000_000, got {}",
-            price_yes
-        );
-        assert!(
-            (4_900_000..=5_100_000).contains(&price_no),
-            "Expected price_no near 5_000_000, got {}",
-            price_no
+        // It proposes the right outcome and disputes it away every time
+        for puppet in puppets.iter() {
+            client.resolve(&oracle, &0);
+            client.dispute(&puppet);
+        }
+
+        // A wrong outcome can't then be slipped in past the dispute window;
+        // the market can only be cancelled and holders refunded
+        assert_eq!(
+            client.try_resolve(&oracle, &1),
+            Err(Ok(MarketError::DisputeLimitReached))
         );
+        assert_eq!(client.get_proposed_resolution(), None);
+        client.cancel(&oracle);
+        assert_eq!(client.get_status(), String::from_str(&env, "cancelled"));
     }
 
-    // --- Authorization tests ---
+    // --- Add liquidity tests ---
+
+    #[test]
+    fn test_add_liquidity_reduces_price_impact() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let user = Address::generate(&env);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        let trade = 10 * SCALE_FACTOR;
+        let (_, _, before_thin, after_thin) = client.get_quote_detailed(&0, &trade);
+
+        let (_, _, pool_before, _) = client.get_state();
+        let oracle_before = token_client.balance(&oracle);
+        let cost_before = client.get_cost();
+
+        let deposit = client.add_liquidity(&oracle, &(100 * SCALE_FACTOR));
+
+        // The deposit is exactly the cost function's increase, paid by the oracle
+        assert!(deposit > 0);
+        assert_eq!(client.get_cost() - cost_before, deposit);
+        assert_eq!(token_client.balance(&oracle), oracle_before - deposit);
+        let (yes_sold, _, pool_after, _) = client.get_state();
+        assert_eq!(pool_after, pool_before + deposit);
+        assert_eq!(yes_sold, 20 * SCALE_FACTOR);
+        assert_eq!(client.get_balance(&user, &0), 20 * SCALE_FACTOR);
+
+        // The same trade now moves the price less
+        let (_, _, before_deep, after_deep) = client.get_quote_detailed(&0, &trade);
+        assert!(after_deep - before_deep < after_thin - before_thin);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #11)")] // InvalidLiquidity = 11
+    fn test_add_liquidity_zero_fails() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        client.add_liquidity(&oracle, &0); // Should panic
+    }
 
     #[test]
     #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
-    fn test_resolve_by_non_oracle_fails() {
+    fn test_add_liquidity_non_oracle_fails() {
         let (env, contract_id, _oracle, _token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Try to resolve with non-oracle address
-        let attacker = Address::generate(&env);
-        client.resolve(&attacker, &0); // Should panic with Unauthorized
+        client.add_liquidity(&Address::generate(&env), &(10 * SCALE_FACTOR)); // Should panic
     }
 
-    // --- Double-claim prevention tests ---
+    // --- Solvency check tests ---
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #13)")] // NothingToClaim = 13
-    fn test_double_claim_fails() {
+    fn test_check_solvency_after_trading() {
         let (env, contract_id, oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
-
-        // User buys YES tokens
-        let user = Address::generate(&env);
         let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
 
-        let amount = 10 * SCALE_FACTOR;
-        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        let (pool, theoretical_min) = client.check_solvency();
+        assert_eq!(pool, 70 * SCALE_FACTOR);
+        assert_eq!(theoretical_min, 0);
 
-        // Resolve market with YES winning
-        client.resolve(&oracle, &0);
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        token_admin_client.mint(&alice, &(200 * SCALE_FACTOR));
+        token_admin_client.mint(&bob, &(200 * SCALE_FACTOR));
 
-        // First claim succeeds
-        client.claim(&user);
+        client.buy(&alice, &0, &(30 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        client.buy(&bob, &1, &(17 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        client.sell(&alice, &0, &(11 * SCALE_FACTOR), &0);
+        client.buy(&bob, &0, &(7 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        client.sell(&bob, &1, &(3 * SCALE_FACTOR), &0);
 
-        // Second claim should fail
-        client.claim(&user); // Should panic with NothingToClaim
+        let (pool, theoretical_min) = client.check_solvency();
+        assert!(theoretical_min > 0);
+        assert!(pool >= theoretical_min);
+
+        // After resolution the pool must cover the unclaimed winnings
+        client.resolve(&oracle, &0);
+        let (pool, theoretical_min) = client.check_solvency();
+        assert_eq!(theoretical_min, 26 * SCALE_FACTOR);
+        assert!(pool >= theoretical_min);
     }
 
-    // --- Slippage protection tests ---
+    #[test]
+    fn test_invariants_hold_after_mixed_activity() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        token_admin_client.mint(&alice, &(200 * SCALE_FACTOR));
+        token_admin_client.mint(&bob, &(200 * SCALE_FACTOR));
+
+        client.buy(&alice, &0, &(30 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        client.buy(&bob, &1, &(17 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        client.sell(&alice, &0, &(11 * SCALE_FACTOR), &0);
+        client.buy(&bob, &0, &(7 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        client.sell(&bob, &1, &(3 * SCALE_FACTOR), &0);
+
+        let invariants = client.get_invariants();
+        assert_eq!(invariants.len(), 4);
+        for (name, ok) in invariants.iter() {
+            assert!(ok, "invariant {:?} failed while trading", name);
+        }
+
+        client.resolve(&oracle, &0);
+        client.claim(&alice);
+        for (name, ok) in client.get_invariants().iter() {
+            assert!(ok, "invariant {:?} failed after resolution", name);
+        }
+    }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #8)")] // SlippageExceeded = 8
-    fn test_buy_slippage_exceeded() {
-        let (env, contract_id, _oracle, token_address) = setup_test();
+    fn test_max_loss_is_b_ln2_before_fees() {
+        let (env, contract_id, oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
+        // b = 100, so the bound is 100 * ln(2) ≈ 69.31
+        let max_loss = client.get_max_loss();
+        assert!(
+            (max_loss - 100 * LN2_SCALED).abs() <= 1,
+            "max_loss = {}",
+            max_loss
+        );
+
+        // Claim fees kept in the pool reduce it
         let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.resolve(&oracle, &0);
+        client.claim(&user);
 
-        // Try to buy with max_cost = 0 (impossible)
-        let amount = 10 * SCALE_FACTOR;
-        client.buy(&user, &0, &amount, &0); // Should panic with SlippageExceeded
+        let (_, claim_fees) = client.get_fee_revenue();
+        assert!(claim_fees > 0);
+        assert_eq!(client.get_max_loss(), max_loss - claim_fees);
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #9)")] // ReturnTooLow = 9
-    fn test_sell_min_return_not_met() {
-        let (env, contract_id, _oracle, token_address) = setup_test();
+    fn test_payout_liability_follows_larger_side() {
+        let (env, contract_id, oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        assert_eq!(client.max_payout_liability(), 0);
+        assert!(client.is_solvent());
 
-        let user = Address::generate(&env);
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
         let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&alice, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&bob, &(100 * SCALE_FACTOR));
+        client.buy(&alice, &0, &(40 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&bob, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
 
-        // First buy some tokens
-        let amount = 10 * SCALE_FACTOR;
-        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        assert_eq!(client.max_payout_liability(), 40 * SCALE_FACTOR);
+        assert!(client.is_solvent());
 
-        // Try to sell with impossibly high min_return
-        client.sell(&user, &0, &amount, &(i128::MAX / 2)); // Should panic with ReturnTooLow
+        // After resolution only the unclaimed winners are owed
+        client.resolve(&oracle, &0);
+        assert_eq!(client.max_payout_liability(), 40 * SCALE_FACTOR);
+        client.claim(&alice);
+        assert_eq!(client.max_payout_liability(), 0);
+        assert!(client.is_solvent());
     }
 
-    // --- Sell function tests ---
-
     #[test]
-    fn test_sell_basic() {
+    fn test_is_solvent_detects_shortfall() {
         let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
         let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
-
-        // Buy tokens
-        let amount = 10 * SCALE_FACTOR;
-        let buy_cost = client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
-        assert_eq!(client.get_balance(&user, &0), amount);
-
-        // Sell half the tokens
-        let sell_amount = 5 * SCALE_FACTOR;
-        let sell_return = client.sell(&user, &0, &sell_amount, &0);
-        assert!(sell_return > 0, "Sell return should be positive");
-        assert_eq!(client.get_balance(&user, &0), amount - sell_amount);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(30 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
 
-        // Sell return should be less than buy cost (round-trip loss expected)
-        assert!(
-            sell_return < buy_cost,
-            "Selling should return less than buying cost due to price movement"
-        );
+        env.as_contract(&contract_id, || {
+            env.storage()
+                .instance()
+                .set(&DataKey::CollateralPool, &(30 * SCALE_FACTOR - 1));
+        });
+        assert!(!client.is_solvent());
     }
 
+    // --- Analytics tests ---
+
     #[test]
-    #[should_panic(expected = "Error(Contract, #7)")] // InsufficientBalance = 7
-    fn test_sell_insufficient_balance() {
+    fn test_get_analytics_after_trades() {
         let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
+        assert_eq!(client.get_analytics(), (0, 0, 0));
+
         let user = Address::generate(&env);
         let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
-
-        // Buy a small amount
-        client.buy(&user, &0, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        token_admin_client.mint(&user, &(200 * SCALE_FACTOR));
 
-        // Try to sell more than owned
-        client.sell(&user, &0, &(10 * SCALE_FACTOR), &0); // Should panic
+        let start = env.ledger().sequence();
+        let cost_yes = client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        env.ledger().set_sequence_number(start + 50);
+        let cost_no = client.buy(&user, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        env.ledger().set_sequence_number(start + 100);
+        let returned = client.sell(&user, &0, &(4 * SCALE_FACTOR), &0);
+
+        let (age, volume, trades) = client.get_analytics();
+        assert_eq!(age, 100);
+        assert_eq!(volume, cost_yes + cost_no + returned);
+        assert_eq!(trades, 3);
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #3)")] // AlreadyResolved = 3
-    fn test_sell_after_resolution_fails() {
-        let (env, contract_id, oracle, token_address) = setup_test();
+    fn test_get_stats_counts_buys_and_sells() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
+        assert_eq!(client.get_stats(), (0, 0, 0));
+
         let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(200 * SCALE_FACTOR));
 
-        // Buy tokens
-        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        let mut volume = client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        volume += client.buy(&user, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_stats(), (volume, 2, 0));
 
-        // Resolve market
-        client.resolve(&oracle, &0);
+        volume += client.sell(&user, &0, &(4 * SCALE_FACTOR), &0);
+        assert_eq!(client.get_stats(), (volume, 2, 1));
 
-        // Try to sell after resolution
-        client.sell(&user, &0, &(5 * SCALE_FACTOR), &0); // Should panic
+        volume += client.sell(&user, &1, &(2 * SCALE_FACTOR), &0);
+        volume += client.buy(&user, &0, &SCALE_FACTOR, &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_stats(), (volume, 3, 2));
+        assert_eq!(client.get_analytics().2, 5);
     }
 
-    // --- Market lifecycle error state tests ---
-
-    // Note: test_buy_on_uninitialized_contract was removed because with the constructor,
-    // contracts are always initialized at deployment time.
-
     #[test]
-    #[should_panic(expected = "Error(Contract, #3)")] // AlreadyResolved = 3
-    fn test_buy_on_resolved_market() {
+    fn test_initial_funding_survives_trades() {
         let (env, contract_id, oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Resolve immediately
-        client.resolve(&oracle, &0);
+        assert_eq!(
+            last_event(&env),
+            vec![
+                &env,
+                (
+                    contract_id.clone(),
+                    (symbol_short!("init"), oracle.clone()).into_val(&env),
+                    (70 * SCALE_FACTOR, token_address.clone()).into_val(&env),
+                ),
+            ]
+        );
 
-        // Try to buy after resolution
         let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(200 * SCALE_FACTOR));
         client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.sell(&user, &0, &(4 * SCALE_FACTOR), &0);
+        client.add_liquidity(&oracle, &(10 * SCALE_FACTOR));
+
+        assert_eq!(client.get_initial_funding(), 70 * SCALE_FACTOR);
+        let (_, _, pool, _) = client.get_state();
+        assert_ne!(pool, 70 * SCALE_FACTOR);
     }
 
+    // --- Balance tests ---
+
     #[test]
-    #[should_panic(expected = "Error(Contract, #4)")] // NotResolved = 4
-    fn test_claim_on_unresolved_market() {
+    fn test_get_balances_matches_single_getter() {
         let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
         let user = Address::generate(&env);
+        assert_eq!(client.get_balances(&user), (0, 0));
+
         let token_admin_client = StellarAssetClient::new(&env, &token_address);
         token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(8 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(3 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
 
-        // Buy tokens but don't resolve
-        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
-
-        // Try to claim without resolution
-        client.claim(&user); // Should panic
+        assert_eq!(
+            client.get_balances(&user),
+            (client.get_balance(&user, &0), client.get_balance(&user, &1))
+        );
+        assert_eq!(
+            client.get_balances(&user),
+            (8 * SCALE_FACTOR, 3 * SCALE_FACTOR)
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #13)")] // NothingToClaim = 13
-    fn test_loser_cannot_claim() {
-        let (env, contract_id, oracle, token_address) = setup_test();
+    fn test_get_user_positions_skips_empty_outcomes() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
         let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        assert_eq!(client.get_user_positions(&user).len(), 0);
 
-        // User buys NO tokens
-        client.buy(&user, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &1, &(3 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(
+            client.get_user_positions(&user),
+            vec![&env, (1u32, 3 * SCALE_FACTOR)]
+        );
 
-        // Resolve market with YES winning (user loses)
-        client.resolve(&oracle, &0);
+        client.buy(&user, &0, &(2 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(
+            client.get_user_positions(&user),
+            vec![&env, (0u32, 2 * SCALE_FACTOR), (1u32, 3 * SCALE_FACTOR)]
+        );
+    }
 
-        // Loser tries to claim
-        client.claim(&user); // Should panic with NothingToClaim
+    // --- Cost cache tests ---
+
+    mod market_wasm {
+        soroban_sdk::contractimport!(
+            file = "../target/wasm32-unknown-unknown/release/lmsr_market.wasm"
+        );
     }
 
-    // --- Withdraw remaining tests ---
+    fn cached_cost(env: &Env, contract_id: &Address) -> i128 {
+        env.as_contract(contract_id, || {
+            env.storage().instance().get(&DataKey::LastCost).unwrap()
+        })
+    }
 
     #[test]
-    fn test_withdraw_remaining_after_claims() {
+    fn test_cached_cost_tracks_state() {
         let (env, contract_id, oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        assert_eq!(cached_cost(&env, &contract_id), client.get_cost());
 
-        let winner = Address::generate(&env);
-        let loser = Address::generate(&env);
+        let user = Address::generate(&env);
         let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&winner, &(100 * SCALE_FACTOR));
-        token_admin_client.mint(&loser, &(100 * SCALE_FACTOR));
-
-        // Winner buys YES, loser buys NO
-        let yes_cost = client.buy(&winner, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
-        let no_cost = client.buy(&loser, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
-
-        // Check pool increased (initial_funding is 70 * SCALE_FACTOR from setup_test)
-        let (_, _, pool_before, _) = client.get_state();
-        assert_eq!(pool_before, 70 * SCALE_FACTOR + yes_cost + no_cost);
+        token_admin_client.mint(&user, &(200 * SCALE_FACTOR));
 
-        // Resolve: YES wins
-        client.resolve(&oracle, &0);
+        client.buy(&user, &0, &(25 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        assert_eq!(cached_cost(&env, &contract_id), client.get_cost());
+        client.buy(&user, &1, &(9 * SCALE_FACTOR), &(100 * SCALE_FACTOR));
+        assert_eq!(cached_cost(&env, &contract_id), client.get_cost());
+        client.sell(&user, &0, &(13 * SCALE_FACTOR), &0);
+        assert_eq!(cached_cost(&env, &contract_id), client.get_cost());
+        client.add_liquidity(&oracle, &(50 * SCALE_FACTOR));
+        assert_eq!(cached_cost(&env, &contract_id), client.get_cost());
+        client.sell_all(&user, &1, &0);
+        assert_eq!(cached_cost(&env, &contract_id), client.get_cost());
+    }
 
-        // Winner claims (minus 2% fee)
-        let payout = client.claim(&winner);
-        let expected_payout =
-            10 * SCALE_FACTOR - (10 * SCALE_FACTOR * CLAIM_FEE_BPS / BPS_DENOMINATOR);
-        assert_eq!(payout, expected_payout); // 1:1 redemption minus 2% fee
+    #[test]
+    fn test_cached_cost_saves_instructions() {
+        let env = Env::default();
+        // The Wasm constructor's funding transfer is a non-root auth
+        env.mock_all_auths_allowing_non_root_auth();
 
-        // Pool should have remaining funds (loser's money + part of initial funding)
-        let (_, _, pool_after_claim, _) = client.get_state();
-        assert!(pool_after_claim > 0, "Pool should have remaining funds");
+        let oracle = Address::generate(&env);
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        token_admin_client.mint(&oracle, &(1000 * SCALE_FACTOR));
 
-        // Oracle withdraws remaining
-        let withdrawn = client.withdraw_remaining(&oracle);
-        assert_eq!(withdrawn, pool_after_claim);
+        // Metering only covers Wasm execution, so deploy the built contract
+        let mut markets = [(); 2].map(|_| {
+            env.register(
+                market_wasm::WASM,
+                (
+                    oracle.clone(),
+                    token_address.clone(),
+                    100 * SCALE_FACTOR,
+                    String::from_str(&env, "QmTest"),
+                    70 * SCALE_FACTOR,
+                    test_config(),
+                ),
+            )
+        });
+        // The second market looks like one deployed before the cache existed
+        env.as_contract(&markets[1], || {
+            env.storage().instance().remove(&DataKey::LastCost);
+        });
 
-        // Pool should be zero now
-        let (_, _, pool_final, _) = client.get_state();
-        assert_eq!(pool_final, 0);
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(200 * SCALE_FACTOR));
+        let [cached, legacy] = markets.each_mut().map(|market| {
+            let client = market_wasm::Client::new(&env, market);
+            client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+            env.cost_estimate().budget().cpu_instruction_cost()
+        });
+
+        // Skipping one cost evaluation saved ~52k of ~7.6M instructions per
+        // buy when measured; most of the total is Wasm instantiation
+        assert!(cached < legacy);
     }
 
+    // --- Upgrade tests ---
+
     #[test]
-    fn test_withdraw_remaining_no_trades() {
+    fn test_upgrade_by_oracle() {
         let (env, contract_id, oracle, _token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let price_before = client.get_price(&0);
 
-        // No trades, just resolve
-        client.resolve(&oracle, &0);
+        let wasm_hash = env.deployer().upload_contract_wasm(market_wasm::WASM);
+        client.upgrade(&oracle, &wasm_hash);
 
-        // Oracle can withdraw entire initial funding (70 * SCALE_FACTOR from setup_test)
-        let withdrawn = client.withdraw_remaining(&oracle);
-        assert_eq!(withdrawn, 70 * SCALE_FACTOR);
+        // Only the oracle's authorization was required
+        let auths = env.auths();
+        assert_eq!(auths.len(), 1);
+        assert_eq!(auths[0].0, oracle);
+
+        // State carries over to the new code
+        let upgraded = market_wasm::Client::new(&env, &contract_id);
+        assert_eq!(upgraded.get_price(&0), price_before);
+        assert_eq!(upgraded.get_oracle(), oracle);
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #4)")] // NotResolved = 4
-    fn test_withdraw_remaining_before_resolve() {
-        let (env, contract_id, oracle, _token_address) = setup_test();
+    fn test_upgrade_rejects_non_oracle() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Try to withdraw before resolution
-        client.withdraw_remaining(&oracle); // Should panic
+        let wasm_hash = env.deployer().upload_contract_wasm(market_wasm::WASM);
+        assert_eq!(
+            client.try_upgrade(&Address::generate(&env), &wasm_hash),
+            Err(Ok(MarketError::Unauthorized))
+        );
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #10)")] // Unauthorized = 10
-    fn test_withdraw_remaining_non_oracle() {
+    fn test_upgrade_rejected_once_settled() {
         let (env, contract_id, oracle, _token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let wasm_hash = env.deployer().upload_contract_wasm(market_wasm::WASM);
 
         client.resolve(&oracle, &0);
+        assert_eq!(
+            client.try_upgrade(&oracle, &wasm_hash),
+            Err(Ok(MarketError::AlreadyResolved))
+        );
 
-        // Non-oracle tries to withdraw
-        let attacker = Address::generate(&env);
-        client.withdraw_remaining(&attacker); // Should panic
-    }
-
-    #[test]
-    #[should_panic(expected = "Error(Contract, #13)")] // NothingToClaim = 13
-    fn test_withdraw_remaining_twice() {
         let (env, contract_id, oracle, _token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let wasm_hash = env.deployer().upload_contract_wasm(market_wasm::WASM);
+
+        client.cancel(&oracle);
+        assert_eq!(
+            client.try_upgrade(&oracle, &wasm_hash),
+            Err(Ok(MarketError::Cancelled))
+        );
+
+        // A proposed resolution already reserves the pool
+        let (env, contract_id, oracle, _token_address, _user) = setup_with_position(MarketConfig {
+            dispute_window_ledgers: Some(100),
+            ..test_config()
+        });
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let wasm_hash = env.deployer().upload_contract_wasm(market_wasm::WASM);
 
         client.resolve(&oracle, &0);
+        assert_eq!(
+            client.try_upgrade(&oracle, &wasm_hash),
+            Err(Ok(MarketError::DisputeWindowOpen))
+        );
+    }
 
-        // First withdrawal succeeds
-        client.withdraw_remaining(&oracle);
+    // --- Cost to certainty tests ---
 
-        // Second withdrawal should fail
-        client.withdraw_remaining(&oracle); // Should panic
+    #[test]
+    fn test_cost_to_certainty_scales_with_b() {
+        let (env, contract_id, _oracle, _token_address) =
+            setup_test_with_params(100 * SCALE_FACTOR, 70 * SCALE_FACTOR);
+        let thin = LmsrMarketClient::new(&env, &contract_id).get_cost_to_certainty(&0);
+
+        let (env, contract_id, _oracle, _token_address) =
+            setup_test_with_params(200 * SCALE_FACTOR, 140 * SCALE_FACTOR);
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let deep = client.get_cost_to_certainty(&0);
+
+        // From 50/50 both outcomes cost the same, and doubling b doubles it
+        assert!(thin > 0);
+        assert_eq!(client.get_cost_to_certainty(&1), deep);
+        assert!((deep - 2 * thin).abs() <= 2, "thin={} deep={}", thin, deep);
     }
 
     #[test]
-    fn test_withdraw_remaining_reserves_for_unclaimed() {
-        let (env, contract_id, oracle, token_address) = setup_test();
+    fn test_cost_to_certainty_matches_buy() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        let winner = Address::generate(&env);
-        let loser = Address::generate(&env);
+        let user = Address::generate(&env);
         let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&winner, &(100 * SCALE_FACTOR));
-        token_admin_client.mint(&loser, &(100 * SCALE_FACTOR));
+        token_admin_client.mint(&user, &(1000 * SCALE_FACTOR));
+        client.buy(&user, &1, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        // Paying the quoted amount drives YES to 0.99, after which it's free
+        let cost = client.get_cost_to_certainty(&0);
+        let amount = client.buy_with_budget(&user, &0, &cost, &1);
+        assert!(amount > 0);
+        assert!((client.get_price(&0) - CERTAINTY_PRICE).abs() <= 10);
+        assert_eq!(client.get_cost_to_certainty(&0), 0);
+    }
 
-        // Winner buys YES, loser buys NO
-        let yes_cost = client.buy(&winner, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
-        let no_cost = client.buy(&loser, &1, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+    // --- Outcome label tests ---
+
+    #[test]
+    fn test_outcome_labels() {
+        let env = Env::default();
+        let labels = vec![
+            &env,
+            String::from_str(&env, "Team A wins"),
+            String::from_str(&env, "Team B wins"),
+        ];
+        let (contract_id, _oracle, _token_address) = register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                outcome_labels: Some(labels),
+                ..test_config()
+            },
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Initial funding is 70 * SCALE_FACTOR
-        let initial_funding = 70 * SCALE_FACTOR;
-        let pool_before = initial_funding + yes_cost + no_cost;
+        assert_eq!(
+            client.get_outcome_label(&0),
+            String::from_str(&env, "Team A wins")
+        );
+        assert_eq!(
+            client.get_outcome_label(&1),
+            String::from_str(&env, "Team B wins")
+        );
+        assert_eq!(
+            client.try_get_outcome_label(&2),
+            Err(Ok(MarketError::InvalidOutcome))
+        );
+    }
 
-        // Resolve: YES wins
-        client.resolve(&oracle, &0);
+    #[test]
+    fn test_outcome_labels_default_to_yes_no() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Winner does NOT claim yet
-        // Oracle tries to withdraw - should only get losers' funds + fees, not winners' reserved funds
+        assert_eq!(client.get_outcome_label(&0), String::from_str(&env, "YES"));
+        assert_eq!(client.get_outcome_label(&1), String::from_str(&env, "NO"));
+    }
 
-        // Reserved for winner: 10 tokens * 98% = 9.8 tokens (unclaimed payout)
-        let winner_tokens = 10 * SCALE_FACTOR;
-        let reserved = winner_tokens * (BPS_DENOMINATOR - CLAIM_FEE_BPS) / BPS_DENOMINATOR;
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidConfig")]
+    fn test_outcome_label_count_mismatch_fails() {
+        let env = Env::default();
+        register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                outcome_labels: Some(vec![&env, String::from_str(&env, "Only one")]),
+                ..test_config()
+            },
+        );
+    }
 
-        // Withdrawable = pool - reserved
-        let expected_withdrawable = pool_before - reserved;
+    // --- Batch buy tests ---
 
-        let withdrawn = client.withdraw_remaining(&oracle);
-        assert_eq!(withdrawn, expected_withdrawable);
+    #[test]
+    fn test_buy_batch_matches_sequential_buys() {
+        let (env, batch_id, _oracle, batch_token) = setup_test();
+        let batch = LmsrMarketClient::new(&env, &batch_id);
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &batch_token).mint(&user, &(100 * SCALE_FACTOR));
 
-        // Pool should now have exactly reserved amount
-        let (_, _, pool_after, _) = client.get_state();
-        assert_eq!(pool_after, reserved);
+        let legs = vec![&env, (0u32, 10 * SCALE_FACTOR), (1u32, 5 * SCALE_FACTOR)];
+        let batch_cost = batch.buy_batch(&user, &legs, &(50 * SCALE_FACTOR));
 
-        // Now winner claims - should still work
-        let payout = client.claim(&winner);
-        assert_eq!(payout, reserved); // Gets exactly what was reserved
+        let (env2, seq_id, _oracle2, seq_token) = setup_test();
+        let sequential = LmsrMarketClient::new(&env2, &seq_id);
+        let user2 = Address::generate(&env2);
+        StellarAssetClient::new(&env2, &seq_token).mint(&user2, &(100 * SCALE_FACTOR));
+        let seq_cost = sequential.buy(&user2, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR))
+            + sequential.buy(&user2, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
 
-        // Pool is now zero
-        let (_, _, pool_final, _) = client.get_state();
-        assert_eq!(pool_final, 0);
+        assert_eq!(batch_cost, seq_cost);
+        assert_eq!(batch.get_state(), sequential.get_state());
+        assert_eq!(
+            batch.get_balances(&user),
+            (10 * SCALE_FACTOR, 5 * SCALE_FACTOR)
+        );
+        assert_eq!(
+            token::Client::new(&env, &batch_token).balance(&user),
+            100 * SCALE_FACTOR - batch_cost
+        );
     }
 
     #[test]
-    fn test_winner_can_claim_after_oracle_withdrawal() {
-        // Verify that the bug is fixed: winner can claim even after oracle withdraws
-        let (env, contract_id, oracle, token_address) = setup_test();
+    fn test_buy_batch_reverts_as_a_whole() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
 
-        let winner = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&winner, &(100 * SCALE_FACTOR));
-
-        // Winner buys YES
-        client.buy(&winner, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
-
-        // Resolve: YES wins
-        client.resolve(&oracle, &0);
+        // A bad leg rejects the earlier valid one too
+        let legs = vec![&env, (0u32, 10 * SCALE_FACTOR), (1u32, 0)];
+        assert_eq!(
+            client.try_buy_batch(&user, &legs, &(50 * SCALE_FACTOR)),
+            Err(Ok(MarketError::InvalidAmount))
+        );
 
-        // Oracle withdraws first (before winner claims)
-        let withdrawn = client.withdraw_remaining(&oracle);
-        assert!(withdrawn > 0, "Oracle should withdraw something");
+        // The cap applies to the total, not each leg
+        let legs = vec![&env, (0u32, 10 * SCALE_FACTOR), (1u32, 10 * SCALE_FACTOR)];
+        assert_eq!(
+            client.try_buy_batch(&user, &legs, &(6 * SCALE_FACTOR)),
+            Err(Ok(MarketError::SlippageExceeded))
+        );
 
-        // Winner claims after oracle withdrawal - THIS USED TO FAIL
-        let payout = client.claim(&winner);
-        let expected = 10 * SCALE_FACTOR * (BPS_DENOMINATOR - CLAIM_FEE_BPS) / BPS_DENOMINATOR;
-        assert_eq!(payout, expected);
+        assert_eq!(client.get_balances(&user), (0, 0));
+        assert_eq!(client.get_state().0, 0);
     }
 
-    // --- Quote validation tests ---
+    // --- Effective spread tests ---
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
-    fn test_get_quote_invalid_outcome() {
+    fn test_effective_spread_includes_trade_fees() {
         let (env, contract_id, _oracle, _token_address) = setup_test();
+        let fee_free = LmsrMarketClient::new(&env, &contract_id).get_effective_spread(&0);
+        assert_eq!(fee_free, 0);
+
+        let env = Env::default();
+        let (contract_id, _oracle, token_address) = register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                protocol_fee_bps: 100,
+                protocol_fee_recipient: Some(Address::generate(&env)),
+                ..test_config()
+            },
+        );
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
 
-        // Try to get quote with invalid outcome
-        client.get_quote(&99, &(10 * SCALE_FACTOR));
+        let spread = client.get_effective_spread(&0);
+        assert!(spread > fee_free);
+
+        // Matches an actual buy and sell of one token
+        let paid = client.buy(&user, &0, &SCALE_FACTOR, &(10 * SCALE_FACTOR));
+        let received = client.sell(&user, &0, &SCALE_FACTOR, &0);
+        assert_eq!(spread, paid - received);
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
-    fn test_get_quote_zero_amount() {
-        let (env, contract_id, _oracle, _token_address) = setup_test();
+    fn test_redeem_pair_beats_two_sells() {
+        let amount = 10 * SCALE_FACTOR;
+        let hold_both_sides = |env: &Env, contract_id: &Address, token_address: &Address| {
+            let client = LmsrMarketClient::new(env, contract_id);
+            let user = Address::generate(env);
+            StellarAssetClient::new(env, token_address).mint(&user, &(100 * SCALE_FACTOR));
+            client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+            client.buy(&user, &1, &(15 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+            user
+        };
+
+        let env = Env::default();
+        let (contract_id, _oracle, token_address) = register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                protocol_fee_bps: 100,
+                protocol_fee_recipient: Some(Address::generate(&env)),
+                ..test_config()
+            },
+        );
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let user = hold_both_sides(&env, &contract_id, &token_address);
+        let token = token::Client::new(&env, &token_address);
+        let prices_before = (client.get_price(&0), client.get_price(&1));
+        let cash_before = token.balance(&user);
 
-        // Try to get quote with zero amount
-        client.get_quote(&0, &0);
+        assert_eq!(client.redeem_pair(&user, &amount), amount);
+        assert_eq!(token.balance(&user) - cash_before, amount);
+        assert_eq!(
+            client.get_balances(&user),
+            (10 * SCALE_FACTOR, 5 * SCALE_FACTOR)
+        );
+        assert_eq!((client.get_price(&0), client.get_price(&1)), prices_before);
+        let (pool, min_pool) = client.check_solvency();
+        assert!(pool >= min_pool);
+
+        // The same position closed with two sells in an identical market
+        let env = Env::default();
+        let (contract_id, _oracle, token_address) = register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                protocol_fee_bps: 100,
+                protocol_fee_recipient: Some(Address::generate(&env)),
+                ..test_config()
+            },
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let user = hold_both_sides(&env, &contract_id, &token_address);
+        let sold = client.sell(&user, &0, &amount, &0) + client.sell(&user, &1, &amount, &0);
+        assert!(sold < amount, "two sells returned {}", sold);
     }
 
-    // --- Double resolution prevention test ---
+    #[test]
+    fn test_redeem_pair_needs_both_sides() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(5 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+
+        assert_eq!(
+            client.try_redeem_pair(&user, &(6 * SCALE_FACTOR)),
+            Err(Ok(MarketError::InsufficientBalance))
+        );
+        assert_eq!(
+            client.try_redeem_pair(&user, &0),
+            Err(Ok(MarketError::InvalidAmount))
+        );
+
+        client.redeem_pair(&user, &(5 * SCALE_FACTOR));
+        assert_eq!(
+            client.get_user_positions(&user),
+            vec![&env, (0u32, 15 * SCALE_FACTOR)]
+        );
+    }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #3)")] // AlreadyResolved = 3
-    fn test_double_resolve_fails() {
-        let (env, contract_id, oracle, _token_address) = setup_test();
+    fn test_spread_at_equilibrium() {
+        let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // First resolution succeeds
-        client.resolve(&oracle, &0);
+        // Equal quantities outstanding on both sides
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &1, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
 
-        // Second resolution should fail
-        client.resolve(&oracle, &1); // Should panic with AlreadyResolved
+        let amount = 10 * SCALE_FACTOR;
+        let (buy_cost, sell_return, spread) = client.get_spread(&0, &amount);
+        assert_eq!(buy_cost, client.get_quote(&0, &amount).0);
+        assert_eq!(sell_return, client.get_sell_quote(&0, &amount).0);
+        assert_eq!(spread, buy_cost - sell_return);
+
+        // Convexity makes it non-negative; for 10 tokens at b = 100 it is
+        // about a quarter of a token
+        assert!(spread >= 0, "spread = {}", spread);
+        assert!(spread < amount / 20, "spread = {}", spread);
     }
 
-    // --- Insufficient initial funding test ---
+    // --- Minimum trade size tests ---
 
     #[test]
-    #[should_panic(expected = "InvalidAmount")]
-    fn test_initialize_insufficient_funding() {
+    fn test_min_trade_amount() {
         let env = Env::default();
         env.mock_all_auths();
 
         let oracle = Address::generate(&env);
-
-        // Create a test token
         let token_admin = Address::generate(&env);
-        let token_contract = env.register_stellar_asset_contract_v2(token_admin.clone());
-        let token_address = token_contract.address();
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
         let token_admin_client = StellarAssetClient::new(&env, &token_address);
         token_admin_client.mint(&oracle, &(1000 * SCALE_FACTOR));
 
-        let b = 100 * SCALE_FACTOR;
-        // Required funding is b * ln(2) ≈ 69.3, so 50 is insufficient
-        let insufficient_funding = 50 * SCALE_FACTOR;
-
-        // Constructor should panic with InvalidAmount
-        let _contract_id = env.register(
+        let min = SCALE_FACTOR;
+        let contract_id = env.register(
             LmsrMarket,
             (
-                oracle.clone(),
-                token_address.clone(),
-                b,
+                oracle,
+                token_address,
+                100 * SCALE_FACTOR,
                 String::from_str(&env, "QmTest"),
-                insufficient_funding,
+                70 * SCALE_FACTOR,
+                MarketConfig {
+                    min_trade_amount: min,
+                    ..test_config()
+                },
             ),
         );
-    }
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        assert_eq!(client.get_min_trade_amount(), min);
 
-    // --- Invalid outcome tests for buy/sell/resolve ---
+        // Another holder keeps the market away from 50/50 throughout
+        let whale = Address::generate(&env);
+        token_admin_client.mint(&whale, &(100 * SCALE_FACTOR));
+        client.buy(&whale, &0, &(20 * min), &(50 * SCALE_FACTOR));
+
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+
+        // Dust is rejected on both sides
+        assert_eq!(
+            client.try_buy(&user, &0, &(min - 1), &SCALE_FACTOR),
+            Err(Ok(MarketError::BelowMinimum))
+        );
+        client.buy(&user, &0, &min, &SCALE_FACTOR);
+        client.buy(&user, &0, &(3 * min), &(5 * SCALE_FACTOR));
+        assert_eq!(
+            client.try_sell(&user, &0, &(min - 1), &0),
+            Err(Ok(MarketError::BelowMinimum))
+        );
+        client.sell(&user, &0, &min, &0);
+        assert_eq!(client.get_balance(&user, &0), 3 * min);
+
+        // A remainder below the minimum can still be sold in full
+        client.sell(&user, &0, &(3 * min - 1), &0);
+        assert_eq!(client.get_balance(&user, &0), 1);
+        client.sell(&user, &0, &1, &0);
+        assert_eq!(client.get_balance(&user, &0), 0);
+    }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
-    fn test_buy_invalid_outcome() {
+    fn test_min_trade_amount_defaults_to_zero() {
         let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        assert_eq!(client.get_min_trade_amount(), 0);
 
         let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.buy(&user, &0, &1, &SCALE_FACTOR);
+        assert_eq!(client.get_balance(&user, &0), 20 * SCALE_FACTOR + 1);
+    }
 
-        // Try to buy with invalid outcome (99)
-        client.buy(&user, &99, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+    // --- Resolution callback tests ---
+
+    /// Vault stand-in that records the last resolution it was notified of.
+    #[contract]
+    struct MockVault;
+
+    #[contractimpl]
+    impl MockVault {
+        pub fn on_resolved(env: Env, market: Address, winning_outcome: u32) {
+            env.storage()
+                .instance()
+                .set(&symbol_short!("resolved"), &(market, winning_outcome));
+        }
+
+        pub fn last_resolved(env: Env) -> Option<(Address, u32)> {
+            env.storage().instance().get(&symbol_short!("resolved"))
+        }
+    }
+
+    /// Callback that always fails (own module, since both mocks export `on_resolved`).
+    mod failing_vault {
+        use soroban_sdk::{contract, contractimpl, Address, Env};
+
+        #[contract]
+        pub struct FailingVault;
+
+        #[contractimpl]
+        impl FailingVault {
+            pub fn on_resolved(_env: Env, _market: Address, _winning_outcome: u32) {
+                panic!("vault rejected the notification");
+            }
+        }
+    }
+
+    /// Callback that burns through the budget (own module, like FailingVault).
+    mod hostile_vault {
+        use soroban_sdk::{contract, contractimpl, symbol_short, Address, Env};
+
+        #[contract]
+        pub struct HostileVault;
+
+        #[contractimpl]
+        impl HostileVault {
+            pub fn on_resolved(env: Env, _market: Address, _winning_outcome: u32) {
+                let mut i: u64 = 0;
+                loop {
+                    env.storage().instance().set(&symbol_short!("spin"), &i);
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    /// Give `holder` one YES token so it may register a callback.
+    fn give_position(
+        env: &Env,
+        client: &LmsrMarketClient,
+        token_address: &Address,
+        holder: &Address,
+    ) {
+        StellarAssetClient::new(env, token_address).mint(holder, &(10 * SCALE_FACTOR));
+        client.buy(holder, &0, &SCALE_FACTOR, &(10 * SCALE_FACTOR));
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
-    fn test_sell_invalid_outcome() {
-        let (env, contract_id, _oracle, token_address) = setup_test();
+    fn test_resolution_callback_is_notified() {
+        let (env, contract_id, oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        let holder = Address::generate(&env);
+        give_position(&env, &client, &token_address, &holder);
+        let vault_id = env.register(MockVault, ());
+        let vault = MockVaultClient::new(&env, &vault_id);
 
-        // First buy some valid tokens
-        client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        client.set_resolution_callback(&holder, &Some(vault_id.clone()));
+        assert_eq!(client.get_resolution_callback(&holder), Some(vault_id));
+        assert_eq!(
+            client.try_notify_callbacks(&vec![&env, holder.clone()]),
+            Err(Ok(MarketError::NotResolved))
+        );
 
-        // Try to sell with invalid outcome (99)
-        client.sell(&user, &99, &(5 * SCALE_FACTOR), &0);
+        // Resolving doesn't call out; notification is a separate step
+        client.resolve(&oracle, &1);
+        assert_eq!(vault.last_resolved(), None);
+
+        assert_eq!(client.notify_callbacks(&vec![&env, holder.clone()]), 1);
+        assert_eq!(vault.last_resolved(), Some((contract_id, 1)));
+
+        // Each callback is notified once
+        assert_eq!(client.notify_callbacks(&vec![&env, holder.clone()]), 0);
+        assert_eq!(client.get_resolution_callback(&holder), None);
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
-    fn test_resolve_invalid_outcome() {
-        let (env, contract_id, oracle, _token_address) = setup_test();
+    fn test_failing_resolution_callback_is_skipped() {
+        let (env, contract_id, oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Try to resolve with invalid outcome (99)
-        client.resolve(&oracle, &99);
+        let failing_holder = Address::generate(&env);
+        let holder = Address::generate(&env);
+        give_position(&env, &client, &token_address, &failing_holder);
+        give_position(&env, &client, &token_address, &holder);
+        let failing_id = env.register(failing_vault::FailingVault, ());
+        let vault_id = env.register(MockVault, ());
+        client.set_resolution_callback(&failing_holder, &Some(failing_id));
+        client.set_resolution_callback(&holder, &Some(vault_id.clone()));
+
+        client.resolve(&oracle, &0);
+        assert_eq!(client.notify_callbacks(&client.get_callback_holders()), 2);
+
+        assert_eq!(
+            MockVaultClient::new(&env, &vault_id).last_resolved(),
+            Some((contract_id, 0))
+        );
+        assert_eq!(client.get_callback_holders().len(), 0);
     }
 
-    // --- Zero/negative amount tests ---
+    #[test]
+    fn test_hostile_resolution_callback_does_not_block_resolve() {
+        let (env, contract_id, oracle, token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        let hostile_holder = Address::generate(&env);
+        let holder = Address::generate(&env);
+        give_position(&env, &client, &token_address, &hostile_holder);
+        give_position(&env, &client, &token_address, &holder);
+        let hostile_id = env.register(hostile_vault::HostileVault, ());
+        let vault_id = env.register(MockVault, ());
+        client.set_resolution_callback(&hostile_holder, &Some(hostile_id));
+        client.set_resolution_callback(&holder, &Some(vault_id.clone()));
+
+        client.resolve(&oracle, &0);
+        assert_eq!(client.get_status(), String::from_str(&env, "resolved"));
+
+        // Notifying the hostile callback would exhaust the budget, which no
+        // caller can recover from, so it is left off the list
+        assert_eq!(client.notify_callbacks(&vec![&env, holder]), 1);
+        assert_eq!(client.get_callback_holders(), vec![&env, hostile_holder]);
+        assert_eq!(
+            MockVaultClient::new(&env, &vault_id).last_resolved(),
+            Some((contract_id, 0))
+        );
+    }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
-    fn test_buy_zero_amount() {
+    fn test_resolution_callback_requires_position() {
         let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let vault_id = env.register(MockVault, ());
 
-        let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        let holder = Address::generate(&env);
+        assert_eq!(
+            client.try_set_resolution_callback(&holder, &Some(vault_id.clone())),
+            Err(Ok(MarketError::InsufficientBalance))
+        );
 
-        // Try to buy zero amount
-        client.buy(&user, &0, &0, &(50 * SCALE_FACTOR));
+        give_position(&env, &client, &token_address, &holder);
+        client.set_resolution_callback(&holder, &Some(vault_id));
+        assert_eq!(client.get_callback_holders(), vec![&env, holder.clone()]);
+
+        // Selling out frees the slot
+        client.sell_all(&holder, &0, &0);
+        assert_eq!(client.get_resolution_callback(&holder), None);
+        assert_eq!(client.get_callback_holders().len(), 0);
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #6)")] // InvalidAmount = 6
-    fn test_buy_negative_amount() {
+    fn test_resolution_callbacks_are_bounded() {
         let (env, contract_id, _oracle, token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        let vault_id = env.register(MockVault, ());
+
+        let mut holders = Vec::new(&env);
+        for _ in 0..MAX_RESOLUTION_CALLBACKS {
+            let holder = Address::generate(&env);
+            give_position(&env, &client, &token_address, &holder);
+            client.set_resolution_callback(&holder, &Some(vault_id.clone()));
+            holders.push_back(holder);
+        }
 
-        let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        let extra = Address::generate(&env);
+        give_position(&env, &client, &token_address, &extra);
+        assert_eq!(
+            client.try_set_resolution_callback(&extra, &Some(vault_id.clone())),
+            Err(Ok(MarketError::TooManyCallbacks))
+        );
 
-        // Try to buy negative amount
-        client.buy(&user, &0, &(-10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        // Re-registering an existing holder doesn't take a slot
+        let first = holders.get(0).unwrap();
+        client.set_resolution_callback(&first, &Some(vault_id.clone()));
+
+        // Clearing one frees a slot
+        client.set_resolution_callback(&first, &None);
+        assert_eq!(client.get_resolution_callback(&first), None);
+        client.set_resolution_callback(&extra, &Some(vault_id));
     }
 
-    // --- Multiple users scenario ---
+    // --- Non-zero winner tests ---
 
     #[test]
-    fn test_multiple_users_claim_correctly() {
-        let (env, contract_id, oracle, token_address) = setup_test();
+    fn test_require_nonzero_winner_rejects_empty_side() {
+        let (env, contract_id, oracle, _token_address, _user) = setup_with_position(MarketConfig {
+            require_nonzero_winner: true,
+            ..test_config()
+        });
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        assert_eq!(
+            client.try_resolve(&oracle, &1),
+            Err(Ok(MarketError::EmptyWinningSide))
+        );
+        assert_eq!(client.get_status(), String::from_str(&env, "open"));
 
-        // User1 buys YES, User2 buys NO
-        let user1 = Address::generate(&env);
-        let user2 = Address::generate(&env);
-        token_admin_client.mint(&user1, &(100 * SCALE_FACTOR));
-        token_admin_client.mint(&user2, &(100 * SCALE_FACTOR));
+        client.resolve(&oracle, &0);
+        assert_eq!(client.get_status(), String::from_str(&env, "resolved"));
+    }
 
-        let amount1 = 10 * SCALE_FACTOR;
-        let amount2 = 8 * SCALE_FACTOR;
-        client.buy(&user1, &0, &amount1, &(50 * SCALE_FACTOR)); // YES
-        client.buy(&user2, &1, &amount2, &(50 * SCALE_FACTOR)); // NO
+    #[test]
+    fn test_empty_winning_side_allowed_by_default() {
+        let (env, contract_id, oracle, _token_address, _user) = setup_with_position(MarketConfig {
+            require_nonzero_winner: false,
+            ..test_config()
+        });
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Resolve with YES winning
-        client.resolve(&oracle, &0);
+        client.resolve(&oracle, &1);
+        assert_eq!(client.get_status(), String::from_str(&env, "resolved"));
+    }
 
-        // User1 (winner) claims successfully (minus 2% fee)
-        let payout1 = client.claim(&user1);
-        let expected_payout1 = amount1 - (amount1 * CLAIM_FEE_BPS / BPS_DENOMINATOR);
-        assert_eq!(payout1, expected_payout1);
+    // --- Units tests ---
 
-        // User2's balance should be 0 for winning outcome
-        assert_eq!(client.get_balance(&user2, &0), 0);
+    /// Collateral stand-in with 6 decimals; transfers are no-ops.
+    mod six_decimal_token {
+        use soroban_sdk::{contract, contractimpl, Address, Env};
+
+        #[contract]
+        pub struct SixDecimalToken;
+
+        #[contractimpl]
+        impl SixDecimalToken {
+            pub fn decimals(_env: Env) -> u32 {
+                6
+            }
+
+            pub fn transfer(_env: Env, _from: Address, _to: Address, _amount: i128) {}
+        }
     }
 
-    // --- Claim fee tests ---
+    #[test]
+    fn test_units_info_for_stellar_asset() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
+
+        assert_eq!(client.get_units_info(), (SHARE_SCALE, 10_000_000));
+    }
 
     #[test]
-    fn test_claim_fee_calculation() {
-        let (env, contract_id, oracle, token_address) = setup_test();
+    fn test_units_info_for_six_decimal_collateral() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let token_address = env.register(six_decimal_token::SixDecimalToken, ());
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                Address::generate(&env),
+                token_address,
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                test_config(),
+            ),
+        );
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+        assert_eq!(client.get_units_info(), (10_000_000, 1_000_000));
+    }
+
+    /// Collateral with configurable decimals that keeps real balances.
+    mod decimal_token {
+        use soroban_sdk::{contract, contractimpl, Address, Env};
+
+        #[contract]
+        pub struct DecimalToken;
+
+        #[contractimpl]
+        impl DecimalToken {
+            pub fn __constructor(env: Env, decimals: u32) {
+                env.storage().instance().set(&(), &decimals);
+            }
+
+            pub fn decimals(env: Env) -> u32 {
+                env.storage().instance().get(&()).unwrap()
+            }
+
+            pub fn mint(env: Env, to: Address, amount: i128) {
+                let balance = Self::balance(env.clone(), to.clone());
+                env.storage().persistent().set(&to, &(balance + amount));
+            }
+
+            pub fn balance(env: Env, id: Address) -> i128 {
+                env.storage().persistent().get(&id).unwrap_or(0)
+            }
+
+            pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+                from.require_auth();
+                let from_balance = Self::balance(env.clone(), from.clone());
+                assert!(from_balance >= amount, "insufficient balance");
+                env.storage()
+                    .persistent()
+                    .set(&from, &(from_balance - amount));
+                Self::mint(env, to, amount);
+            }
+        }
+    }
+
+    /// Run a market through funding, trading, claiming and withdrawal with a
+    /// `decimals`-decimal collateral, checking every transfer is the internal
+    /// 7-decimal amount converted to token units.
+    fn assert_collateral_round_trip(decimals: u32) {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let token_address = env.register(decimal_token::DecimalToken, (decimals,));
+        let token_client = decimal_token::DecimalTokenClient::new(&env, &token_address);
+        let unit = 10i128.pow(decimals);
+        // Payments into the market round up, payouts round down
+        let to_token = |amount: i128, round_up: bool| {
+            if decimals >= 7 {
+                amount * 10i128.pow(decimals - 7)
+            } else {
+                let divisor = 10i128.pow(7 - decimals);
+                (amount + if round_up { divisor - 1 } else { 0 }) / divisor
+            }
+        };
 
+        let oracle = Address::generate(&env);
         let user = Address::generate(&env);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        token_client.mint(&oracle, &(1000 * unit));
+        token_client.mint(&user, &(100 * unit));
 
-        // Buy exactly 100 tokens (for easy math)
-        let amount = 100 * SCALE_FACTOR;
-        client.buy(&user, &0, &amount, &(100 * SCALE_FACTOR));
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address,
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                test_config(),
+            ),
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        assert_eq!(token_client.balance(&contract_id), 70 * unit);
 
-        let (_, _, pool_before, _) = client.get_state();
+        let cost = client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(
+            token_client.balance(&user),
+            100 * unit - to_token(cost, true)
+        );
 
-        // Resolve: YES wins
-        client.resolve(&oracle, &0);
+        let before = token_client.balance(&user);
+        let returned = client.sell(&user, &0, &(4 * SCALE_FACTOR), &0);
+        assert_eq!(
+            token_client.balance(&user),
+            before + to_token(returned, false)
+        );
 
-        // Claim and verify 2% fee
+        client.resolve(&oracle, &0);
+        let before = token_client.balance(&user);
         let payout = client.claim(&user);
+        assert_eq!(
+            token_client.balance(&user),
+            before + to_token(payout, false)
+        );
 
-        // 2% of 100 = 2, so payout should be 98
-        let expected_fee = 2 * SCALE_FACTOR;
-        let expected_payout = amount - expected_fee;
-        assert_eq!(payout, expected_payout, "Expected 98% payout after 2% fee");
-
-        // Verify fee stayed in pool
-        let (_, _, pool_after, _) = client.get_state();
-        // Pool should have: pool_before - payout = pool_before - (amount - fee) = pool_before - amount + fee
-        // Since winning tokens are 1:1 redeemable from pool, the fee portion remains
+        let before = token_client.balance(&oracle);
+        let withdrawn = client.withdraw_remaining(&oracle);
         assert_eq!(
-            pool_after,
-            pool_before - expected_payout,
-            "Fee should remain in pool"
+            token_client.balance(&oracle),
+            before + to_token(withdrawn, false)
         );
+
+        // Whatever is left still covers the pool
+        let (_, _, pool, _) = client.get_state();
+        assert!(token_client.balance(&contract_id) >= to_token(pool, false));
     }
 
     #[test]
-    fn test_oracle_collects_accumulated_fees_from_multiple_claims() {
-        let (env, contract_id, oracle, token_address) = setup_test();
-        let client = LmsrMarketClient::new(&env, &contract_id);
+    fn test_six_decimal_collateral_round_trip() {
+        assert_collateral_round_trip(6);
+    }
 
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+    #[test]
+    fn test_eighteen_decimal_collateral_round_trip() {
+        assert_collateral_round_trip(18);
+    }
 
-        // Three users: two winners (YES), one loser (NO)
-        let winner1 = Address::generate(&env);
-        let winner2 = Address::generate(&env);
-        let loser = Address::generate(&env);
-        token_admin_client.mint(&winner1, &(100 * SCALE_FACTOR));
-        token_admin_client.mint(&winner2, &(100 * SCALE_FACTOR));
-        token_admin_client.mint(&loser, &(100 * SCALE_FACTOR));
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidConfig")]
+    fn test_collateral_decimals_above_max_rejected() {
+        let env = Env::default();
+        env.mock_all_auths();
 
-        // Winner1 buys 100 YES, Winner2 buys 50 YES, Loser buys 30 NO
-        let amount1 = 100 * SCALE_FACTOR;
-        let amount2 = 50 * SCALE_FACTOR;
-        let amount3 = 30 * SCALE_FACTOR;
-        client.buy(&winner1, &0, &amount1, &(100 * SCALE_FACTOR));
-        client.buy(&winner2, &0, &amount2, &(100 * SCALE_FACTOR));
-        client.buy(&loser, &1, &amount3, &(50 * SCALE_FACTOR));
+        let token_address = env.register(decimal_token::DecimalToken, (19u32,));
+        env.register(
+            LmsrMarket,
+            (
+                Address::generate(&env),
+                token_address,
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                test_config(),
+            ),
+        );
+    }
 
-        let (_, _, _pool_after_buys, _) = client.get_state();
+    // --- Claim fee rewards tests ---
 
-        // Resolve: YES wins
+    #[test]
+    fn test_claim_fee_split_with_rewards_sums_to_fee() {
+        let env = Env::default();
+        let rewards_address = Address::generate(&env);
+        let (contract_id, oracle, token_address) = register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                rewards_address: Some(rewards_address.clone()),
+                rewards_share_bps: 2_500,
+                ..test_config()
+            },
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        let amount = 10 * SCALE_FACTOR;
+        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
         client.resolve(&oracle, &0);
 
-        // Both winners claim
-        let payout1 = client.claim(&winner1);
-        let payout2 = client.claim(&winner2);
+        let (_, _, pool_before, _) = client.get_state();
+        let payout = client.claim(&user);
+        let fee = amount - payout;
+        assert_eq!(fee, amount * CLAIM_FEE_BPS / BPS_DENOMINATOR);
 
-        // Verify payouts are 98% of token amounts
-        let expected_fee1 = amount1 * CLAIM_FEE_BPS / BPS_DENOMINATOR; // 2% of 100 = 2
-        let expected_fee2 = amount2 * CLAIM_FEE_BPS / BPS_DENOMINATOR; // 2% of 50 = 1
-        assert_eq!(payout1, amount1 - expected_fee1);
-        assert_eq!(payout2, amount2 - expected_fee2);
+        // A quarter of the fee goes to rewards, the rest stays for the oracle
+        let rewards = token_client.balance(&rewards_address);
+        assert_eq!(rewards, fee / 4);
+        assert_eq!(rewards + client.get_accrued_fees(), fee);
+        let (_, _, pool_after, _) = client.get_state();
+        assert_eq!(pool_before - pool_after, payout + rewards);
+    }
 
-        // Total fees accumulated: 2 + 1 = 3 SCALE_FACTOR
-        let total_fees = expected_fee1 + expected_fee2;
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidFee")]
+    fn test_rewards_share_above_full_fee_rejected() {
+        let env = Env::default();
+        register_with_config(
+            &env,
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                rewards_address: Some(Address::generate(&env)),
+                rewards_share_bps: 10_001,
+                ..test_config()
+            },
+        );
+    }
 
-        // Pool should have: initial_funding + all_buy_costs - winner_payouts
-        // Which includes: loser's bet + accumulated fees
-        let (_, _, pool_after_claims, _) = client.get_state();
-        assert!(pool_after_claims > 0, "Pool should have remaining funds");
+    #[test]
+    #[should_panic(expected = "initialization failed: InvalidConfig")]
+    fn test_rewards_share_without_address_rejected() {
+        setup_test_with_config(
+            100 * SCALE_FACTOR,
+            70 * SCALE_FACTOR,
+            MarketConfig {
+                rewards_share_bps: 2_500,
+                ..test_config()
+            },
+        );
+    }
 
-        // Oracle withdraws everything remaining
-        let withdrawn = client.withdraw_remaining(&oracle);
-        assert_eq!(withdrawn, pool_after_claims);
+    // --- Reentrancy tests ---
 
-        // Verify oracle got at least the accumulated fees
-        // (also includes loser's funds and any leftover from initial funding)
-        assert!(
-            withdrawn >= total_fees,
-            "Oracle should receive at least the accumulated fees: {} >= {}",
-            withdrawn,
-            total_fees
-        );
+    /// Collateral that calls back into the market from `transfer`, once per
+    /// `arm`, and records whether the nested call went through.
+    mod reentrant_token {
+        use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Symbol, Val, Vec};
 
-        // Pool should be empty now
-        let (_, _, pool_final, _) = client.get_state();
-        assert_eq!(pool_final, 0);
+        #[contracttype]
+        enum Key {
+            Balance(Address),
+            Target,
+            Reentered,
+        }
+
+        #[contract]
+        pub struct ReentrantToken;
+
+        #[contractimpl]
+        impl ReentrantToken {
+            pub fn arm(env: Env, market: Address, func: Symbol, args: Vec<Val>) {
+                env.storage()
+                    .instance()
+                    .set(&Key::Target, &(market, func, args));
+            }
+
+            pub fn reentered(env: Env) -> Option<bool> {
+                env.storage().instance().get(&Key::Reentered)
+            }
+
+            pub fn decimals(_env: Env) -> u32 {
+                7
+            }
+
+            pub fn mint(env: Env, to: Address, amount: i128) {
+                let balance = Self::balance(env.clone(), to.clone());
+                env.storage()
+                    .instance()
+                    .set(&Key::Balance(to), &(balance + amount));
+            }
+
+            pub fn balance(env: Env, id: Address) -> i128 {
+                env.storage().instance().get(&Key::Balance(id)).unwrap_or(0)
+            }
+
+            pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+                from.require_auth();
+                let from_balance = Self::balance(env.clone(), from.clone());
+                assert!(from_balance >= amount, "insufficient balance");
+                env.storage()
+                    .instance()
+                    .set(&Key::Balance(from), &(from_balance - amount));
+                Self::mint(env.clone(), to, amount);
+
+                let target: Option<(Address, Symbol, Vec<Val>)> =
+                    env.storage().instance().get(&Key::Target);
+                if let Some((market, func, args)) = target {
+                    env.storage().instance().remove(&Key::Target);
+                    let result =
+                        env.try_invoke_contract::<Val, soroban_sdk::Error>(&market, &func, args);
+                    env.storage()
+                        .instance()
+                        .set(&Key::Reentered, &matches!(result, Ok(Ok(_))));
+                }
+            }
+        }
     }
 
     #[test]
-    fn test_claim_fee_small_amount_truncation() {
-        // Documents expected behavior: fee rounds to 0 for tiny amounts
-        // due to integer division truncation.
-        // This is acceptable as these are dust-level amounts.
-        let (env, contract_id, oracle, token_address) = setup_test();
-        let client = LmsrMarketClient::new(&env, &contract_id);
-
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
+    fn test_reentrant_token_cannot_double_buy_or_claim() {
+        let env = Env::default();
+        env.mock_all_auths();
 
+        let token_address = env.register(reentrant_token::ReentrantToken, ());
+        let token_client = reentrant_token::ReentrantTokenClient::new(&env, &token_address);
+        let oracle = Address::generate(&env);
         let user = Address::generate(&env);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        token_client.mint(&oracle, &(1000 * SCALE_FACTOR));
+        token_client.mint(&user, &(100 * SCALE_FACTOR));
 
-        // Buy 49 units (below threshold where fee = 0)
-        // Fee calculation: 49 * 200 / 10000 = 9800 / 10000 = 0 (integer truncation)
-        let tiny_amount: i128 = 49;
-        client.buy(&user, &0, &tiny_amount, &SCALE_FACTOR);
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address.clone(),
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                test_config(),
+            ),
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        client.resolve(&oracle, &0);
+        // Paying for a buy tries to buy again
+        let amount = 10 * SCALE_FACTOR;
+        token_client.arm(
+            &contract_id,
+            &Symbol::new(&env, "buy"),
+            &vec![
+                &env,
+                user.into_val(&env),
+                0u32.into_val(&env),
+                amount.into_val(&env),
+                (50 * SCALE_FACTOR).into_val(&env),
+            ],
+        );
+        let cost = client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        assert_eq!(token_client.reentered(), Some(false));
+        assert_eq!(client.get_balance(&user, &0), amount);
+        assert_eq!(token_client.balance(&user), 100 * SCALE_FACTOR - cost);
 
+        // Receiving a payout tries to claim again
+        client.resolve(&oracle, &0);
+        token_client.arm(
+            &contract_id,
+            &Symbol::new(&env, "claim"),
+            &vec![&env, user.into_val(&env)],
+        );
         let payout = client.claim(&user);
-
-        // With amount = 49, fee = 49 * 200 / 10000 = 0 (truncated)
-        // So user gets full amount back (no fee on dust amounts)
+        assert_eq!(token_client.reentered(), Some(false));
         assert_eq!(
-            payout, tiny_amount,
-            "Dust amounts should have zero fee due to integer truncation"
+            token_client.balance(&user),
+            100 * SCALE_FACTOR - cost + payout
+        );
+        assert_eq!(
+            client.try_claim(&user),
+            Err(Ok(MarketError::NothingToClaim))
         );
     }
 
-    // --- Sell all tokens to equilibrium ---
+    // --- Transfer failure tests ---
 
     #[test]
-    fn test_sell_all_returns_to_near_equilibrium() {
-        let (env, contract_id, _oracle, token_address) = setup_test();
-        let client = LmsrMarketClient::new(&env, &contract_id);
+    fn test_payout_to_deauthorized_recipient_returns_transfer_failed() {
+        use soroban_sdk::testutils::IssuerFlags;
 
-        let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(200 * SCALE_FACTOR));
+        let env = Env::default();
+        env.mock_all_auths();
 
-        // Buy tokens
-        let amount = 10 * SCALE_FACTOR;
-        let buy_cost = client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        // An asset whose issuer can revoke authorization
+        let token_contract = env.register_stellar_asset_contract_v2(Address::generate(&env));
+        token_contract.issuer().set_flag(IssuerFlags::RevocableFlag);
+        let token_address = token_contract.address();
+        let token_admin_client = StellarAssetClient::new(&env, &token_address);
 
-        // Get state after buy
-        let (q_yes_after_buy, _, _, _) = client.get_state();
-        assert_eq!(q_yes_after_buy, amount);
+        let oracle = Address::generate(&env);
+        token_admin_client.mint(&oracle, &(1000 * SCALE_FACTOR));
+        let contract_id = env.register(
+            LmsrMarket,
+            (
+                oracle.clone(),
+                token_address,
+                100 * SCALE_FACTOR,
+                String::from_str(&env, "QmTest"),
+                70 * SCALE_FACTOR,
+                test_config(),
+            ),
+        );
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Sell all back
-        let sell_return = client.sell(&user, &0, &amount, &0);
+        let user = Address::generate(&env);
+        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        client.buy(&user, &0, &(20 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
 
-        // Get state after sell
-        let (q_yes_after_sell, q_no_after_sell, _, _) = client.get_state();
-        assert_eq!(q_yes_after_sell, 0);
-        assert_eq!(q_no_after_sell, 0);
+        // The issuer freezes the user's balance
+        token_admin_client.set_authorized(&user, &false);
 
-        // User balance should be 0
-        assert_eq!(client.get_balance(&user, &0), 0);
+        assert_eq!(
+            client.try_sell(&user, &0, &(5 * SCALE_FACTOR), &0),
+            Err(Ok(MarketError::TransferFailed))
+        );
 
-        // LMSR is symmetric: buying and immediately selling the same amount
-        // returns exactly what was paid (no spread). The sell_return should
-        // equal buy_cost when returning to the same state.
+        client.resolve(&oracle, &0);
         assert_eq!(
-            sell_return, buy_cost,
-            "LMSR symmetric round-trip: sell_return={}, buy_cost={}",
-            sell_return, buy_cost
+            client.try_claim(&user),
+            Err(Ok(MarketError::TransferFailed))
         );
+
+        // The failed claim left the position intact, so it can be retried
+        assert_eq!(client.get_balance(&user, &0), 20 * SCALE_FACTOR);
+        token_admin_client.set_authorized(&user, &true);
+        assert!(client.claim(&user) > 0);
     }
 
-    // --- get_sell_quote tests ---
+    // --- Resolution evidence tests ---
 
     #[test]
-    fn test_get_sell_quote_basic() {
-        let (env, contract_id, _oracle, token_address) = setup_test();
+    fn test_resolution_evidence_round_trip() {
+        let (env, contract_id, oracle, _token_address) = setup_test();
         let client = LmsrMarketClient::new(&env, &contract_id);
+        assert_eq!(client.get_resolution_evidence(), None);
 
-        let user = Address::generate(&env);
-        let token_admin_client = StellarAssetClient::new(&env, &token_address);
-        token_admin_client.mint(&user, &(100 * SCALE_FACTOR));
+        let evidence = String::from_str(&env, "QmResolutionSource");
+        client.resolve_with_evidence(&oracle, &1, &evidence);
 
-        // Buy tokens first
-        let amount = 10 * SCALE_FACTOR;
-        client.buy(&user, &0, &amount, &(50 * SCALE_FACTOR));
+        assert_eq!(client.get_resolution_evidence(), Some(evidence));
+        assert_eq!(client.get_status(), String::from_str(&env, "resolved"));
+        assert_eq!(client.get_winning_outcome(), 1);
+    }
 
-        // Get sell quote
-        let (return_amount, price_after) = client.get_sell_quote(&0, &amount);
+    #[test]
+    fn test_resolution_evidence_rejected_with_resolve() {
+        let (env, contract_id, _oracle, _token_address) = setup_test();
+        let client = LmsrMarketClient::new(&env, &contract_id);
 
-        assert!(return_amount > 0, "Sell return should be positive");
-        assert!(
-            (0..=SCALE_FACTOR).contains(&price_after),
-            "Price after should be in [0, 1]"
+        let evidence = String::from_str(&env, "QmForged");
+        assert_eq!(
+            client.try_resolve_with_evidence(&Address::generate(&env), &0, &evidence),
+            Err(Ok(MarketError::Unauthorized))
         );
+        assert_eq!(client.get_resolution_evidence(), None);
     }
 
     #[test]
-    #[should_panic(expected = "Error(Contract, #5)")] // InvalidOutcome = 5
-    fn test_get_sell_quote_invalid_outcome() {
-        let (env, contract_id, _oracle, _token_address) = setup_test();
+    fn test_dispute_clears_resolution_evidence() {
+        let (env, contract_id, oracle, _token_address, user) = setup_with_position(MarketConfig {
+            dispute_window_ledgers: Some(100),
+            ..test_config()
+        });
         let client = LmsrMarketClient::new(&env, &contract_id);
 
-        // Try to get sell quote with invalid outcome
-        client.get_sell_quote(&99, &(10 * SCALE_FACTOR));
+        client.resolve_with_evidence(&oracle, &0, &String::from_str(&env, "QmWrong"));
+        client.dispute(&user);
+        assert_eq!(client.get_resolution_evidence(), None);
     }
 }
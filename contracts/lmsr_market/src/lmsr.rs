@@ -5,57 +5,160 @@
 //! All calculations use fixed-point arithmetic with SCALE_FACTOR (10^7).
 //! This matches Stellar's 7 decimal place precision.
 //!
+//! exp and ln run at a higher internal precision. exp's iteration count and input
+//! range are derived from that precision and SCALE_FACTOR (see `ExpParams`).
+//!
 //! Formulas:
 //! - Cost function: C(q) = b * ln(e^(qYes/b) + e^(qNo/b))
 //! - Price: P(yes) = e^(qYes/b) / (e^(qYes/b) + e^(qNo/b))
 //! - Buy cost: C(q_new) - C(q_old)
 
 use crate::error::MarketError;
-use crate::storage::{LN2_SCALED, SCALE_FACTOR};
+#[cfg(test)]
+use crate::storage::COLLATERAL_DECIMALS;
+use crate::storage::{LN2_SCALED, OUTCOME_COUNT, SCALE_FACTOR};
+
+/// Highest internal precision exp supports, in decimal digits.
+/// One more digit and `term * r` in the series overflows i128.
+const MAX_EXP_PRECISION_DECIMALS: u32 = 18;
+
+/// Internal precision exp_scaled runs at, in decimal digits.
+/// Anything from COLLATERAL_DECIMALS up to MAX_EXP_PRECISION_DECIMALS works;
+/// more digits trade a slightly lower input limit for accuracy.
+const EXP_PRECISION_DECIMALS: u32 = MAX_EXP_PRECISION_DECIMALS;
+
+/// ln(2) at MAX_EXP_PRECISION_DECIMALS.
+const LN2_PRECISE: i128 = 693_147_180_559_945_309;
+
+/// Tuning for exp at a given internal precision.
+///
+/// Both the Taylor iteration count and the input limit follow from the
+/// precision and SCALE_FACTOR (i.e. the collateral decimals):
+/// - After range reduction |r| <= ln(2)/2, so the series needs the smallest
+///   n with (ln(2)/2)^n / n! below one internal unit.
+/// - The result is e^r * 2^k with e^r < 2, so k must leave 2^(k+1) * precision
+///   inside i128; 2^k * SCALE_FACTOR^2 is kept inside it too, so a result
+///   can be rescaled by SCALE_FACTOR. One step of k is held back because k
+///   is rounded, not truncated.
+struct ExpParams {
+    /// 10^decimals
+    precision: i128,
+    /// ln(2) at `precision`
+    ln2: i128,
+    /// Taylor terms needed to reach `precision`
+    iterations: u32,
+    /// Largest |x| (scaled by SCALE_FACTOR) accepted
+    input_limit: i128,
+}
+
+impl ExpParams {
+    const fn new(decimals: u32) -> Self {
+        let precision = 10i128.pow(decimals);
+        let ln2 = LN2_PRECISE / 10i128.pow(MAX_EXP_PRECISION_DECIMALS - decimals);
 
-/// Maximum iterations for exp Taylor series approximation.
-/// 20 iterations provides approximately 7+ decimal digits of accuracy
-/// for inputs within the [-20, 20] range (scaled), matching SCALE_FACTOR precision.
-/// Each iteration uses checked arithmetic to detect overflow.
-const EXP_ITERATIONS: u32 = 20;
+        let mut iterations: u32 = 0;
+        let mut term = precision;
+        while term > 0 {
+            iterations += 1;
+            term = term * (ln2 / 2) / (iterations as i128 * precision);
+        }
+
+        let k_shift = (i128::MAX / (2 * precision)).ilog2();
+        let k_price = (i128::MAX / (SCALE_FACTOR * SCALE_FACTOR)).ilog2();
+        let k_max = if k_shift < k_price { k_shift } else { k_price } - 1;
 
-/// Scaled exp function using Taylor series: e^x = 1 + x + x²/2! + x³/3! + ...
+        ExpParams {
+            precision,
+            ln2,
+            iterations,
+            input_limit: k_max as i128 * LN2_SCALED,
+        }
+    }
+}
+
+const EXP_PARAMS: ExpParams = ExpParams::new(EXP_PRECISION_DECIMALS);
+
+/// Largest exponent (scaled) exp_scaled accepts before returning Overflow.
+/// Cost and price only exponentiate the gap between the sides,
+/// -|q_yes - q_no| / b, so it is the gap, not either quantity, that this
+/// bounds as a multiple of b.
+///
+/// This is also the max safe gap ratio, about 45: buys that would widen the
+/// gap past it stop with TradeTooLarge, and beyond it e^(-gap/b) bottoms out
+/// at one unit, so calculate_price clamps larger gaps to it.
+const EXP_INPUT_LIMIT: i128 = EXP_PARAMS.input_limit;
+
+/// Largest quantity `cost` accepts. It scales each quantity by SCALE_FACTOR
+/// before dividing by b, so beyond this the multiply overflows i128 however
+/// large b is.
+const MAX_COST_QUANTITY: i128 = i128::MAX / SCALE_FACTOR;
+
+/// Scaled exp function using range reduction.
 /// Input and output are scaled by SCALE_FACTOR.
-/// For numerical stability, we limit the input range.
+///
+/// Writes x = k*ln(2) + r with |r| <= ln(2)/2, sums the Taylor series for
+/// e^r at the internal precision, then shifts by 2^k. Never returns 0, so
+/// results are safe to divide by.
 fn exp_scaled(x: i128) -> Result<i128, MarketError> {
+    exp_with(x, &EXP_PARAMS)
+}
+
+fn exp_with(x: i128, params: &ExpParams) -> Result<i128, MarketError> {
     // For very negative x, return smallest positive value (avoids division by zero)
-    // e^(-20) ≈ 2e-9, which is effectively zero but we return 1 to prevent 0/x issues
-    if x < -20 * SCALE_FACTOR {
+    if x < -params.input_limit {
         return Ok(1);
     }
     // For very large x, cap to prevent overflow
-    if x > 20 * SCALE_FACTOR {
+    if x > params.input_limit {
         return Err(MarketError::Overflow);
     }
 
-    // Taylor series: e^x = sum(x^n / n!) for n = 0 to infinity
-    let mut result: i128 = SCALE_FACTOR; // 1.0 scaled
-    let mut term: i128 = SCALE_FACTOR; // Current term (x^n / n!)
+    let x_precise = x
+        .checked_mul(params.precision / SCALE_FACTOR)
+        .ok_or(MarketError::Overflow)?;
+
+    // k = round(x / ln(2)), r = x - k * ln(2)
+    let half_ln2 = params.ln2 / 2;
+    let k = if x_precise >= 0 {
+        (x_precise + half_ln2) / params.ln2
+    } else {
+        (x_precise - half_ln2) / params.ln2
+    };
+    let r = x_precise - k * params.ln2;
 
-    for n in 1..=EXP_ITERATIONS {
-        // term = term * x / (n * SCALE_FACTOR)
-        term = term.checked_mul(x).ok_or(MarketError::Overflow)?;
+    // Taylor series: e^r = sum(r^n / n!)
+    let mut result: i128 = params.precision; // 1.0
+    let mut term: i128 = params.precision;
+    for n in 1..=params.iterations {
+        // term = term * r / (n * precision)
         term = term
-            .checked_div(n as i128 * SCALE_FACTOR)
+            .checked_mul(r)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(n as i128 * params.precision)
             .ok_or(MarketError::Overflow)?;
-
-        result = result.checked_add(term).ok_or(MarketError::Overflow)?;
-
-        // Early termination if term becomes negligible
-        if term.abs() < 1 {
+        if term == 0 {
             break;
         }
+        result = result.checked_add(term).ok_or(MarketError::Overflow)?;
     }
 
-    Ok(result.max(0))
+    // Multiply by 2^k and drop back to SCALE_FACTOR precision
+    let shifted = if k >= 0 {
+        result
+            .checked_mul(1i128 << k)
+            .ok_or(MarketError::Overflow)?
+    } else {
+        result >> (-k)
+    };
+    let scaled = shifted / (params.precision / SCALE_FACTOR);
+
+    Ok(scaled.max(1))
 }
 
-/// Natural logarithm using Taylor series expansion for ln(1+y).
+/// Internal precision of ln_scaled: 10^18, the same as LN2_PRECISE.
+const LN_PRECISION: i128 = 10i128.pow(MAX_EXP_PRECISION_DECIMALS);
+
+/// Natural logarithm via ln(m) = 2 * atanh((m-1)/(m+1)) after range reduction.
 /// Input and output are scaled by SCALE_FACTOR.
 /// Returns Overflow error if x <= 0.
 fn ln_scaled(x: i128) -> Result<i128, MarketError> {
@@ -68,63 +171,58 @@ fn ln_scaled(x: i128) -> Result<i128, MarketError> {
         return Ok(0);
     }
 
-    // Use the identity: ln(x) = 2 * atanh((x-1)/(x+1))
-    // atanh(y) = y + y³/3 + y⁵/5 + ...
-    //
-    // For better convergence, normalize x to [1, 2) range:
-    // ln(x * 2^n) = ln(x) + n * ln(2)
-
+    // Normalize to m in [1, 2) with x = m * 2^n, so ln(x) = ln(m) + n * ln(2).
+    // Halving composes exactly under integer division, so n is exact even
+    // though the halved value is not kept.
     let mut normalized = x;
-    let mut n: i128 = 0;
-
-    // Scale down to [SCALE_FACTOR, 2*SCALE_FACTOR)
+    let mut n: i32 = 0;
     while normalized >= 2 * SCALE_FACTOR {
-        normalized = normalized.checked_div(2).ok_or(MarketError::Overflow)?;
+        normalized /= 2;
         n += 1;
     }
-
-    // Scale up if less than 1
-    while normalized < SCALE_FACTOR && normalized > 0 {
-        normalized = normalized.checked_mul(2).ok_or(MarketError::Overflow)?;
+    while normalized < SCALE_FACTOR {
+        normalized *= 2;
         n -= 1;
     }
 
-    // Now normalized is in [SCALE_FACTOR, 2*SCALE_FACTOR)
-    // Compute ln(normalized) using series for ln(1+y) where y = (normalized - SCALE_FACTOR) / SCALE_FACTOR
-
-    // y = (normalized - SCALE_FACTOR) scaled
-    let y_num = normalized - SCALE_FACTOR;
-
-    // ln(1+y) = y - y²/2 + y³/3 - y⁴/4 + ...
-    let mut result: i128 = 0;
-    let mut y_power = y_num; // y^1 * SCALE_FACTOR
-    let mut sign: i128 = 1;
-
-    for k in 1..=30 {
-        let term = sign * y_power / k;
-        result = result.checked_add(term).ok_or(MarketError::Overflow)?;
-
-        // y_power = y_power * y / SCALE_FACTOR
-        y_power = y_power
-            .checked_mul(y_num)
-            .ok_or(MarketError::Overflow)?
-            .checked_div(SCALE_FACTOR)
-            .ok_or(MarketError::Overflow)?;
-
-        sign = -sign;
+    // Rebuild m from x at LN_PRECISION rather than from the truncated
+    // halvings. For large x, drop low bits first so x * ratio stays in i128;
+    // 2^60 leaves 18 significant digits.
+    let ratio = LN_PRECISION / SCALE_FACTOR;
+    let m = if n >= 0 {
+        let pre_shift = (n - 60).max(0);
+        ((x >> pre_shift)
+            .checked_mul(ratio)
+            .ok_or(MarketError::Overflow)?)
+            >> (n - pre_shift)
+    } else {
+        x.checked_mul(ratio).ok_or(MarketError::Overflow)? << (-n)
+    };
 
-        if y_power.abs() < 1 {
-            break;
-        }
+    // z = (m-1)/(m+1) lies in [0, 1/3), so each atanh term
+    // z^(2k+1)/(2k+1) is at least 9x smaller than the last and the series
+    // reaches one internal unit in about 20 terms.
+    let z = (m - LN_PRECISION) * LN_PRECISION / (m + LN_PRECISION);
+    let z_squared = z * z / LN_PRECISION;
+
+    let mut sum: i128 = 0;
+    let mut z_power = z;
+    let mut k: i128 = 1;
+    while z_power > 0 {
+        sum += z_power / k;
+        z_power = z_power * z_squared / LN_PRECISION;
+        k += 2;
     }
 
-    // Add n * ln(2)
-    let adjustment = n.checked_mul(LN2_SCALED).ok_or(MarketError::Overflow)?;
-    result = result
-        .checked_add(adjustment)
-        .ok_or(MarketError::Overflow)?;
+    let result = 2 * sum + n as i128 * LN2_PRECISE;
 
-    Ok(result)
+    // Round to nearest on the way back to SCALE_FACTOR
+    let half = ratio / 2;
+    Ok(if result >= 0 {
+        (result + half) / ratio
+    } else {
+        (result - half) / ratio
+    })
 }
 
 /// Calculate the LMSR cost function: C(q) = b * ln(e^(qYes/b) + e^(qNo/b))
@@ -133,6 +231,10 @@ pub fn cost(q_yes: i128, q_no: i128, b: i128) -> Result<i128, MarketError> {
     if b <= 0 {
         return Err(MarketError::InvalidLiquidity);
     }
+    let quantity_range = -MAX_COST_QUANTITY..=MAX_COST_QUANTITY;
+    if !quantity_range.contains(&q_yes) || !quantity_range.contains(&q_no) {
+        return Err(MarketError::Overflow);
+    }
 
     // Calculate qYes/b and qNo/b (result still scaled)
     let q_yes_over_b = q_yes
@@ -178,32 +280,129 @@ pub fn calculate_buy_cost(
     amount: i128,
     outcome: u32,
     b: i128,
+) -> Result<i128, MarketError> {
+    calculate_buy_cost_from(cost(q_yes, q_no, b)?, q_yes, q_no, amount, outcome, b)
+}
+
+/// `calculate_buy_cost` with C(q_yes, q_no) supplied by the caller, saving
+/// one cost evaluation when it is already known.
+pub fn calculate_buy_cost_from(
+    cost_before: i128,
+    q_yes: i128,
+    q_no: i128,
+    amount: i128,
+    outcome: u32,
+    b: i128,
 ) -> Result<i128, MarketError> {
     if amount <= 0 {
         return Err(MarketError::InvalidAmount);
     }
+    if b <= 0 {
+        return Err(MarketError::InvalidLiquidity);
+    }
 
-    let cost_before = cost(q_yes, q_no, b)?;
-
-    let cost_after = match outcome {
-        0 => cost(
-            q_yes.checked_add(amount).ok_or(MarketError::Overflow)?,
+    // Reject trades that would push the gap between the sides past what
+    // exp_scaled supports up front, rather than letting prices saturate.
+    // A balanced market can grow to any q/b
+    let (q_yes_after, q_no_after) = match outcome {
+        0 => (
+            q_yes
+                .checked_add(amount)
+                .ok_or(MarketError::TradeTooLarge)?,
             q_no,
-            b,
-        )?,
-        1 => cost(
+        ),
+        1 => (
             q_yes,
-            q_no.checked_add(amount).ok_or(MarketError::Overflow)?,
-            b,
-        )?,
+            q_no.checked_add(amount).ok_or(MarketError::TradeTooLarge)?,
+        ),
         _ => return Err(MarketError::InvalidOutcome),
     };
+    let gap = q_yes_after
+        .checked_sub(q_no_after)
+        .ok_or(MarketError::TradeTooLarge)?;
+    check_exp_input(gap.abs(), b)?;
+
+    let cost_after = cost(q_yes_after, q_no_after, b)?;
 
     cost_after
         .checked_sub(cost_before)
         .ok_or(MarketError::Overflow)
 }
 
+/// Verify that `gap / b` (scaled) stays within the exp_scaled input range.
+/// Returns TradeTooLarge otherwise.
+fn check_exp_input(gap: i128, b: i128) -> Result<(), MarketError> {
+    let gap_over_b = gap
+        .checked_mul(SCALE_FACTOR)
+        .ok_or(MarketError::TradeTooLarge)?
+        .checked_div(b)
+        .ok_or(MarketError::TradeTooLarge)?;
+    if gap_over_b > EXP_INPUT_LIMIT {
+        return Err(MarketError::TradeTooLarge);
+    }
+    Ok(())
+}
+
+/// Iteration cap for `bounded_search`.
+/// A non-negative i128 range narrows to one unit within 127 halvings, so the
+/// cap is never the limiting factor; it only bounds gas if a caller passes a
+/// malformed range.
+pub const SEARCH_MAX_ITERATIONS: u32 = 128;
+
+/// Binary search for the largest value in `[lo, hi)` accepted by `accept`.
+///
+/// `accept` must be monotone (true up to some boundary, false after it) and
+/// `lo` must be accepted. Stops once the bracket is one unit wide or after
+/// `SEARCH_MAX_ITERATIONS` steps, and always returns the accepted end of the
+/// bracket, so the result never overshoots the boundary. Tolerance: exact
+/// (within one unit) for any range `0 <= lo < hi`.
+pub fn bounded_search<F>(mut lo: i128, mut hi: i128, mut accept: F) -> Result<i128, MarketError>
+where
+    F: FnMut(i128) -> Result<bool, MarketError>,
+{
+    for _ in 0..SEARCH_MAX_ITERATIONS {
+        if hi - lo <= 1 {
+            break;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if accept(mid)? {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Ok(lo)
+}
+
+/// Find the largest amount of `outcome` tokens whose buy cost is at most `budget`.
+/// Returns 0 if not even one unit is affordable.
+pub fn calculate_amount_for_budget(
+    q_yes: i128,
+    q_no: i128,
+    budget: i128,
+    outcome: u32,
+    b: i128,
+) -> Result<i128, MarketError> {
+    if budget <= 0 {
+        return Err(MarketError::InvalidAmount);
+    }
+
+    // Every token costs at least the current price, so more than
+    // budget / price tokens can never be affordable
+    let price = calculate_price(q_yes, q_no, outcome, b)?.max(1);
+    let hi: i128 = budget.saturating_mul(SCALE_FACTOR) / price + 1;
+
+    let cost_before = cost(q_yes, q_no, b)?;
+    bounded_search(0, hi, |amount| {
+        match calculate_buy_cost_from(cost_before, q_yes, q_no, amount, outcome, b) {
+            Ok(cost) => Ok(cost <= budget),
+            Err(MarketError::TradeTooLarge) | Err(MarketError::Overflow) => Ok(false),
+            Err(e) => Err(e),
+        }
+    })
+}
+
 /// Calculate the return from selling `amount` of `outcome` tokens.
 /// Returns the collateral received (scaled by SCALE_FACTOR).
 pub fn calculate_sell_return(
@@ -212,13 +411,24 @@ pub fn calculate_sell_return(
     amount: i128,
     outcome: u32,
     b: i128,
+) -> Result<i128, MarketError> {
+    calculate_sell_return_from(cost(q_yes, q_no, b)?, q_yes, q_no, amount, outcome, b)
+}
+
+/// `calculate_sell_return` with C(q_yes, q_no) supplied by the caller, saving
+/// one cost evaluation when it is already known.
+pub fn calculate_sell_return_from(
+    cost_before: i128,
+    q_yes: i128,
+    q_no: i128,
+    amount: i128,
+    outcome: u32,
+    b: i128,
 ) -> Result<i128, MarketError> {
     if amount <= 0 {
         return Err(MarketError::InvalidAmount);
     }
 
-    let cost_before = cost(q_yes, q_no, b)?;
-
     let cost_after = match outcome {
         0 => {
             if q_yes < amount {
@@ -250,6 +460,10 @@ pub fn calculate_sell_return(
 
 /// Calculate the current price (probability) of an outcome.
 /// Returns price scaled by SCALE_FACTOR (0 to SCALE_FACTOR represents 0 to 1).
+///
+/// Uses the logistic form P = 1 / (1 + e^((q_other - q_outcome)/b)) and only
+/// ever exponentiates a non-positive number, so no imbalance overflows: a
+/// dominant outcome approaches but never exceeds SCALE_FACTOR.
 pub fn calculate_price(
     q_yes: i128,
     q_no: i128,
@@ -259,53 +473,121 @@ pub fn calculate_price(
     if b <= 0 {
         return Err(MarketError::InvalidLiquidity);
     }
+    let (q_outcome, q_other) = match outcome {
+        0 => (q_yes, q_no),
+        1 => (q_no, q_yes),
+        _ => return Err(MarketError::InvalidOutcome),
+    };
 
-    // P(yes) = e^(qYes/b) / (e^(qYes/b) + e^(qNo/b))
-    let q_yes_over_b = q_yes
-        .checked_mul(SCALE_FACTOR)
-        .ok_or(MarketError::Overflow)?
-        .checked_div(b)
+    // d = (q_other - q_outcome) / b, scaled. Past EXP_INPUT_LIMIT the price
+    // no longer moves, so clamp there before scaling a gap that may overflow.
+    let gap = q_other
+        .checked_sub(q_outcome)
         .ok_or(MarketError::Overflow)?;
-    let q_no_over_b = q_no
-        .checked_mul(SCALE_FACTOR)
-        .ok_or(MarketError::Overflow)?
-        .checked_div(b)
+    let d = if gap.unsigned_abs() / b.unsigned_abs() > (EXP_INPUT_LIMIT / SCALE_FACTOR) as u128 {
+        (EXP_INPUT_LIMIT + 1) * gap.signum()
+    } else {
+        gap.checked_mul(SCALE_FACTOR)
+            .ok_or(MarketError::Overflow)?
+            .checked_div(b)
+            .ok_or(MarketError::Overflow)?
+    };
+
+    // e^(-|d|) is in [1, SCALE_FACTOR], so neither product below can overflow
+    let exp_neg = exp_scaled(-d.abs())?;
+    let numerator = if d <= 0 { SCALE_FACTOR } else { exp_neg };
+    let sum = SCALE_FACTOR
+        .checked_add(exp_neg)
         .ok_or(MarketError::Overflow)?;
 
-    let exp_yes = exp_scaled(q_yes_over_b)?;
-    let exp_no = exp_scaled(q_no_over_b)?;
-    let sum = exp_yes.checked_add(exp_no).ok_or(MarketError::Overflow)?;
+    numerator
+        .checked_mul(SCALE_FACTOR)
+        .ok_or(MarketError::Overflow)?
+        .checked_div(sum)
+        .ok_or(MarketError::Overflow)
+}
 
-    if sum == 0 {
-        return Err(MarketError::Overflow);
+/// Calculate how many `outcome` tokens must be bought to lift its price to
+/// `target_price` (scaled, strictly between 0 and SCALE_FACTOR).
+///
+/// Inverts the price formula: the outcome's price is p exactly when
+/// q_outcome - q_other = b * ln(p / (1 - p)). Returns 0 if the price is
+/// already at or above the target.
+pub fn calculate_amount_to_price(
+    q_yes: i128,
+    q_no: i128,
+    target_price: i128,
+    outcome: u32,
+    b: i128,
+) -> Result<i128, MarketError> {
+    if b <= 0 {
+        return Err(MarketError::InvalidLiquidity);
     }
-
-    match outcome {
-        0 => Ok(exp_yes
-            .checked_mul(SCALE_FACTOR)
-            .ok_or(MarketError::Overflow)?
-            .checked_div(sum)
-            .ok_or(MarketError::Overflow)?),
-        1 => Ok(exp_no
-            .checked_mul(SCALE_FACTOR)
-            .ok_or(MarketError::Overflow)?
-            .checked_div(sum)
-            .ok_or(MarketError::Overflow)?),
-        _ => Err(MarketError::InvalidOutcome),
+    if target_price <= 0 || target_price >= SCALE_FACTOR {
+        return Err(MarketError::InvalidAmount);
     }
+    let (q_outcome, q_other) = match outcome {
+        0 => (q_yes, q_no),
+        1 => (q_no, q_yes),
+        _ => return Err(MarketError::InvalidOutcome),
+    };
+
+    let odds = target_price
+        .checked_mul(SCALE_FACTOR)
+        .ok_or(MarketError::Overflow)?
+        .checked_div(SCALE_FACTOR - target_price)
+        .ok_or(MarketError::Overflow)?;
+    let gap = b
+        .checked_mul(ln_scaled(odds)?)
+        .ok_or(MarketError::Overflow)?
+        .checked_div(SCALE_FACTOR)
+        .ok_or(MarketError::Overflow)?;
+
+    let needed = q_other
+        .checked_add(gap)
+        .and_then(|q| q.checked_sub(q_outcome))
+        .ok_or(MarketError::Overflow)?;
+    Ok(needed.max(0))
 }
 
-/// Calculate initial liquidity required: b * ln(2)
+/// Calculate initial liquidity required: b * ln(n) for n = OUTCOME_COUNT,
+/// the market maker's worst-case loss. b * ln(2) for a binary market.
 pub fn initial_liquidity(b: i128) -> Result<i128, MarketError> {
     if b <= 0 {
         return Err(MarketError::InvalidLiquidity);
     }
-    b.checked_mul(LN2_SCALED)
+    let ln_outcomes = ln_scaled(OUTCOME_COUNT as i128 * SCALE_FACTOR)?;
+    b.checked_mul(ln_outcomes)
         .ok_or(MarketError::Overflow)?
         .checked_div(SCALE_FACTOR)
         .ok_or(MarketError::Overflow)
 }
 
+/// Test-only access to the pricing internals, so contract tests can assert
+/// intermediate values instead of just final prices.
+#[cfg(test)]
+pub mod debug {
+    use crate::error::MarketError;
+
+    /// e^(x / SCALE_FACTOR), scaled by SCALE_FACTOR.
+    /// Inputs below -EXP_INPUT_LIMIT return 1; inputs above it return Overflow.
+    pub fn exp_scaled(x: i128) -> Result<i128, MarketError> {
+        super::exp_scaled(x)
+    }
+
+    /// ln(x / SCALE_FACTOR), scaled by SCALE_FACTOR.
+    /// Returns Overflow for x <= 0.
+    pub fn ln_scaled(x: i128) -> Result<i128, MarketError> {
+        super::ln_scaled(x)
+    }
+
+    /// LMSR cost C(q) = b * ln(e^(qYes/b) + e^(qNo/b)), scaled by SCALE_FACTOR.
+    /// Returns InvalidLiquidity for b <= 0.
+    pub fn cost(q_yes: i128, q_no: i128, b: i128) -> Result<i128, MarketError> {
+        super::cost(q_yes, q_no, b)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -315,9 +597,44 @@ mod tests {
         // e^0 = 1
         assert_eq!(exp_scaled(0).unwrap(), SCALE_FACTOR);
 
-        // e^1 ≈ 2.718
+        // e^1 ≈ 2.7182818
         let e1 = exp_scaled(SCALE_FACTOR).unwrap();
-        assert!(e1 > 27_000_000 && e1 < 28_000_000, "e^1 = {}", e1);
+        assert!((e1 - 27_182_818).abs() <= 1, "e^1 = {}", e1);
+
+        // e^-1 ≈ 0.3678794
+        let e_neg1 = exp_scaled(-SCALE_FACTOR).unwrap();
+        assert!((e_neg1 - 3_678_794).abs() <= 1, "e^-1 = {}", e_neg1);
+    }
+
+    #[test]
+    fn test_exp_scaled_e5() {
+        // e^5 ≈ 148.4131591
+        let e5 = exp_scaled(5 * SCALE_FACTOR).unwrap();
+        assert!((e5 - 1_484_131_591).abs() <= 10, "e^5 = {}", e5);
+    }
+
+    #[test]
+    fn test_exp_scaled_e15() {
+        // e^15 ≈ 3269017.3724721
+        let e15 = exp_scaled(15 * SCALE_FACTOR).unwrap();
+        let expected: i128 = 32_690_173_724_721;
+        assert!(
+            (e15 - expected).abs() < expected / 1_000_000,
+            "e^15 = {}",
+            e15
+        );
+    }
+
+    #[test]
+    fn test_exp_scaled_beyond_old_cap() {
+        // e^25 ≈ 7.2004899e10, which the old ±20 cap rejected
+        let e25 = exp_scaled(25 * SCALE_FACTOR).unwrap();
+        let expected: i128 = 720_048_993_373_858_725;
+        assert!(
+            (e25 - expected).abs() < expected / 1_000_000,
+            "e^25 = {}",
+            e25
+        );
     }
 
     #[test]
@@ -330,6 +647,29 @@ mod tests {
         assert!(ln_e > 9_900_000 && ln_e < 10_100_000, "ln(e) = {}", ln_e);
     }
 
+    #[test]
+    fn test_ln_scaled_reference_values() {
+        // (x, ln(x)), both scaled by SCALE_FACTOR and rounded to 7 decimals
+        let cases = [
+            (15_000_000, 4_054_651),   // ln(1.5)  = 0.4054651081
+            (19_000_000, 6_418_539),   // ln(1.9)  = 0.6418538862
+            (100_000_000, 23_025_851), // ln(10)   = 2.3025850930
+            (20_000_000, 6_931_472),   // ln(2)    = 0.6931471806
+            (5_000_000, -6_931_472),   // ln(0.5)  = -0.6931471806
+            (1_234, -90_000_794),      // ln(0.0001234) = -9.0000794
+        ];
+        for (x, expected) in cases {
+            let ln = ln_scaled(x).unwrap();
+            assert!(
+                (ln - expected).abs() <= 1,
+                "ln({}) = {}, expected {}",
+                x,
+                ln,
+                expected
+            );
+        }
+    }
+
     #[test]
     fn test_price_at_equilibrium() {
         let b = 100 * SCALE_FACTOR;
@@ -372,17 +712,20 @@ mod tests {
 
     #[test]
     fn test_exp_scaled_overflow_positive() {
-        // exp(x) for x > 20 * SCALE_FACTOR should return Overflow error
-        let result = exp_scaled(21 * SCALE_FACTOR);
+        // exp(x) past the input limit should return Overflow error
+        let result = exp_scaled(EXP_INPUT_LIMIT + 1);
         assert!(matches!(result, Err(MarketError::Overflow)));
     }
 
     #[test]
     fn test_exp_scaled_very_negative_returns_one() {
-        // exp(x) for x < -20 * SCALE_FACTOR should return 1 (smallest positive value)
+        // exp(x) below -EXP_INPUT_LIMIT should return 1 (smallest positive value)
         // to prevent division by zero in price calculations
-        let result = exp_scaled(-21 * SCALE_FACTOR).unwrap();
+        let result = exp_scaled(-EXP_INPUT_LIMIT - 1).unwrap();
         assert_eq!(result, 1);
+
+        // Values that round to zero inside the range are floored at 1 too
+        assert_eq!(exp_scaled(-30 * SCALE_FACTOR).unwrap(), 1);
     }
 
     #[test]
@@ -446,6 +789,30 @@ mod tests {
         assert!(matches!(result, Err(MarketError::InvalidAmount)));
     }
 
+    #[test]
+    fn test_buy_cost_trade_too_large() {
+        let b = 100 * SCALE_FACTOR;
+
+        // q at the exp input limit, in token units for this b
+        let limit_q = EXP_INPUT_LIMIT * b / SCALE_FACTOR;
+
+        // One more token pushes the gap past the limit
+        let result = calculate_buy_cost(0, 0, limit_q + SCALE_FACTOR, 0, b);
+        assert!(matches!(result, Err(MarketError::TradeTooLarge)));
+
+        // Existing quantity counts towards the limit
+        let result = calculate_buy_cost(0, limit_q - 5 * b, 6 * b, 1, b);
+        assert!(matches!(result, Err(MarketError::TradeTooLarge)));
+
+        // Only the gap matters: a balanced market far past q/b = 45 still trades
+        let q = 2 * limit_q;
+        let result = calculate_buy_cost(q, q, 6 * b, 1, b).unwrap();
+        assert!(result > 0 && result < 6 * b, "cost = {}", result);
+
+        // Exactly at the limit is still allowed
+        assert!(calculate_buy_cost(0, 0, limit_q, 0, b).is_ok());
+    }
+
     #[test]
     fn test_sell_insufficient_global_balance() {
         let b = 100 * SCALE_FACTOR;
@@ -460,4 +827,178 @@ mod tests {
             calculate_sell_return(10 * SCALE_FACTOR, 5 * SCALE_FACTOR, 10 * SCALE_FACTOR, 1, b);
         assert!(matches!(result, Err(MarketError::InsufficientBalance)));
     }
+
+    // --- Skewed sell tests ---
+
+    #[test]
+    fn test_sell_minority_at_heavy_skew() {
+        let b = 100 * SCALE_FACTOR;
+        let q_yes = 1500 * SCALE_FACTOR;
+        let q_no = 10 * SCALE_FACTOR;
+        let amount = 10 * SCALE_FACTOR;
+
+        // NO is priced near zero, so selling it returns almost nothing,
+        // but never a negative amount or more than the tokens' face value
+        let ret = calculate_sell_return(q_yes, q_no, amount, 1, b).unwrap();
+        assert!(ret >= 0, "sell return = {}", ret);
+        assert!(ret < SCALE_FACTOR / 100, "sell return = {}", ret);
+
+        let buy = calculate_buy_cost(q_yes, 0, amount, 1, b).unwrap();
+        assert!((ret - buy).abs() <= 10, "buy {} vs sell {}", buy, ret);
+    }
+
+    // --- Exp precision tests ---
+
+    #[test]
+    fn test_exp_params_derivation() {
+        // Default precision: a handful of terms, range well beyond the old ±20
+        let params = ExpParams::new(EXP_PRECISION_DECIMALS);
+        assert!(params.iterations <= 20, "{}", params.iterations);
+        assert_eq!(params.input_limit, 65 * LN2_SCALED);
+
+        // Lower precision needs fewer terms
+        let low = ExpParams::new(COLLATERAL_DECIMALS);
+        assert!(low.iterations < params.iterations);
+    }
+
+    #[test]
+    fn test_exp_highest_precision_tighter_at_equilibrium() {
+        let high = ExpParams::new(MAX_EXP_PRECISION_DECIMALS);
+        let low = ExpParams::new(COLLATERAL_DECIMALS);
+
+        // Near equilibrium q/b is close to 0; reference values of e^x scaled
+        let cases = [
+            (SCALE_FACTOR / 1000, 10_010_005),   // e^0.001
+            (SCALE_FACTOR / 10, 11_051_709),     // e^0.1
+            (3 * SCALE_FACTOR / 10, 13_498_588), // e^0.3
+            (7 * SCALE_FACTOR / 10, 20_137_527), // e^0.7
+        ];
+        let mut high_err = 0;
+        let mut low_err = 0;
+        for (x, expected) in cases {
+            high_err += (exp_with(x, &high).unwrap() - expected).abs();
+            low_err += (exp_with(x, &low).unwrap() - expected).abs();
+        }
+        assert!(
+            high_err <= cases.len() as i128,
+            "high precision error {}",
+            high_err
+        );
+        assert!(high_err < low_err, "high {} vs low {}", high_err, low_err);
+
+        // Equal quantities price at exactly one half
+        let b = 100 * SCALE_FACTOR;
+        let q = 37 * SCALE_FACTOR;
+        assert_eq!(calculate_price(q, q, 0, b).unwrap(), SCALE_FACTOR / 2);
+    }
+
+    #[test]
+    fn test_amount_for_budget_is_largest_affordable() {
+        let b = 100 * SCALE_FACTOR;
+        let q_yes = 20 * SCALE_FACTOR;
+        let budget = 50 * SCALE_FACTOR;
+
+        for outcome in [0, 1] {
+            let amount = calculate_amount_for_budget(q_yes, 0, budget, outcome, b).unwrap();
+            assert!(calculate_buy_cost(q_yes, 0, amount, outcome, b).unwrap() <= budget);
+            assert!(calculate_buy_cost(q_yes, 0, amount + 1, outcome, b).unwrap() > budget);
+        }
+    }
+
+    #[test]
+    fn test_amount_for_budget_rejects_zero_budget() {
+        assert_eq!(
+            calculate_amount_for_budget(0, 0, 0, 0, 100 * SCALE_FACTOR),
+            Err(MarketError::InvalidAmount)
+        );
+    }
+
+    #[test]
+    fn test_bounded_search_converges_within_cap() {
+        // Widest possible range still narrows to the exact boundary
+        let boundary = 123_456_789;
+        let mut steps = 0;
+        let found = bounded_search(0, i128::MAX, |x| {
+            steps += 1;
+            Ok(x <= boundary)
+        })
+        .unwrap();
+        assert_eq!(found, boundary);
+        assert!(steps <= SEARCH_MAX_ITERATIONS);
+
+        // Nothing above lo accepted: the conservative bound is lo itself
+        assert_eq!(bounded_search(5, 1_000, |_| Ok(false)).unwrap(), 5);
+
+        // Errors from the predicate propagate
+        assert_eq!(
+            bounded_search(0, 10, |_| Err(MarketError::Overflow)),
+            Err(MarketError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_amount_to_price_reaches_target() {
+        let b = 100 * SCALE_FACTOR;
+        let target = 9_900_000; // 0.99
+
+        for (q_yes, q_no) in [(0, 0), (30 * SCALE_FACTOR, 0), (0, 45 * SCALE_FACTOR)] {
+            let amount = calculate_amount_to_price(q_yes, q_no, target, 0, b).unwrap();
+            let price = calculate_price(q_yes + amount, q_no, 0, b).unwrap();
+            assert!(
+                (price - target).abs() <= 10,
+                "price {} for {:?}",
+                price,
+                (q_yes, q_no)
+            );
+        }
+
+        // Already past the target: nothing to buy
+        assert_eq!(
+            calculate_amount_to_price(600 * SCALE_FACTOR, 0, target, 0, b).unwrap(),
+            0
+        );
+        assert_eq!(
+            calculate_amount_to_price(0, 0, SCALE_FACTOR, 0, b),
+            Err(MarketError::InvalidAmount)
+        );
+    }
+
+    #[test]
+    fn test_tiny_b_large_q_fails_cleanly() {
+        let b = 1;
+        let q = i128::MAX / 2;
+
+        // q * SCALE_FACTOR would overflow, so cost errors before the multiply
+        assert_eq!(cost(q, 0, b), Err(MarketError::Overflow));
+        assert_eq!(cost(0, -q, b), Err(MarketError::Overflow));
+        assert_eq!(
+            calculate_buy_cost(0, 0, q, 0, b),
+            Err(MarketError::TradeTooLarge)
+        );
+
+        // Prices clamp instead: the dominant side is priced at ~1
+        let price_yes = calculate_price(q, 0, 0, b).unwrap();
+        assert!(price_yes >= SCALE_FACTOR - 1);
+        assert!(calculate_price(q, 0, 1, b).unwrap() <= 1);
+        assert_eq!(calculate_price(0, q, 1, b).unwrap(), price_yes);
+    }
+
+    #[test]
+    fn test_price_stable_when_one_outcome_dominates() {
+        let b = 100 * SCALE_FACTOR;
+
+        // Far past where e^(q/b) itself would overflow
+        let price_yes = calculate_price(50 * b, 0, 0, b).unwrap();
+        let price_no = calculate_price(50 * b, 0, 1, b).unwrap();
+        assert!(price_yes < SCALE_FACTOR);
+        assert!(price_yes >= SCALE_FACTOR - 1);
+        assert!(price_no <= 1);
+
+        // Symmetric in the other direction
+        assert_eq!(calculate_price(0, 50 * b, 1, b).unwrap(), price_yes);
+
+        // Still accurate in the normal range: 1 / (1 + e^-1) ≈ 0.7310586
+        let price = calculate_price(b, 0, 0, b).unwrap();
+        assert!((price - 7_310_586).abs() <= 2, "price {}", price);
+    }
 }
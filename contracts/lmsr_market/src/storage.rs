@@ -27,11 +27,117 @@ pub enum DataKey {
     MetadataHash,
     /// User balance for outcome tokens: UserBalance(user, outcome)
     UserBalance(Address, u32),
+    /// Cumulative YES price weighted by ledgers elapsed (TWAP accumulator)
+    PriceCumulative,
+    /// Ledger sequence of the last accumulator update
+    PriceLastLedger,
+    /// Most recent TWAP checkpoint: (ledger, cumulative)
+    TwapCheckpoint,
+    /// Checkpoint preceding TwapCheckpoint, marking the start of the averaging window
+    TwapPrevCheckpoint,
+    /// Time-weighted price of an outcome captured at resolution: SettlementPrice(outcome)
+    SettlementPrice(u32),
+    /// Factory that deployed this market (can pause it)
+    Factory,
+    /// Whether trading is paused by the factory
+    Paused,
+    /// Whether trading is paused by the oracle
+    OraclePaused,
+    /// Oracle-set rate for an accepted basket token: base units per token unit (scaled)
+    AcceptedTokenRate(Address),
+    /// Collateral paid for a user's current outcome tokens: UserCostBasis(user, outcome)
+    UserCostBasis(Address, u32),
+    /// User's cumulative realized profit and loss
+    UserRealizedPnl(Address),
+    /// URI prefix prepended to the metadata hash (e.g. "ipfs://")
+    MetadataBaseUri,
+    /// Whether the oracle cancelled the market
+    Cancelled,
+    /// Outcome price snapshotted at cancellation, used for refunds: RefundPrice(outcome)
+    RefundPrice(u32),
+    /// Protocol fee on each buy/sell in basis points, set by the factory
+    ProtocolFeeBps,
+    /// Address that receives the protocol fee
+    ProtocolFeeRecipient,
+    /// Claim fee on winnings in basis points
+    ClaimFeeBps,
+    /// Oracle proposed by the current oracle, waiting to accept
+    PendingOracle,
+    /// Number of addresses holding a positive balance of an outcome: HolderCount(outcome)
+    HolderCount(u32),
+    /// Ledgers after resolution during which winnings can be claimed
+    ClaimWindow,
+    /// Last ledger at which winnings can be claimed, set at resolution
+    ClaimDeadline,
+    /// Address allowed to resolve, if not the oracle
+    Resolver,
+    /// Ledgers a proposed resolution stays open to dispute
+    DisputeWindow,
+    /// Outcome proposed by `resolve`, awaiting finalization
+    ProposedOutcome,
+    /// First ledger at which the proposed outcome can be finalized
+    ResolveAt,
+    /// Protocol fees collected on buys and sells
+    TradeFeeRevenue,
+    /// Claim fees collected on winnings
+    ClaimFeeRevenue,
+    /// Ledger sequence at which the market was created
+    CreatedLedger,
+    /// Collateral traded across all buys and sells, before protocol fees
+    TotalVolume,
+    /// Number of buys and sells
+    TradeCount,
+    /// Cached LMSR cost C(q_yes, q_no) at the current quantities and b
+    LastCost,
+    /// Display labels for the outcomes, indexed by outcome
+    OutcomeLabels,
+    /// Smallest token amount a single buy or sell may move
+    MinTradeAmount,
+    /// Contract notified when the market resolves: ResolutionCallback(holder)
+    ResolutionCallback(Address),
+    /// Holders with a registered resolution callback, in registration order
+    CallbackHolders,
+    /// Whether resolving to an outcome with no tokens sold is rejected
+    RequireNonZeroWinner,
+    /// Reference to the source backing the resolution (e.g. an IPFS hash)
+    ResolutionEvidence,
+    /// Claim fees collected and not yet withdrawn, held apart from the residual pool
+    AccruedFees,
+}
+
+/// Storage keys added once `DataKey` reached the 50-variant limit of
+/// `#[contracttype]` enums. Stored the same way, so the two never collide
+/// as long as variant names stay distinct.
+#[derive(Clone)]
+#[contracttype]
+pub enum ExtDataKey {
+    /// Collateral deposited at initialization, kept apart from later liquidity
+    InitialFunding,
+    /// Decimals of the collateral token, read from it at initialization
+    CollateralDecimals,
+    /// Address receiving a share of each claim fee
+    RewardsAddress,
+    /// Share of each claim fee sent to RewardsAddress, in basis points
+    RewardsShareBps,
+    /// Number of buys
+    BuyCount,
+    /// Number of sells
+    SellCount,
+    /// Number of disputes raised against proposed resolutions
+    DisputeCount,
+    /// Whether a holder has already disputed: Disputed(holder)
+    Disputed(Address),
+    /// Length of the pre-resolution TWAP window in ledgers
+    TwapWindow,
+    /// Oracle-funded base collateral that settles basket buys
+    BasketFloat,
 }
 
 /// Outcome constants
 pub const OUTCOME_YES: u32 = 0;
 pub const OUTCOME_NO: u32 = 1;
+/// Number of outcomes in a binary market
+pub const OUTCOME_COUNT: u32 = 2;
 
 /// Check if an outcome value is valid (YES=0 or NO=1).
 #[inline]
@@ -39,20 +145,59 @@ pub fn is_valid_outcome(outcome: u32) -> bool {
     outcome == OUTCOME_YES || outcome == OUTCOME_NO
 }
 
+/// Decimal places of the collateral token (Stellar assets use 7).
+pub const COLLATERAL_DECIMALS: u32 = 7;
+
+/// Most decimals a collateral token may have. Converting an internal amount
+/// multiplies it by 10^(decimals - COLLATERAL_DECIMALS), so more would leave
+/// little headroom in i128.
+pub const MAX_COLLATERAL_DECIMALS: u32 = 18;
+
 /// Scale factor for fixed-point arithmetic.
 /// Uses 7 decimal places to match Stellar/Soroban native token precision,
 /// ensuring seamless conversion between contract amounts and on-chain balances.
-pub const SCALE_FACTOR: i128 = 10_000_000; // 10^7
+pub const SCALE_FACTOR: i128 = 10i128.pow(COLLATERAL_DECIMALS); // 10^7
+
+/// Scale of outcome token (share) amounts: one whole share is SHARE_SCALE units.
+/// Shares always use this scale; the collateral token's own scale comes from
+/// its `decimals()` and is reported by `get_units_info`.
+pub const SHARE_SCALE: i128 = SCALE_FACTOR;
 
 /// Natural log of 2 scaled (ln(2) * SCALE_FACTOR).
 /// ln(2) ≈ 0.6931472
 /// Used for initial liquidity calculation: b * ln(2).
 pub const LN2_SCALED: i128 = 6_931_472;
 
-/// Claim fee in basis points (1 bp = 0.01%).
-/// 200 bp = 2% fee on winnings.
-/// Fee stays in pool and goes to oracle via withdraw_remaining.
+/// Default claim fee in basis points (1 bp = 0.01%).
+/// 200 bp = 2% fee on winnings. Markets set their own fee via MarketConfig;
+/// this applies to markets deployed before the fee was configurable.
+/// Fee is held apart in the pool and goes to the oracle via withdraw_fees.
 pub const CLAIM_FEE_BPS: i128 = 200;
 
+/// Default length of the pre-resolution TWAP window in ledgers, for markets
+/// that don't set `twap_window_ledgers`.
+/// 720 ledgers ≈ 1 hour at ~5 seconds per ledger.
+pub const TWAP_WINDOW_LEDGERS: u32 = 720;
+
+/// Maximum length in bytes of the full metadata URI (base + hash).
+/// get_metadata_uri builds the string in a stack buffer of this size.
+pub const MAX_METADATA_URI_LEN: usize = 256;
+
+/// Price treated as certainty by `get_cost_to_certainty` (0.99 scaled).
+pub const CERTAINTY_PRICE: i128 = 9_900_000;
+
+/// How far below SCALE_FACTOR the two outcome prices may sum.
+/// Each price is rounded down, so the sum can fall short by one unit per outcome.
+pub const PRICE_SUM_TOLERANCE: i128 = 2;
+
+/// Maximum number of disputed proposals before a market stops accepting
+/// new ones and can only be cancelled. Bounds how long disputes can delay
+/// settlement to this many dispute windows.
+pub const MAX_DISPUTES: u32 = 3;
+
+/// Maximum number of resolution callbacks invoked by `resolve`.
+/// Bounds the extra cross-contract calls a resolution can trigger.
+pub const MAX_RESOLUTION_CALLBACKS: u32 = 10;
+
 /// Basis points denominator (100% = 10000 bp).
 pub const BPS_DENOMINATOR: i128 = 10_000;
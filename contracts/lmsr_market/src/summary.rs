@@ -0,0 +1,28 @@
+use soroban_sdk::{contracttype, Address};
+
+/// Snapshot of the key market views, returned by `get_summary`.
+///
+/// Lets dashboards fetch in one call what would otherwise take a call per
+/// getter. Amounts and prices are scaled by 10^7.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketSummary {
+    /// Current YES price, as `get_price(0)`
+    pub yes_price: i128,
+    /// Current NO price, as `get_price(1)`
+    pub no_price: i128,
+    /// Quantity of YES tokens sold
+    pub yes_sold: i128,
+    /// Quantity of NO tokens sold
+    pub no_sold: i128,
+    /// Collateral held in the pool
+    pub pool: i128,
+    /// Whether the market is resolved
+    pub resolved: bool,
+    /// Winning outcome once resolved, None before
+    pub winning_outcome: Option<u32>,
+    /// Current oracle
+    pub oracle: Address,
+    /// LMSR liquidity parameter b
+    pub liquidity_param: i128,
+}
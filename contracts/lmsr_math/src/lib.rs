@@ -0,0 +1,214 @@
+#![no_std]
+
+//! Stateless LMSR pricing for off-chain dry-runs.
+//!
+//! Compiles the market contract's `lmsr` module as-is, so simulated prices and
+//! costs match what a deployed market charges for the same quantities and b.
+
+#[path = "../../lmsr_market/src/error.rs"]
+mod error;
+// Only part of the shared modules is exposed here
+#[path = "../../lmsr_market/src/lmsr.rs"]
+#[allow(dead_code)]
+mod lmsr;
+#[path = "../../lmsr_market/src/storage.rs"]
+#[allow(dead_code)]
+mod storage;
+
+use error::MarketError;
+use soroban_sdk::{contract, contractimpl, Env};
+
+#[contract]
+pub struct LmsrMath;
+
+#[contractimpl]
+impl LmsrMath {
+    /// LMSR cost C(q) = b * ln(e^(qYes/b) + e^(qNo/b)).
+    pub fn cost(_env: Env, q_yes: i128, q_no: i128, b: i128) -> Result<i128, MarketError> {
+        lmsr::cost(q_yes, q_no, b)
+    }
+
+    /// Price of an outcome (0 to SCALE_FACTOR).
+    pub fn price(
+        _env: Env,
+        q_yes: i128,
+        q_no: i128,
+        outcome: u32,
+        b: i128,
+    ) -> Result<i128, MarketError> {
+        lmsr::calculate_price(q_yes, q_no, outcome, b)
+    }
+
+    /// Cost to buy `amount` of `outcome` tokens, before any protocol fee.
+    pub fn buy_cost(
+        _env: Env,
+        q_yes: i128,
+        q_no: i128,
+        amount: i128,
+        outcome: u32,
+        b: i128,
+    ) -> Result<i128, MarketError> {
+        lmsr::calculate_buy_cost(q_yes, q_no, amount, outcome, b)
+    }
+
+    /// Return from selling `amount` of `outcome` tokens, before any protocol fee.
+    pub fn sell_return(
+        _env: Env,
+        q_yes: i128,
+        q_no: i128,
+        amount: i128,
+        outcome: u32,
+        b: i128,
+    ) -> Result<i128, MarketError> {
+        lmsr::calculate_sell_return(q_yes, q_no, amount, outcome, b)
+    }
+
+    /// Minimum initial funding for a market with liquidity `b`: b * ln(2).
+    pub fn initial_liquidity(_env: Env, b: i128) -> Result<i128, MarketError> {
+        lmsr::initial_liquidity(b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use storage::SCALE_FACTOR;
+
+    fn setup() -> (Env, LmsrMathClient<'static>) {
+        let env = Env::default();
+        let contract_id = env.register(LmsrMath, ());
+        let client = LmsrMathClient::new(&env, &contract_id);
+        (env, client)
+    }
+
+    #[test]
+    fn test_price_at_equilibrium() {
+        let (_env, client) = setup();
+        let b = 100 * SCALE_FACTOR;
+
+        let price_yes = client.price(&0, &0, &0, &b);
+        let price_no = client.price(&0, &0, &1, &b);
+        assert!(
+            price_yes > 4_900_000 && price_yes < 5_100_000,
+            "price_yes = {}",
+            price_yes
+        );
+        assert!(
+            price_no > 4_900_000 && price_no < 5_100_000,
+            "price_no = {}",
+            price_no
+        );
+    }
+
+    #[test]
+    fn test_buy_cost_positive() {
+        let (_env, client) = setup();
+        let b = 100 * SCALE_FACTOR;
+
+        let cost = client.buy_cost(&0, &0, &(10 * SCALE_FACTOR), &0, &b);
+        assert!(cost > 0, "Buy cost should be positive");
+    }
+
+    #[test]
+    fn test_buy_cost_is_cost_difference() {
+        let (_env, client) = setup();
+        let b = 100 * SCALE_FACTOR;
+        let q_yes = 30 * SCALE_FACTOR;
+        let amount = 10 * SCALE_FACTOR;
+
+        let cost = client.buy_cost(&q_yes, &0, &amount, &0, &b);
+        assert_eq!(
+            cost,
+            client.cost(&(q_yes + amount), &0, &b) - client.cost(&q_yes, &0, &b)
+        );
+
+        // Selling the same tokens back returns what they cost
+        let ret = client.sell_return(&(q_yes + amount), &0, &amount, &0, &b);
+        assert_eq!(ret, cost);
+    }
+
+    #[test]
+    fn test_initial_liquidity() {
+        let (_env, client) = setup();
+        let b = 100 * SCALE_FACTOR;
+
+        // Should be approximately 100 * 0.693 = 69.3
+        let liquidity = client.initial_liquidity(&b);
+        assert!(
+            liquidity > 69 * SCALE_FACTOR && liquidity < 70 * SCALE_FACTOR,
+            "initial_liquidity = {}",
+            liquidity
+        );
+    }
+
+    #[test]
+    fn test_invalid_liquidity_param() {
+        let (_env, client) = setup();
+
+        assert_eq!(
+            client.try_initial_liquidity(&0),
+            Err(Ok(MarketError::InvalidLiquidity))
+        );
+        assert_eq!(
+            client.try_cost(&0, &0, &-100),
+            Err(Ok(MarketError::InvalidLiquidity))
+        );
+    }
+
+    #[test]
+    fn test_invalid_outcome() {
+        let (_env, client) = setup();
+        let b = 100 * SCALE_FACTOR;
+
+        assert_eq!(
+            client.try_buy_cost(&0, &0, &(10 * SCALE_FACTOR), &99, &b),
+            Err(Ok(MarketError::InvalidOutcome))
+        );
+        assert_eq!(
+            client.try_sell_return(
+                &(100 * SCALE_FACTOR),
+                &(100 * SCALE_FACTOR),
+                &(10 * SCALE_FACTOR),
+                &99,
+                &b
+            ),
+            Err(Ok(MarketError::InvalidOutcome))
+        );
+        assert_eq!(
+            client.try_price(&0, &0, &99, &b),
+            Err(Ok(MarketError::InvalidOutcome))
+        );
+    }
+
+    #[test]
+    fn test_invalid_amount() {
+        let (_env, client) = setup();
+        let b = 100 * SCALE_FACTOR;
+
+        assert_eq!(
+            client.try_buy_cost(&0, &0, &0, &0, &b),
+            Err(Ok(MarketError::InvalidAmount))
+        );
+        assert_eq!(
+            client.try_buy_cost(&0, &0, &-10, &0, &b),
+            Err(Ok(MarketError::InvalidAmount))
+        );
+    }
+
+    #[test]
+    fn test_sell_insufficient_global_balance() {
+        let (_env, client) = setup();
+        let b = 100 * SCALE_FACTOR;
+
+        assert_eq!(
+            client.try_sell_return(
+                &(5 * SCALE_FACTOR),
+                &(10 * SCALE_FACTOR),
+                &(10 * SCALE_FACTOR),
+                &0,
+                &b
+            ),
+            Err(Ok(MarketError::InsufficientBalance))
+        );
+    }
+}
@@ -1,7 +1,8 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contracterror, contractimpl, contracttype, Address, BytesN, Env, String, Vec,
+    contract, contractclient, contracterror, contractimpl, contracttype, Address, BytesN, Env,
+    IntoVal, String, TryFromVal, Val, Vec,
 };
 
 #[contracterror]
@@ -20,6 +21,18 @@ pub enum FactoryError {
     IndexOutOfBounds = 5,
     /// Critical storage data missing
     StorageCorrupted = 6,
+    /// Market WASM hash has been banned by the admin
+    WasmBanned = 7,
+    /// Protocol fee exceeds 100%
+    InvalidFee = 8,
+    /// No admin transfer has been proposed
+    NoPendingAdmin = 9,
+    /// Salt was already used by an earlier deployment
+    SaltAlreadyUsed = 10,
+    /// Oracle has deployed as many markets as the admin allows
+    OracleQuotaExceeded = 11,
+    /// Arithmetic overflow while totalling payouts
+    Overflow = 12,
 }
 
 #[derive(Clone)]
@@ -29,10 +42,112 @@ pub enum DataKey {
     Admin,
     /// WASM hash of the LMSR market contract
     MarketWasmHash,
-    /// List of deployed market addresses
+    /// List of active market addresses, in deployment order
     Markets,
     /// Default collateral token
     DefaultCollateralToken,
+    /// Market WASM hashes banned from deployment: BannedWasm(hash)
+    BannedWasm(BytesN<32>),
+    /// Protocol fee in basis points applied by newly deployed markets
+    ProtocolFeeBps,
+    /// Recipient of the protocol fee (venue treasury)
+    ProtocolFeeRecipient,
+    /// Cached summary of a deployed market: MarketInfo(market)
+    MarketInfo(Address),
+    /// First market deployed with a metadata hash: MarketByHash(hash)
+    MarketByHash(String),
+    /// Markets removed from the active list by the admin, in archive order
+    ArchivedMarkets,
+    /// Admin proposed by the current admin, waiting to accept
+    PendingAdmin,
+    /// Salts already used to deploy a market: UsedSalt(salt)
+    UsedSalt(BytesN<32>),
+    /// Most markets a single oracle may deploy, if limited
+    MaxMarketsPerOracle,
+    /// Markets deployed for an oracle: OracleMarketCount(oracle)
+    OracleMarketCount(Address),
+}
+
+/// Optional market settings passed to the market constructor.
+///
+/// Mirrors `MarketConfig` in the lmsr_market contract; field names and types
+/// must match so the struct encodes identically.
+#[derive(Clone)]
+#[contracttype]
+pub struct MarketConfig {
+    /// Factory that deployed the market (may pause it)
+    pub factory: Option<Address>,
+    /// Prefix for the market's metadata URI
+    pub metadata_base_uri: Option<String>,
+    /// Protocol fee on every buy and sell, in basis points
+    pub protocol_fee_bps: u32,
+    /// Where the protocol fee is sent
+    pub protocol_fee_recipient: Option<Address>,
+    /// Fee on claimed winnings in basis points
+    pub claim_fee_bps: i128,
+    /// Ledgers after resolution during which winnings can be claimed
+    pub claim_deadline_ledgers: Option<u32>,
+    /// Address allowed to resolve, if not the oracle
+    pub resolver: Option<Address>,
+    /// Ledgers a proposed resolution can be disputed
+    pub dispute_window_ledgers: Option<u32>,
+    /// Required funding buffer over b * ln(2), in basis points
+    pub safety_multiplier_bps: u32,
+    /// Human-readable outcome names, one per outcome
+    pub outcome_labels: Option<Vec<String>>,
+    /// Account that pays the initial funding instead of the oracle
+    pub funder: Option<Address>,
+    /// Smallest token amount a single buy or sell may move
+    pub min_trade_amount: i128,
+    /// Reject resolving to an outcome with no tokens sold
+    pub require_nonzero_winner: bool,
+    /// Receives a share of every claim fee
+    pub rewards_address: Option<Address>,
+    /// Share of each claim fee sent to rewards_address, in basis points
+    pub rewards_share_bps: u32,
+    /// Ledgers the settlement price averages over before resolution
+    pub twap_window_ledgers: Option<u32>,
+}
+
+/// Basis points denominator (100% = 10000 bp), matching the market contract.
+const BPS_DENOMINATOR: u32 = 10_000;
+
+/// Claim fee for factory-deployed markets (2%), the market's default.
+const CLAIM_FEE_BPS: i128 = 200;
+
+/// Remaining TTL, in ledgers, below which a per-market entry is extended
+/// when read or written (about 7 days at 5s ledgers).
+const MARKET_ENTRY_TTL_THRESHOLD: u32 = 120_960;
+
+/// TTL, in ledgers, per-market entries are extended to (about 30 days).
+const MARKET_ENTRY_TTL: u32 = 518_400;
+
+/// Cached summary of a deployed market for dashboards.
+///
+/// Written at deploy time; `resolved` and `oracle` can go stale until
+/// `sync_market_status` refreshes them from the market. `collateral_token`
+/// is the token the market was deployed with and never changes, whatever
+/// the factory default becomes later.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct MarketInfo {
+    pub address: Address,
+    pub oracle: Address,
+    pub collateral_token: Address,
+    pub metadata_hash: String,
+    pub resolved: bool,
+}
+
+/// Subset of the LMSR market interface the factory calls into.
+#[contractclient(name = "MarketClient")]
+pub trait MarketInterface {
+    fn factory_set_paused(env: Env, paused: bool);
+    fn get_state(env: Env) -> (i128, i128, i128, bool);
+    fn get_oracle(env: Env) -> Address;
+    fn get_status(env: Env) -> String;
+    fn cancel(env: Env, oracle: Address);
+    fn withdraw_remaining(env: Env, oracle: Address) -> i128;
+    fn claim(env: Env, user: Address) -> i128;
 }
 
 /// Market Factory Contract
@@ -69,21 +184,20 @@ impl MarketFactory {
         env.storage()
             .instance()
             .set(&DataKey::DefaultCollateralToken, &default_collateral_token);
-        env.storage()
-            .instance()
-            .set(&DataKey::Markets, &Vec::<Address>::new(&env));
+        Self::set_market_entry(&env, &DataKey::Markets, &Vec::<Address>::new(&env));
 
         Ok(())
     }
 
-    /// Deploy a new prediction market.
+    /// Deploy a new prediction market in the default collateral token.
     ///
     /// # Arguments
     /// * `oracle` - Address that can resolve the market
     /// * `liquidity_param` - LMSR b parameter (scaled by 10^7)
     /// * `metadata_hash` - IPFS hash for market metadata
     /// * `initial_funding` - Collateral to fund the market
-    /// * `salt` - Unique salt for deterministic address generation
+    /// * `salt` - Unique salt for deterministic address generation (reuse fails
+    ///   with SaltAlreadyUsed)
     ///
     /// # Returns
     /// Address of the deployed market contract
@@ -97,40 +211,202 @@ impl MarketFactory {
     ) -> Result<Address, FactoryError> {
         Self::require_initialized(&env)?;
 
-        oracle.require_auth();
-
-        let wasm_hash: BytesN<32> = env
+        let collateral_token: Address = env
             .storage()
             .instance()
-            .get(&DataKey::MarketWasmHash)
+            .get(&DataKey::DefaultCollateralToken)
             .ok_or(FactoryError::StorageCorrupted)?;
 
+        Self::deploy_market_with_token(
+            env,
+            oracle,
+            collateral_token,
+            liquidity_param,
+            metadata_hash,
+            initial_funding,
+            salt,
+        )
+    }
+
+    /// Deploy a new prediction market denominated in the given collateral token.
+    ///
+    /// # Arguments
+    /// * `oracle` - Address that can resolve the market
+    /// * `collateral_token` - Token the market trades and settles in
+    /// * `liquidity_param` - LMSR b parameter (scaled by 10^7)
+    /// * `metadata_hash` - IPFS hash for market metadata
+    /// * `initial_funding` - Collateral to fund the market
+    /// * `salt` - Unique salt for deterministic address generation (reuse fails
+    ///   with SaltAlreadyUsed)
+    ///
+    /// # Returns
+    /// Address of the deployed market contract
+    pub fn deploy_market_with_token(
+        env: Env,
+        oracle: Address,
+        collateral_token: Address,
+        liquidity_param: i128,
+        metadata_hash: String,
+        initial_funding: i128,
+        salt: BytesN<32>,
+    ) -> Result<Address, FactoryError> {
+        Self::deploy(
+            env,
+            oracle,
+            None,
+            collateral_token,
+            liquidity_param,
+            metadata_hash,
+            initial_funding,
+            salt,
+        )
+    }
+
+    /// Deploy a new prediction market funded by a separate account.
+    ///
+    /// `funder` authorizes and pays `initial_funding` (e.g. a treasury), while
+    /// `oracle` administers and resolves the market as usual, including
+    /// withdrawing what remains of the pool afterwards.
+    ///
+    /// # Arguments
+    /// * `oracle` - Address that can resolve the market
+    /// * `funder` - Address that provides the initial funding
+    /// * `liquidity_param` - LMSR b parameter (scaled by 10^7)
+    /// * `metadata_hash` - IPFS hash for market metadata
+    /// * `initial_funding` - Collateral to fund the market
+    /// * `salt` - Unique salt for deterministic address generation (reuse fails
+    ///   with SaltAlreadyUsed)
+    ///
+    /// # Returns
+    /// Address of the deployed market contract
+    pub fn deploy_market_funded(
+        env: Env,
+        oracle: Address,
+        funder: Address,
+        liquidity_param: i128,
+        metadata_hash: String,
+        initial_funding: i128,
+        salt: BytesN<32>,
+    ) -> Result<Address, FactoryError> {
+        Self::require_initialized(&env)?;
+
         let collateral_token: Address = env
             .storage()
             .instance()
             .get(&DataKey::DefaultCollateralToken)
             .ok_or(FactoryError::StorageCorrupted)?;
 
+        Self::deploy(
+            env,
+            oracle,
+            Some(funder),
+            collateral_token,
+            liquidity_param,
+            metadata_hash,
+            initial_funding,
+            salt,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn deploy(
+        env: Env,
+        oracle: Address,
+        funder: Option<Address>,
+        collateral_token: Address,
+        liquidity_param: i128,
+        metadata_hash: String,
+        initial_funding: i128,
+        salt: BytesN<32>,
+    ) -> Result<Address, FactoryError> {
+        Self::require_initialized(&env)?;
+
+        oracle.require_auth();
+        if let Some(funder) = &funder {
+            if *funder != oracle {
+                funder.require_auth();
+            }
+        }
+
+        let wasm_hash: BytesN<32> = env
+            .storage()
+            .instance()
+            .get(&DataKey::MarketWasmHash)
+            .ok_or(FactoryError::StorageCorrupted)?;
+        if Self::is_wasm_banned(env.clone(), wasm_hash.clone()) {
+            return Err(FactoryError::WasmBanned);
+        }
+
+        // A reused salt resolves to an existing address and the deploy would
+        // fail deep in the host, so reject it up front
+        let salt_key = DataKey::UsedSalt(salt.clone());
+        if Self::get_market_entry::<bool>(&env, &salt_key).is_some() {
+            return Err(FactoryError::SaltAlreadyUsed);
+        }
+        Self::set_market_entry(&env, &salt_key, &true);
+
+        let count_key = DataKey::OracleMarketCount(oracle.clone());
+        let oracle_markets: u32 = Self::get_market_entry(&env, &count_key).unwrap_or(0);
+        let max_markets: Option<u32> = env.storage().instance().get(&DataKey::MaxMarketsPerOracle);
+        if max_markets.is_some_and(|max| oracle_markets >= max) {
+            return Err(FactoryError::OracleQuotaExceeded);
+        }
+        Self::set_market_entry(&env, &count_key, &(oracle_markets + 1));
+
+        let (protocol_fee_bps, protocol_fee_recipient) = Self::get_protocol_fee(env.clone());
+
         // Deploy the market contract
         let market_address = env.deployer().with_current_contract(salt).deploy_v2(
             wasm_hash,
             (
                 oracle.clone(),
-                collateral_token,
+                collateral_token.clone(),
                 liquidity_param,
-                metadata_hash,
+                metadata_hash.clone(),
                 initial_funding,
+                MarketConfig {
+                    factory: Some(env.current_contract_address()),
+                    metadata_base_uri: None,
+                    protocol_fee_bps,
+                    protocol_fee_recipient,
+                    claim_fee_bps: CLAIM_FEE_BPS,
+                    claim_deadline_ledgers: None,
+                    resolver: None,
+                    dispute_window_ledgers: None,
+                    safety_multiplier_bps: 0,
+                    outcome_labels: None,
+                    funder,
+                    min_trade_amount: 0,
+                    require_nonzero_winner: false,
+                    rewards_address: None,
+                    rewards_share_bps: 0,
+                    twap_window_ledgers: None,
+                },
             ),
         );
 
         // Track the deployed market
-        let mut markets: Vec<Address> = env
-            .storage()
-            .instance()
-            .get(&DataKey::Markets)
-            .ok_or(FactoryError::StorageCorrupted)?;
+        let mut markets = Self::markets(&env);
         markets.push_back(market_address.clone());
-        env.storage().instance().set(&DataKey::Markets, &markets);
+        Self::set_market_entry(&env, &DataKey::Markets, &markets);
+
+        // Keep the first market if a metadata hash is reused
+        let hash_key = DataKey::MarketByHash(metadata_hash.clone());
+        if Self::get_market_entry::<Address>(&env, &hash_key).is_none() {
+            Self::set_market_entry(&env, &hash_key, &market_address);
+        }
+
+        Self::set_market_entry(
+            &env,
+            &DataKey::MarketInfo(market_address.clone()),
+            &MarketInfo {
+                address: market_address.clone(),
+                oracle,
+                collateral_token,
+                metadata_hash,
+                resolved: false,
+            },
+        );
 
         Ok(market_address)
     }
@@ -138,34 +414,188 @@ impl MarketFactory {
     /// Get all deployed market addresses.
     pub fn list_markets(env: Env) -> Result<Vec<Address>, FactoryError> {
         Self::require_initialized(&env)?;
-        env.storage()
-            .instance()
-            .get(&DataKey::Markets)
-            .ok_or(FactoryError::StorageCorrupted)
+        Ok(Self::markets(&env))
+    }
+
+    /// Get the cached summary of every deployed market, in deployment order.
+    pub fn list_markets_detailed(env: Env) -> Result<Vec<MarketInfo>, FactoryError> {
+        Self::require_initialized(&env)?;
+
+        let mut infos = Vec::new(&env);
+        for market in Self::markets(&env).iter() {
+            let info: MarketInfo = Self::get_market_entry(&env, &DataKey::MarketInfo(market))
+                .ok_or(FactoryError::StorageCorrupted)?;
+            infos.push_back(info);
+        }
+        Ok(infos)
+    }
+
+    /// Get the addresses of all markets denominated in `collateral_token`,
+    /// in deployment order.
+    pub fn markets_by_collateral(
+        env: Env,
+        collateral_token: Address,
+    ) -> Result<Vec<Address>, FactoryError> {
+        let mut markets = Vec::new(&env);
+        for info in Self::list_markets_detailed(env.clone())?.iter() {
+            if info.collateral_token == collateral_token {
+                markets.push_back(info.address);
+            }
+        }
+        Ok(markets)
+    }
+
+    /// Find the market deployed with `metadata_hash`.
+    ///
+    /// If several markets share the hash, returns the first one deployed.
+    pub fn find_market(env: Env, metadata_hash: String) -> Option<Address> {
+        Self::get_market_entry(&env, &DataKey::MarketByHash(metadata_hash))
+    }
+
+    /// Refresh the cached summary of a market from the market itself.
+    ///
+    /// Anyone can call this; it only copies public market state.
+    pub fn sync_market_status(env: Env, index: u32) -> Result<MarketInfo, FactoryError> {
+        Self::require_initialized(&env)?;
+
+        let market_address = Self::markets(&env)
+            .get(index)
+            .ok_or(FactoryError::IndexOutOfBounds)?;
+        let key = DataKey::MarketInfo(market_address);
+        let mut info: MarketInfo =
+            Self::get_market_entry(&env, &key).ok_or(FactoryError::StorageCorrupted)?;
+
+        let market = MarketClient::new(&env, &info.address);
+        let (_, _, _, resolved) = market.get_state();
+        info.resolved = resolved;
+        info.oracle = market.get_oracle();
+
+        Self::set_market_entry(&env, &key, &info);
+
+        Ok(info)
+    }
+
+    /// Recover the funding of an oracle's stale markets.
+    ///
+    /// Markets have no expiry, so a market is stale when nobody holds outcome
+    /// tokens and collateral is left in its pool. Open stale markets are
+    /// cancelled first; resolved or already cancelled ones are only withdrawn.
+    /// Markets are picked by their cached oracle, and markets whose calls
+    /// fail are skipped rather than failing the batch. The oracle's
+    /// authorization must cover the `cancel` and `withdraw_remaining` calls
+    /// made on its behalf.
+    ///
+    /// # Returns
+    /// Total collateral transferred to the oracle
+    pub fn reclaim_stale(env: Env, oracle: Address) -> Result<i128, FactoryError> {
+        Self::require_initialized(&env)?;
+
+        oracle.require_auth();
+
+        let resolved = String::from_str(&env, "resolved");
+        let cancelled = String::from_str(&env, "cancelled");
+
+        let mut total: i128 = 0;
+        for info in Self::list_markets_detailed(env.clone())?.iter() {
+            if info.oracle != oracle {
+                continue;
+            }
+
+            let market = MarketClient::new(&env, &info.address);
+            // Re-check the live oracle; the cached one may predate a transfer
+            if market.try_get_oracle() != Ok(Ok(oracle.clone())) {
+                continue;
+            }
+
+            let Ok(Ok((yes_sold, no_sold, pool, _))) = market.try_get_state() else {
+                continue;
+            };
+            if yes_sold != 0 || no_sold != 0 || pool <= 0 {
+                continue;
+            }
+
+            let Ok(Ok(status)) = market.try_get_status() else {
+                continue;
+            };
+            if status != resolved
+                && status != cancelled
+                && !matches!(market.try_cancel(&oracle), Ok(Ok(())))
+            {
+                continue;
+            }
+            if let Ok(Ok(withdrawn)) = market.try_withdraw_remaining(&oracle) {
+                total = total.checked_add(withdrawn).ok_or(FactoryError::Overflow)?;
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Claim `user`'s winnings from several markets in one call.
+    ///
+    /// Markets that can't pay out (unresolved, nothing to claim, claim
+    /// window closed) are skipped rather than failing the batch.
+    ///
+    /// # Returns
+    /// Total collateral paid to `user`
+    pub fn claim_all(env: Env, user: Address, markets: Vec<Address>) -> Result<i128, FactoryError> {
+        Self::require_initialized(&env)?;
+
+        user.require_auth();
+
+        let mut total: i128 = 0;
+        for market_address in markets.iter() {
+            if let Ok(Ok(payout)) = MarketClient::new(&env, &market_address).try_claim(&user) {
+                total = total.checked_add(payout).ok_or(FactoryError::Overflow)?;
+            }
+        }
+
+        Ok(total)
     }
 
     /// Get the number of deployed markets.
     pub fn market_count(env: Env) -> Result<u32, FactoryError> {
         Self::require_initialized(&env)?;
-        let markets: Vec<Address> = env
-            .storage()
-            .instance()
-            .get(&DataKey::Markets)
-            .ok_or(FactoryError::StorageCorrupted)?;
+        let markets = Self::markets(&env);
         Ok(markets.len())
     }
 
     /// Get a market address by index.
     pub fn get_market(env: Env, index: u32) -> Result<Address, FactoryError> {
         Self::require_initialized(&env)?;
-        let markets: Vec<Address> = env
-            .storage()
-            .instance()
-            .get(&DataKey::Markets)
-            .ok_or(FactoryError::StorageCorrupted)?;
+        let markets = Self::markets(&env);
         markets.get(index).ok_or(FactoryError::IndexOutOfBounds)
     }
 
+    /// Move a market out of the active list into the archive (admin only).
+    ///
+    /// Later markets shift down one index. Cached info is keyed by market
+    /// address, so it stays put. The market contract itself is unaffected,
+    /// and `find_market` still resolves its metadata hash.
+    pub fn archive_market(env: Env, admin: Address, index: u32) -> Result<Address, FactoryError> {
+        Self::require_initialized(&env)?;
+        Self::require_admin(&env, &admin)?;
+
+        admin.require_auth();
+
+        let mut markets = Self::markets(&env);
+        let market_address = markets.get(index).ok_or(FactoryError::IndexOutOfBounds)?;
+        markets.remove(index);
+        Self::set_market_entry(&env, &DataKey::Markets, &markets);
+
+        let mut archived = Self::list_archived_markets(env.clone())?;
+        archived.push_back(market_address.clone());
+        Self::set_market_entry(&env, &DataKey::ArchivedMarkets, &archived);
+
+        Ok(market_address)
+    }
+
+    /// Get the addresses of archived markets, in archive order.
+    pub fn list_archived_markets(env: Env) -> Result<Vec<Address>, FactoryError> {
+        Self::require_initialized(&env)?;
+        Ok(Self::get_market_entry(&env, &DataKey::ArchivedMarkets).unwrap_or(Vec::new(&env)))
+    }
+
     /// Get the admin address.
     pub fn get_admin(env: Env) -> Result<Address, FactoryError> {
         Self::require_initialized(&env)?;
@@ -175,6 +605,48 @@ impl MarketFactory {
             .ok_or(FactoryError::StorageCorrupted)
     }
 
+    /// Propose a new admin (current admin only).
+    ///
+    /// The transfer completes only when `new_admin` calls `accept_admin`, so
+    /// a mistyped address can't lock the factory. A later proposal replaces
+    /// an earlier one.
+    pub fn transfer_admin(
+        env: Env,
+        current_admin: Address,
+        new_admin: Address,
+    ) -> Result<(), FactoryError> {
+        Self::require_initialized(&env)?;
+        Self::require_admin(&env, &current_admin)?;
+
+        current_admin.require_auth();
+
+        env.storage()
+            .instance()
+            .set(&DataKey::PendingAdmin, &new_admin);
+
+        Ok(())
+    }
+
+    /// Accept a proposed admin transfer (proposed admin only).
+    pub fn accept_admin(env: Env, new_admin: Address) -> Result<(), FactoryError> {
+        Self::require_initialized(&env)?;
+
+        let pending: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::PendingAdmin)
+            .ok_or(FactoryError::NoPendingAdmin)?;
+        if new_admin != pending {
+            return Err(FactoryError::Unauthorized);
+        }
+        new_admin.require_auth();
+
+        env.storage().instance().set(&DataKey::Admin, &new_admin);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+
+        Ok(())
+    }
+
     /// Get the market WASM hash.
     pub fn get_market_wasm_hash(env: Env) -> Result<BytesN<32>, FactoryError> {
         Self::require_initialized(&env)?;
@@ -195,6 +667,10 @@ impl MarketFactory {
 
         admin.require_auth();
 
+        if Self::is_wasm_banned(env.clone(), new_wasm_hash.clone()) {
+            return Err(FactoryError::WasmBanned);
+        }
+
         env.storage()
             .instance()
             .set(&DataKey::MarketWasmHash, &new_wasm_hash);
@@ -202,6 +678,38 @@ impl MarketFactory {
         Ok(())
     }
 
+    /// Ban or unban a market WASM hash (admin only).
+    ///
+    /// A banned hash can't be used for new deployments. Clients can check
+    /// existing markets against it with `is_wasm_banned`.
+    pub fn set_wasm_banned(
+        env: Env,
+        admin: Address,
+        wasm_hash: BytesN<32>,
+        banned: bool,
+    ) -> Result<(), FactoryError> {
+        Self::require_initialized(&env)?;
+        Self::require_admin(&env, &admin)?;
+
+        admin.require_auth();
+
+        let key = DataKey::BannedWasm(wasm_hash);
+        if banned {
+            env.storage().instance().set(&key, &true);
+        } else {
+            env.storage().instance().remove(&key);
+        }
+
+        Ok(())
+    }
+
+    /// Check whether a market WASM hash is banned.
+    pub fn is_wasm_banned(env: Env, wasm_hash: BytesN<32>) -> bool {
+        env.storage()
+            .instance()
+            .has(&DataKey::BannedWasm(wasm_hash))
+    }
+
     /// Update the default collateral token (admin only).
     pub fn set_default_collateral_token(
         env: Env,
@@ -220,43 +728,190 @@ impl MarketFactory {
         Ok(())
     }
 
-    // --- Internal helpers ---
+    /// Set the protocol fee applied to every buy and sell (admin only).
+    ///
+    /// Markets copy the fee at deploy time, so changes only affect markets
+    /// deployed afterwards. A fee of 0 disables it.
+    pub fn set_protocol_fee(
+        env: Env,
+        admin: Address,
+        fee_bps: u32,
+        recipient: Address,
+    ) -> Result<(), FactoryError> {
+        Self::require_initialized(&env)?;
+        Self::require_admin(&env, &admin)?;
 
-    fn require_initialized(env: &Env) -> Result<(), FactoryError> {
-        if !env.storage().instance().has(&DataKey::Admin) {
-            return Err(FactoryError::NotInitialized);
+        admin.require_auth();
+
+        if fee_bps > BPS_DENOMINATOR {
+            return Err(FactoryError::InvalidFee);
         }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolFeeBps, &fee_bps);
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolFeeRecipient, &recipient);
+
         Ok(())
     }
 
-    fn require_admin(env: &Env, caller: &Address) -> Result<(), FactoryError> {
-        let admin: Address = env
+    /// Get the protocol fee for new markets: (fee_bps, recipient).
+    /// Returns (0, None) if no fee is configured.
+    pub fn get_protocol_fee(env: Env) -> (u32, Option<Address>) {
+        let fee_bps: u32 = env
             .storage()
             .instance()
-            .get(&DataKey::Admin)
-            .ok_or(FactoryError::StorageCorrupted)?;
-        if *caller != admin {
-            return Err(FactoryError::Unauthorized);
+            .get(&DataKey::ProtocolFeeBps)
+            .unwrap_or(0);
+        if fee_bps == 0 {
+            return (0, None);
         }
-        Ok(())
+        (
+            fee_bps,
+            env.storage().instance().get(&DataKey::ProtocolFeeRecipient),
+        )
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use soroban_sdk::{testutils::Address as _, Env};
+    /// Limit how many markets a single oracle may deploy (admin only).
+    ///
+    /// `None` removes the limit. Markets already deployed count toward it,
+    /// so lowering it below an oracle's count blocks only new deployments.
+    pub fn set_max_markets_per_oracle(
+        env: Env,
+        admin: Address,
+        max_markets: Option<u32>,
+    ) -> Result<(), FactoryError> {
+        Self::require_initialized(&env)?;
+        Self::require_admin(&env, &admin)?;
 
-    #[test]
-    fn test_initialize() {
-        let env = Env::default();
-        env.mock_all_auths();
+        admin.require_auth();
 
-        let contract_id = env.register(MarketFactory, ());
-        let client = MarketFactoryClient::new(&env, &contract_id);
+        match max_markets {
+            Some(max) => env
+                .storage()
+                .instance()
+                .set(&DataKey::MaxMarketsPerOracle, &max),
+            None => env
+                .storage()
+                .instance()
+                .remove(&DataKey::MaxMarketsPerOracle),
+        }
 
-        let admin = Address::generate(&env);
-        let wasm_hash = BytesN::from_array(&env, &[0u8; 32]);
+        Ok(())
+    }
+
+    /// Get the number of markets deployed for an oracle.
+    pub fn oracle_market_count(env: Env, oracle: Address) -> u32 {
+        Self::get_market_entry(&env, &DataKey::OracleMarketCount(oracle)).unwrap_or(0)
+    }
+
+    /// Pause trading on a deployed market (admin only).
+    ///
+    /// Venue-wide kill switch: the market accepts this only from the factory
+    /// that deployed it.
+    pub fn factory_pause_market(
+        env: Env,
+        admin: Address,
+        market: Address,
+    ) -> Result<(), FactoryError> {
+        Self::set_market_paused(&env, &admin, &market, true)
+    }
+
+    /// Resume trading on a market paused by the factory (admin only).
+    pub fn factory_unpause_market(
+        env: Env,
+        admin: Address,
+        market: Address,
+    ) -> Result<(), FactoryError> {
+        Self::set_market_paused(&env, &admin, &market, false)
+    }
+
+    // --- Internal helpers ---
+
+    fn set_market_paused(
+        env: &Env,
+        admin: &Address,
+        market: &Address,
+        paused: bool,
+    ) -> Result<(), FactoryError> {
+        Self::require_initialized(env)?;
+        Self::require_admin(env, admin)?;
+
+        admin.require_auth();
+
+        MarketClient::new(env, market).factory_set_paused(&paused);
+
+        Ok(())
+    }
+
+    fn require_initialized(env: &Env) -> Result<(), FactoryError> {
+        if !env.storage().instance().has(&DataKey::Admin) {
+            return Err(FactoryError::NotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Deployed market addresses, empty if the list is missing from storage.
+    fn markets(env: &Env) -> Vec<Address> {
+        Self::get_market_entry(env, &DataKey::Markets).unwrap_or(Vec::new(env))
+    }
+
+    /// Read a per-market entry from persistent storage, extending its TTL
+    /// so entries that are still in use don't expire.
+    fn get_market_entry<V: TryFromVal<Env, Val>>(env: &Env, key: &DataKey) -> Option<V> {
+        let value = env.storage().persistent().get(key);
+        if value.is_some() {
+            env.storage().persistent().extend_ttl(
+                key,
+                MARKET_ENTRY_TTL_THRESHOLD,
+                MARKET_ENTRY_TTL,
+            );
+        }
+        value
+    }
+
+    /// Write a per-market entry to persistent storage and extend its TTL.
+    ///
+    /// Per-market entries and the market lists grow with every deployment,
+    /// so they live in persistent storage rather than the instance entry
+    /// loaded on each call.
+    fn set_market_entry<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage()
+            .persistent()
+            .extend_ttl(key, MARKET_ENTRY_TTL_THRESHOLD, MARKET_ENTRY_TTL);
+    }
+
+    fn require_admin(env: &Env, caller: &Address) -> Result<(), FactoryError> {
+        let admin: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .ok_or(FactoryError::StorageCorrupted)?;
+        if *caller != admin {
+            return Err(FactoryError::Unauthorized);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use soroban_sdk::{testutils::Address as _, Env};
+
+    #[test]
+    fn test_initialize() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register(MarketFactory, ());
+        let client = MarketFactoryClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let wasm_hash = BytesN::from_array(&env, &[0u8; 32]);
         let collateral_token = Address::generate(&env);
 
         client.initialize(&admin, &wasm_hash, &collateral_token);
@@ -323,6 +978,63 @@ mod test {
         client.set_market_wasm_hash(&attacker, &new_wasm_hash);
     }
 
+    #[test]
+    fn test_transfer_admin() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register(MarketFactory, ());
+        let client = MarketFactoryClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let wasm_hash = BytesN::from_array(&env, &[0u8; 32]);
+        client.initialize(&admin, &wasm_hash, &Address::generate(&env));
+
+        let new_admin = Address::generate(&env);
+        client.transfer_admin(&admin, &new_admin);
+
+        // Nothing changes until the new admin accepts
+        assert_eq!(client.get_admin(), admin);
+        assert_eq!(
+            client.try_accept_admin(&Address::generate(&env)),
+            Err(Ok(FactoryError::Unauthorized))
+        );
+        client.accept_admin(&new_admin);
+        assert_eq!(client.get_admin(), new_admin);
+
+        // The old admin loses its privileges and the new one gains them
+        let new_wasm_hash = BytesN::from_array(&env, &[1u8; 32]);
+        assert_eq!(
+            client.try_set_market_wasm_hash(&admin, &new_wasm_hash),
+            Err(Ok(FactoryError::Unauthorized))
+        );
+        client.set_market_wasm_hash(&new_admin, &new_wasm_hash);
+        assert_eq!(client.get_market_wasm_hash(), new_wasm_hash);
+
+        // The proposal is consumed
+        assert_eq!(
+            client.try_accept_admin(&new_admin),
+            Err(Ok(FactoryError::NoPendingAdmin))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #3)")] // Unauthorized = 3
+    fn test_transfer_admin_by_non_admin() {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let contract_id = env.register(MarketFactory, ());
+        let client = MarketFactoryClient::new(&env, &contract_id);
+
+        let admin = Address::generate(&env);
+        let wasm_hash = BytesN::from_array(&env, &[0u8; 32]);
+        client.initialize(&admin, &wasm_hash, &Address::generate(&env));
+
+        let attacker = Address::generate(&env);
+        client.transfer_admin(&attacker, &attacker);
+    }
+
     #[test]
     #[should_panic(expected = "Error(Contract, #3)")] // Unauthorized = 3
     fn test_set_default_collateral_token_by_non_admin() {
@@ -364,4 +1076,663 @@ mod test {
             &salt,
         );
     }
+
+    // --- Deployed market tests ---
+    // These deploy the real market WASM: build it first with
+    // `cargo build --release --target wasm32-unknown-unknown -p lmsr_market`
+
+    mod market {
+        soroban_sdk::contractimport!(
+            file = "../target/wasm32-unknown-unknown/release/lmsr_market.wasm"
+        );
+    }
+
+    use soroban_sdk::token::{self, StellarAssetClient};
+    use soroban_sdk::vec;
+
+    const SCALE_FACTOR: i128 = 10_000_000;
+
+    /// Initialize a factory with the real market WASM and a funded oracle.
+    /// Returns (env, factory_id, admin, oracle, token_address)
+    fn setup_factory() -> (Env, Address, Address, Address, Address) {
+        let env = Env::default();
+        env.mock_all_auths();
+
+        let admin = Address::generate(&env);
+        let oracle = Address::generate(&env);
+
+        let token_admin = Address::generate(&env);
+        let token_address = env
+            .register_stellar_asset_contract_v2(token_admin)
+            .address();
+        StellarAssetClient::new(&env, &token_address).mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        let wasm_hash = env.deployer().upload_contract_wasm(market::WASM);
+        let factory_id = env.register(MarketFactory, ());
+        MarketFactoryClient::new(&env, &factory_id).initialize(&admin, &wasm_hash, &token_address);
+
+        (env, factory_id, admin, oracle, token_address)
+    }
+
+    /// Deploy a market through the factory with standard test params.
+    fn deploy_test_market(
+        env: &Env,
+        client: &MarketFactoryClient,
+        oracle: &Address,
+        salt: u8,
+    ) -> Address {
+        client.deploy_market(
+            oracle,
+            &(100 * SCALE_FACTOR),
+            &String::from_str(env, "QmTest"),
+            &(70 * SCALE_FACTOR),
+            &BytesN::from_array(env, &[salt; 32]),
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #17)")] // market Paused = 17
+    fn test_factory_pause_market() {
+        let (env, factory_id, admin, oracle, token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let market_id = deploy_test_market(&env, &client, &oracle, 1);
+        let market_client = market::Client::new(&env, &market_id);
+
+        client.factory_pause_market(&admin, &market_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        market_client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        // Should panic
+    }
+
+    #[test]
+    fn test_factory_unpause_market() {
+        let (env, factory_id, admin, oracle, token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let market_id = deploy_test_market(&env, &client, &oracle, 1);
+        let market_client = market::Client::new(&env, &market_id);
+
+        client.factory_pause_market(&admin, &market_id);
+        client.factory_unpause_market(&admin, &market_id);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        let cost = market_client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert!(cost > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #3)")] // Unauthorized = 3
+    fn test_factory_pause_market_by_non_admin() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let market_id = deploy_test_market(&env, &client, &oracle, 1);
+
+        let attacker = Address::generate(&env);
+        client.factory_pause_market(&attacker, &market_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Auth, InvalidAction)")]
+    fn test_market_pause_rejects_non_factory_caller() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let market_id = deploy_test_market(&env, &client, &oracle, 1);
+        let market_client = market::Client::new(&env, &market_id);
+
+        // Calling the market directly carries no factory authorization
+        env.set_auths(&[]);
+        market_client.factory_set_paused(&true);
+    }
+
+    // --- Banned WASM tests ---
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #7)")] // WasmBanned = 7
+    fn test_banned_wasm_blocks_deploy() {
+        let (env, factory_id, admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        // Deploys work until the current hash is banned
+        deploy_test_market(&env, &client, &oracle, 1);
+
+        let wasm_hash = client.get_market_wasm_hash();
+        client.set_wasm_banned(&admin, &wasm_hash, &true);
+        assert!(client.is_wasm_banned(&wasm_hash));
+
+        deploy_test_market(&env, &client, &oracle, 2); // Should panic
+    }
+
+    #[test]
+    fn test_reused_salt_fails_cleanly() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        deploy_test_market(&env, &client, &oracle, 1);
+
+        let result = client.try_deploy_market(
+            &oracle,
+            &(100 * SCALE_FACTOR),
+            &String::from_str(&env, "QmOther"),
+            &(70 * SCALE_FACTOR),
+            &BytesN::from_array(&env, &[1; 32]),
+        );
+        assert_eq!(result, Err(Ok(FactoryError::SaltAlreadyUsed)));
+        assert_eq!(client.market_count(), 1);
+
+        // A fresh salt still deploys
+        deploy_test_market(&env, &client, &oracle, 2);
+        assert_eq!(client.market_count(), 2);
+    }
+
+    #[test]
+    fn test_oracle_quota_blocks_extra_markets() {
+        let (env, factory_id, admin, oracle, token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        client.set_max_markets_per_oracle(&admin, &Some(2));
+        deploy_test_market(&env, &client, &oracle, 1);
+        deploy_test_market(&env, &client, &oracle, 2);
+        assert_eq!(client.oracle_market_count(&oracle), 2);
+
+        let result = client.try_deploy_market(
+            &oracle,
+            &(100 * SCALE_FACTOR),
+            &String::from_str(&env, "QmThird"),
+            &(70 * SCALE_FACTOR),
+            &BytesN::from_array(&env, &[3; 32]),
+        );
+        assert_eq!(result, Err(Ok(FactoryError::OracleQuotaExceeded)));
+        assert_eq!(client.market_count(), 2);
+
+        // Other oracles have their own quota
+        let other_oracle = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&other_oracle, &(100 * SCALE_FACTOR));
+        deploy_test_market(&env, &client, &other_oracle, 4);
+        assert_eq!(client.oracle_market_count(&other_oracle), 1);
+
+        // Removing the limit lets the first oracle deploy again
+        client.set_max_markets_per_oracle(&admin, &None);
+        deploy_test_market(&env, &client, &oracle, 5);
+        assert_eq!(client.oracle_market_count(&oracle), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #3)")] // Unauthorized = 3
+    fn test_set_max_markets_per_oracle_by_non_admin() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        client.set_max_markets_per_oracle(&oracle, &Some(1));
+    }
+
+    #[test]
+    fn test_unban_wasm_allows_deploy() {
+        let (env, factory_id, admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let wasm_hash = client.get_market_wasm_hash();
+        client.set_wasm_banned(&admin, &wasm_hash, &true);
+        client.set_wasm_banned(&admin, &wasm_hash, &false);
+        assert!(!client.is_wasm_banned(&wasm_hash));
+
+        deploy_test_market(&env, &client, &oracle, 1);
+        assert_eq!(client.market_count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #3)")] // Unauthorized = 3
+    fn test_set_wasm_banned_by_non_admin() {
+        let (env, factory_id, _admin, _oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let attacker = Address::generate(&env);
+        client.set_wasm_banned(&attacker, &client.get_market_wasm_hash(), &true);
+    }
+
+    // --- Protocol fee tests ---
+
+    #[test]
+    fn test_protocol_fee_routed_to_recipient() {
+        let (env, factory_id, admin, oracle, token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let treasury = Address::generate(&env);
+        client.set_protocol_fee(&admin, &100, &treasury); // 1%
+
+        let market_id = deploy_test_market(&env, &client, &oracle, 1);
+        let market_client = market::Client::new(&env, &market_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+
+        let (quoted, _) = market_client.get_quote(&0, &(10 * SCALE_FACTOR));
+        let paid = market_client.buy(&user, &0, &(10 * SCALE_FACTOR), &(50 * SCALE_FACTOR));
+        assert_eq!(paid, quoted);
+
+        // The market keeps the LMSR cost; the fee goes straight to the treasury
+        let buy_fee = token_client.balance(&treasury);
+        assert!(buy_fee > 0);
+        let (_, _, pool, _) = market_client.get_state();
+        assert_eq!(pool - 70 * SCALE_FACTOR + buy_fee, paid);
+        assert_eq!(buy_fee, (paid - buy_fee) / 100);
+
+        let (quoted_return, _) = market_client.get_sell_quote(&0, &(10 * SCALE_FACTOR));
+        let received = market_client.sell(&user, &0, &(10 * SCALE_FACTOR), &0);
+        assert_eq!(received, quoted_return);
+        assert!(token_client.balance(&treasury) > buy_fee);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #8)")] // InvalidFee = 8
+    fn test_set_protocol_fee_above_100_percent() {
+        let (env, factory_id, admin, _oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        client.set_protocol_fee(&admin, &10_001, &Address::generate(&env));
+    }
+
+    // --- Market info tests ---
+
+    #[test]
+    fn test_list_markets_detailed() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let first = deploy_test_market(&env, &client, &oracle, 1);
+        let second = deploy_test_market(&env, &client, &oracle, 2);
+
+        market::Client::new(&env, &first).resolve(&oracle, &0);
+
+        // Cached at deploy time until synced
+        let infos = client.list_markets_detailed();
+        assert_eq!(infos.len(), 2);
+        assert!(!infos.get(0).unwrap().resolved);
+
+        client.sync_market_status(&0);
+        client.sync_market_status(&1);
+
+        let infos = client.list_markets_detailed();
+        let resolved_info = infos.get(0).unwrap();
+        let open_info = infos.get(1).unwrap();
+        assert_eq!(resolved_info.address, first);
+        assert!(resolved_info.resolved);
+        assert_eq!(open_info.address, second);
+        assert!(!open_info.resolved);
+        assert_eq!(open_info.oracle, oracle);
+        assert_eq!(open_info.metadata_hash, String::from_str(&env, "QmTest"));
+    }
+
+    #[test]
+    fn test_market_entries_are_persistent() {
+        use soroban_sdk::testutils::storage::Persistent as _;
+
+        let (env, factory_id, admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+        let market_address = deploy_test_market(&env, &client, &oracle, 1);
+        deploy_test_market(&env, &client, &oracle, 2);
+        client.archive_market(&admin, &1);
+
+        env.as_contract(&factory_id, || {
+            let keys = [
+                DataKey::Markets,
+                DataKey::ArchivedMarkets,
+                DataKey::MarketInfo(market_address.clone()),
+                DataKey::MarketByHash(String::from_str(&env, "QmTest")),
+                DataKey::UsedSalt(BytesN::from_array(&env, &[1; 32])),
+                DataKey::OracleMarketCount(oracle.clone()),
+            ];
+            for key in keys.iter() {
+                assert!(!env.storage().instance().has(key));
+                assert_eq!(env.storage().persistent().get_ttl(key), MARKET_ENTRY_TTL);
+            }
+        });
+    }
+
+    #[test]
+    fn test_missing_market_list_reads_as_empty() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        env.as_contract(&factory_id, || {
+            env.storage().persistent().remove(&DataKey::Markets);
+        });
+
+        assert_eq!(client.market_count(), 0);
+        assert_eq!(client.list_markets().len(), 0);
+        assert_eq!(
+            client.try_get_market(&0),
+            Err(Ok(FactoryError::IndexOutOfBounds))
+        );
+
+        // Deploying recreates the list
+        let market_address = deploy_test_market(&env, &client, &oracle, 1);
+        assert_eq!(client.market_count(), 1);
+        assert_eq!(client.get_market(&0), market_address);
+    }
+
+    // --- Archive tests ---
+
+    #[test]
+    fn test_archive_market() {
+        let (env, factory_id, admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let first = deploy_test_market(&env, &client, &oracle, 1);
+        let second = deploy_test_market(&env, &client, &oracle, 2);
+        let third = deploy_test_market(&env, &client, &oracle, 3);
+        assert_eq!(client.list_archived_markets().len(), 0);
+
+        assert_eq!(client.archive_market(&admin, &0), first);
+
+        assert_eq!(client.market_count(), 2);
+        assert_eq!(
+            client.list_markets(),
+            vec![&env, second.clone(), third.clone()]
+        );
+        assert_eq!(client.list_archived_markets(), vec![&env, first]);
+
+        // Cached info is keyed by address, so it still matches the shifted indices
+        let infos = client.list_markets_detailed();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos.get(0).unwrap().address, second);
+        assert_eq!(infos.get(1).unwrap().address, third);
+        assert_eq!(client.sync_market_status(&1).address, third);
+
+        // New deployments append after the remaining markets
+        let fourth = deploy_test_market(&env, &client, &oracle, 4);
+        assert_eq!(
+            client.list_markets_detailed().get(2).unwrap().address,
+            fourth
+        );
+    }
+
+    #[test]
+    fn test_archive_market_requires_admin() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+        deploy_test_market(&env, &client, &oracle, 1);
+
+        assert_eq!(
+            client.try_archive_market(&oracle, &0),
+            Err(Ok(FactoryError::Unauthorized))
+        );
+    }
+
+    #[test]
+    fn test_archive_market_out_of_bounds() {
+        let (env, factory_id, admin, _oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        assert_eq!(
+            client.try_archive_market(&admin, &0),
+            Err(Ok(FactoryError::IndexOutOfBounds))
+        );
+    }
+
+    // --- Stale market tests ---
+
+    #[test]
+    fn test_reclaim_stale() {
+        let (env, factory_id, _admin, oracle, token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let stale = deploy_test_market(&env, &client, &oracle, 1);
+        let active = deploy_test_market(&env, &client, &oracle, 2);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        market::Client::new(&env, &active).buy(
+            &user,
+            &0,
+            &(10 * SCALE_FACTOR),
+            &(50 * SCALE_FACTOR),
+        );
+
+        // Markets of other oracles are left alone
+        let other_oracle = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&other_oracle, &(100 * SCALE_FACTOR));
+        let foreign = deploy_test_market(&env, &client, &other_oracle, 3);
+
+        let balance_before = token_client.balance(&oracle);
+        let reclaimed = client.reclaim_stale(&oracle);
+
+        assert_eq!(reclaimed, 70 * SCALE_FACTOR);
+        assert_eq!(token_client.balance(&oracle), balance_before + reclaimed);
+
+        let status = |market_id: &Address| market::Client::new(&env, market_id).get_status();
+        assert_eq!(status(&stale), String::from_str(&env, "cancelled"));
+        assert_eq!(status(&active), String::from_str(&env, "open"));
+        assert_eq!(status(&foreign), String::from_str(&env, "open"));
+
+        // Nothing left to reclaim on a second pass
+        assert_eq!(client.reclaim_stale(&oracle), 0);
+    }
+
+    #[test]
+    fn test_claim_all_collects_from_resolved_markets() {
+        let (env, factory_id, _admin, oracle, token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let first = deploy_test_market(&env, &client, &oracle, 1);
+        let second = deploy_test_market(&env, &client, &oracle, 2);
+        let unresolved = deploy_test_market(&env, &client, &oracle, 3);
+
+        let user = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&user, &(100 * SCALE_FACTOR));
+        for market_id in [&first, &second, &unresolved] {
+            market::Client::new(&env, market_id).buy(
+                &user,
+                &0,
+                &(10 * SCALE_FACTOR),
+                &(50 * SCALE_FACTOR),
+            );
+        }
+        market::Client::new(&env, &first).resolve(&oracle, &0);
+        market::Client::new(&env, &second).resolve(&oracle, &0);
+
+        let markets = vec![&env, first.clone(), second.clone(), unresolved.clone()];
+        let balance_before = token_client.balance(&user);
+        let claimed = client.claim_all(&user, &markets);
+
+        assert!(claimed > 0);
+        assert_eq!(token_client.balance(&user), balance_before + claimed);
+        for market_id in [&first, &second] {
+            assert_eq!(
+                market::Client::new(&env, market_id).get_balance(&user, &0),
+                0
+            );
+        }
+        assert_eq!(
+            market::Client::new(&env, &unresolved).get_balance(&user, &0),
+            10 * SCALE_FACTOR
+        );
+
+        // Everything claimable was collected
+        assert_eq!(client.claim_all(&user, &markets), 0);
+    }
+
+    #[test]
+    fn test_deploy_market_with_token() {
+        let (env, factory_id, _admin, oracle, default_token) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let other_token = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        StellarAssetClient::new(&env, &other_token).mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        let default_market = deploy_test_market(&env, &client, &oracle, 1);
+        let other_market = client.deploy_market_with_token(
+            &oracle,
+            &other_token,
+            &(100 * SCALE_FACTOR),
+            &String::from_str(&env, "QmOther"),
+            &(70 * SCALE_FACTOR),
+            &BytesN::from_array(&env, &[2; 32]),
+        );
+
+        assert_eq!(
+            market::Client::new(&env, &default_market).get_collateral_token(),
+            default_token
+        );
+        assert_eq!(
+            market::Client::new(&env, &other_market).get_collateral_token(),
+            other_token
+        );
+        assert_eq!(
+            token::Client::new(&env, &other_token).balance(&other_market),
+            70 * SCALE_FACTOR
+        );
+
+        // Both deployments are tracked
+        assert_eq!(client.market_count(), 2);
+        assert_eq!(client.get_market(&1), other_market);
+    }
+
+    #[test]
+    fn test_default_token_change_keeps_recorded_token() {
+        let (env, factory_id, admin, oracle, original_token) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let market_address = deploy_test_market(&env, &client, &oracle, 1);
+
+        let new_token = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        client.set_default_collateral_token(&admin, &new_token);
+        client.sync_market_status(&0);
+
+        // Both the market and the registry still record the original token
+        assert_eq!(
+            market::Client::new(&env, &market_address).get_collateral_token(),
+            original_token
+        );
+        let info = client.list_markets_detailed().get(0).unwrap();
+        assert_eq!(info.collateral_token, original_token);
+        assert_eq!(
+            client.markets_by_collateral(&original_token),
+            vec![&env, market_address]
+        );
+        assert_eq!(client.markets_by_collateral(&new_token).len(), 0);
+    }
+
+    #[test]
+    fn test_deploy_market_funded_by_third_party() {
+        let (env, factory_id, _admin, oracle, token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+        let token_client = token::Client::new(&env, &token_address);
+
+        let funder = Address::generate(&env);
+        StellarAssetClient::new(&env, &token_address).mint(&funder, &(100 * SCALE_FACTOR));
+        let oracle_balance = token_client.balance(&oracle);
+
+        let market_address = client.deploy_market_funded(
+            &oracle,
+            &funder,
+            &(100 * SCALE_FACTOR),
+            &String::from_str(&env, "QmTest"),
+            &(70 * SCALE_FACTOR),
+            &BytesN::from_array(&env, &[1; 32]),
+        );
+
+        // Both authorized the deployment
+        let auths = env.auths();
+        assert!(auths.iter().any(|(address, _)| *address == oracle));
+        assert!(auths.iter().any(|(address, _)| *address == funder));
+
+        // The funder pays; the oracle's balance is untouched
+        assert_eq!(token_client.balance(&funder), 30 * SCALE_FACTOR);
+        assert_eq!(token_client.balance(&oracle), oracle_balance);
+        assert_eq!(token_client.balance(&market_address), 70 * SCALE_FACTOR);
+
+        // The oracle still controls resolution
+        let market_client = market::Client::new(&env, &market_address);
+        assert_eq!(market_client.get_oracle(), oracle);
+        market_client.resolve(&oracle, &0);
+        assert!(market_client.get_state().3);
+    }
+
+    #[test]
+    fn test_markets_by_collateral() {
+        let (env, factory_id, _admin, oracle, default_token) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let other_token = env
+            .register_stellar_asset_contract_v2(Address::generate(&env))
+            .address();
+        StellarAssetClient::new(&env, &other_token).mint(&oracle, &(1000 * SCALE_FACTOR));
+
+        let first = deploy_test_market(&env, &client, &oracle, 1);
+        let other = client.deploy_market_with_token(
+            &oracle,
+            &other_token,
+            &(100 * SCALE_FACTOR),
+            &String::from_str(&env, "QmOther"),
+            &(70 * SCALE_FACTOR),
+            &BytesN::from_array(&env, &[2; 32]),
+        );
+        let second = deploy_test_market(&env, &client, &oracle, 3);
+
+        let default_markets = client.markets_by_collateral(&default_token);
+        assert_eq!(default_markets.len(), 2);
+        assert_eq!(default_markets.get(0).unwrap(), first);
+        assert_eq!(default_markets.get(1).unwrap(), second);
+
+        let other_markets = client.markets_by_collateral(&other_token);
+        assert_eq!(other_markets.len(), 1);
+        assert_eq!(other_markets.get(0).unwrap(), other);
+
+        assert_eq!(
+            client.markets_by_collateral(&Address::generate(&env)).len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_find_market_by_metadata_hash() {
+        let (env, factory_id, _admin, oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        let first = deploy_test_market(&env, &client, &oracle, 1);
+        let other = client.deploy_market(
+            &oracle,
+            &(100 * SCALE_FACTOR),
+            &String::from_str(&env, "QmOther"),
+            &(70 * SCALE_FACTOR),
+            &BytesN::from_array(&env, &[2; 32]),
+        );
+        // Same hash as the first market
+        deploy_test_market(&env, &client, &oracle, 3);
+
+        assert_eq!(
+            client.find_market(&String::from_str(&env, "QmTest")),
+            Some(first)
+        );
+        assert_eq!(
+            client.find_market(&String::from_str(&env, "QmOther")),
+            Some(other)
+        );
+        assert_eq!(
+            client.find_market(&String::from_str(&env, "QmMissing")),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #5)")] // IndexOutOfBounds = 5
+    fn test_sync_market_status_out_of_bounds() {
+        let (env, factory_id, _admin, _oracle, _token_address) = setup_factory();
+        let client = MarketFactoryClient::new(&env, &factory_id);
+
+        client.sync_market_status(&0);
+    }
 }
@@ -1116,6 +1116,42 @@ func mapError(err error) errorResponse {
 func mapContractError(errStr string) errorResponse {
 	code := extractLastErrorCode(errStr)
 	switch code {
+	case 33:
+		return errorResponse{"The market cannot accept this token right now. Try paying with the base collateral.", http.StatusBadRequest}
+	case 32:
+		return errorResponse{"This market has reached its dispute limit.", http.StatusBadRequest}
+	case 31:
+		return errorResponse{"Cannot transfer a position to the same address.", http.StatusBadRequest}
+	case 30:
+		return errorResponse{"The collateral token rejected the transfer. Check that your account is authorized to hold it.", http.StatusBadRequest}
+	case 29:
+		return errorResponse{"Cannot resolve to an outcome nobody holds in this market.", http.StatusBadRequest}
+	case 28:
+		return errorResponse{"This market already has the maximum number of resolution callbacks.", http.StatusBadRequest}
+	case 27:
+		return errorResponse{"Trade amount is below this market's minimum trade size.", http.StatusBadRequest}
+	case 26:
+		return errorResponse{"The dispute window for this resolution has closed.", http.StatusBadRequest}
+	case 25:
+		return errorResponse{"Resolution is awaiting its dispute window. Try again after it closes.", http.StatusBadRequest}
+	case 24:
+		return errorResponse{"The claim window for this market has closed.", http.StatusBadRequest}
+	case 23:
+		return errorResponse{"No oracle transfer is pending.", http.StatusBadRequest}
+	case 22:
+		return errorResponse{"Invalid fee. Must be between 0% and 100%.", http.StatusBadRequest}
+	case 21:
+		return errorResponse{"Market is not cancelled. Refunds are only available on cancelled markets.", http.StatusBadRequest}
+	case 20:
+		return errorResponse{"Market has been cancelled. Use refund to recover your collateral.", http.StatusConflict}
+	case 19:
+		return errorResponse{"Trade is too large for this market's liquidity.", http.StatusBadRequest}
+	case 18:
+		return errorResponse{"Token is not accepted as collateral by this market.", http.StatusBadRequest}
+	case 17:
+		return errorResponse{"Trading is paused on this market.", http.StatusConflict}
+	case 16:
+		return errorResponse{"Invalid market configuration.", http.StatusBadRequest}
 	case 15:
 		return errorResponse{"Insufficient pool balance.", http.StatusBadRequest}
 	case 14: